@@ -13,10 +13,16 @@ cargo build
 
 ## Running Tests
 ```bash
-cargo test --features container
+cargo test --all-features
 ```
 >*"sham-wow!"*
 
+A few of the examples run against a real CouchDB in Docker. They're skipped unless you ask for them:
+```bash
+RUSTDOCFLAGS="--cfg docker_tests" cargo test --doc
+```
+Set `COUCHDB_IMAGE` to run them against an image other than `couchdb:2`.
+
 ## Viewing Documentation
 ```bash
 cargo doc --open
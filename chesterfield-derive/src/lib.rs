@@ -0,0 +1,164 @@
+//! Derive macros for [chesterfield](https://docs.rs/chesterfield).
+//!
+//! You shouldn't need to depend on this crate directly- enable the `derive` feature of
+//! chesterfield instead.
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, spanned::Spanned, Data, DeriveInput, Error, Fields, Member};
+
+/// Derive `chesterfield::Document` for a struct.
+///
+/// The id and revision fields are found by name (`id` or `_id`, and `rev` or `_rev`), or
+/// can be marked with `#[document(id)]` and `#[document(rev)]`. Similarly, a `bool` field
+/// may hold the deleted flag, and a `serde_json::Value` field the attachments- either
+/// named `_deleted` and `_attachments`, or marked with `#[document(deleted)]` and
+/// `#[document(attachments)]`. (Fields which are merely called `deleted` or `attachments`
+/// are ordinary fields.)
+///
+/// The id and revision fields may be optional (`Option<DocId>`, `Option<Revision>`).
+#[proc_macro_derive(Document, attributes(document))]
+pub fn derive_document(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// The fields which map onto document metadata
+#[derive(Default)]
+struct Roles {
+    id: Option<Member>,
+    rev: Option<Member>,
+    deleted: Option<Member>,
+    attachments: Option<Member>,
+}
+
+fn expand(input: &DeriveInput) -> Result<TokenStream2, Error> {
+    let roles = roles(input)?;
+
+    let id = roles.id.ok_or_else(|| {
+        Error::new(
+            input.ident.span(),
+            "no id field- name it `id` or mark it with #[document(id)]",
+        )
+    })?;
+    let rev = roles.rev.ok_or_else(|| {
+        Error::new(
+            input.ident.span(),
+            "no revision field- name it `rev` or mark it with #[document(rev)]",
+        )
+    })?;
+
+    let deleted = roles.deleted.map(|deleted| {
+        quote! {
+            fn deleted(&self) -> bool {
+                ::chesterfield::__private::deleted(&self.#deleted)
+            }
+        }
+    });
+    let attachments = roles.attachments.map(|attachments| {
+        quote! {
+            fn attachments(&self) -> ::std::option::Option<&::chesterfield::__private::Value> {
+                ::chesterfield::__private::attachments(&self.#attachments)
+            }
+        }
+    });
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::chesterfield::Document for #name #ty_generics #where_clause {
+            fn id(&self) -> ::std::option::Option<&::chesterfield::DocId> {
+                ::chesterfield::__private::id(&self.#id)
+            }
+
+            fn set_id(&mut self, id: ::chesterfield::DocId) {
+                ::chesterfield::__private::set_id(&mut self.#id, id)
+            }
+
+            fn rev(&self) -> ::std::option::Option<&::chesterfield::Revision> {
+                ::chesterfield::__private::rev(&self.#rev)
+            }
+
+            fn set_rev(&mut self, rev: ::chesterfield::Revision) {
+                ::chesterfield::__private::set_rev(&mut self.#rev, rev)
+            }
+
+            #deleted
+
+            #attachments
+        }
+    })
+}
+
+fn roles(input: &DeriveInput) -> Result<Roles, Error> {
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => {
+            return Err(Error::new(
+                input.span(),
+                "Document can only be derived for structs",
+            ))
+        }
+    };
+    let fields = match fields {
+        Fields::Named(fields) => &fields.named,
+        _ => {
+            return Err(Error::new(
+                fields.span(),
+                "Document can only be derived for structs with named fields",
+            ))
+        }
+    };
+
+    let mut marked = Roles::default();
+    let mut named = Roles::default();
+
+    for field in fields {
+        let ident = field.ident.clone().expect("named fields have names");
+
+        for attr in field.attrs.iter().filter(|a| a.path().is_ident("document")) {
+            attr.parse_nested_meta(|meta| {
+                let slot = if meta.path.is_ident("id") {
+                    &mut marked.id
+                } else if meta.path.is_ident("rev") {
+                    &mut marked.rev
+                } else if meta.path.is_ident("deleted") {
+                    &mut marked.deleted
+                } else if meta.path.is_ident("attachments") {
+                    &mut marked.attachments
+                } else {
+                    return Err(
+                        meta.error("expected one of `id`, `rev`, `deleted` or `attachments`")
+                    );
+                };
+                if slot.is_some() {
+                    return Err(meta.error("only one field can have this role"));
+                }
+                *slot = Some(Member::Named(ident.clone()));
+                Ok(())
+            })?;
+        }
+
+        let slot = match ident.to_string().as_str() {
+            "_id" | "id" => &mut named.id,
+            "_rev" | "rev" => &mut named.rev,
+            "_deleted" => &mut named.deleted,
+            "_attachments" => &mut named.attachments,
+            _ => continue,
+        };
+        slot.get_or_insert(Member::Named(ident));
+    }
+
+    Ok(Roles {
+        id: marked.id.or(named.id),
+        rev: marked.rev.or(named.rev),
+        deleted: marked.deleted.or(named.deleted),
+        attachments: marked.attachments.or(named.attachments),
+    })
+}
@@ -1,28 +1,53 @@
-#![feature(async_await)]
-
-use harbourmaster::{Container, Error, Protocol};
+use std::env;
+use std::io::Error;
 use std::net::TcpListener;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::process::Command;
+
+/// The image which is run, unless overridden by the `COUCHDB_IMAGE` environment variable.
+///
+/// CouchDB 3 refuses to start without an admin account, so the default is the last major
+/// version which runs in 'admin party' mode.
+pub const DEFAULT_IMAGE: &str = "couchdb:2";
+
+/// How long to wait for CouchDB to start answering requests
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(60);
 
 pub struct CouchDbContainer {
-    container: Container,
+    id: String,
     host_port: u16,
 }
 
 impl CouchDbContainer {
+    /// Start a CouchDB container with the `docker` CLI, and wait until it's ready.
     pub async fn new() -> Result<Self, Error> {
+        let image = env::var("COUCHDB_IMAGE").unwrap_or_else(|_| DEFAULT_IMAGE.to_string());
         let host_port = get_unused_port()?;
-        let container = Container::builder("couchdb")
-            .pull_on_build(true)
-            .name("couchdb")
-            .slug_length(6)
-            .expose(5984, host_port, Protocol::Tcp)
-            .build()
+        let output = Command::new("docker")
+            .arg("run")
+            .arg("--detach")
+            .arg("--rm")
+            .args(["--publish", &format!("{}:5984/tcp", host_port)])
+            .arg(&image)
+            .output()
             .await?;
 
-        Ok(Self {
-            container,
-            host_port,
-        })
+        if !output.status.success() {
+            return Err(Error::other(
+                String::from_utf8_lossy(&output.stderr).into_owned(),
+            ));
+        }
+
+        let id = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+        let container = Self { id, host_port };
+
+        if let Err(e) = container.wait_until_ready().await {
+            container.delete().await?;
+            return Err(e);
+        }
+        Ok(container)
     }
 
     pub fn port(&self) -> u16 {
@@ -30,9 +55,45 @@ impl CouchDbContainer {
     }
 
     pub async fn delete(self) -> Result<(), Error> {
-        self.container.delete().await?;
+        Command::new("docker")
+            .args(["rm", "--force", &self.id])
+            .output()
+            .await?;
         Ok(())
     }
+
+    /// Poll the server until it answers `GET /` successfully
+    async fn wait_until_ready(&self) -> Result<(), Error> {
+        let deadline = tokio::time::Instant::now() + STARTUP_TIMEOUT;
+        loop {
+            if self.is_ready().await {
+                return Ok(());
+            }
+            if tokio::time::Instant::now() >= deadline {
+                return Err(Error::new(
+                    std::io::ErrorKind::TimedOut,
+                    format!("CouchDB didn't start within {:?}", STARTUP_TIMEOUT),
+                ));
+            }
+            tokio::time::sleep(Duration::from_millis(250)).await;
+        }
+    }
+
+    async fn is_ready(&self) -> bool {
+        let mut stream = match TcpStream::connect(("localhost", self.host_port)).await {
+            Ok(stream) => stream,
+            Err(_) => return false,
+        };
+        let request = b"GET / HTTP/1.0\r\nHost: localhost\r\n\r\n";
+        if stream.write_all(request).await.is_err() {
+            return false;
+        }
+        let mut response = Vec::new();
+        if stream.read_to_end(&mut response).await.is_err() {
+            return false;
+        }
+        response.starts_with(b"HTTP/1.0 200") || response.starts_with(b"HTTP/1.1 200")
+    }
 }
 
 fn get_unused_port() -> Result<u16, std::io::Error> {
@@ -0,0 +1,904 @@
+//! A blocking CouchDB client.
+//!
+//! This mirrors the asynchronous API, but each request is driven to completion on
+//! an internal (single-threaded) tokio runtime. It's intended for command line tools,
+//! build scripts and other synchronous code that doesn't want to bring its own runtime.
+//!
+//! Requires the `blocking` feature.
+//!
+//! # Panics
+//! The blocking client must not be used from *within* an asynchronous runtime- doing
+//! so will panic. Use the asynchronous [Client](crate::Client) instead.
+//!
+//! # Example
+//! ```no_run
+//! use chesterfield::blocking::Client;
+//! use serde::Serialize;
+//!
+//! #[derive(Serialize)]
+//! struct MyCoolStruct {
+//!     field1: String,
+//!     field2: u32,
+//! }
+//!
+//! let client = Client::from_url_str("http://localhost:5984").unwrap();
+//! let database = client.database("items").unwrap();
+//!
+//! database.create().send().unwrap();
+//!
+//! let doc = MyCoolStruct {
+//!     field1: String::from("some string"),
+//!     field2: 42,
+//! };
+//!
+//! let response = database.insert(&doc, None).send().unwrap();
+//! ```
+
+use crate::database::{CopyResponse, DeleteResponse, GetResponse, InsertResponse, UpdateResponse};
+use crate::transport::Transport;
+use crate::{
+    ActiveTask, ConfigSection, ConflictResolver, DatabaseInfo, DatabaseName, DbsInfo, DocId,
+    Document, Error, HeadResponse, IdGenerator, MetricFamily, NodeStats, Resolution, Revision,
+    SecurityObject, ServerInfo, SystemStats, UpStatus, Url, User,
+};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::runtime::Runtime;
+
+/// A blocking CouchDB client
+pub struct Client {
+    inner: crate::Client,
+    runtime: Arc<Runtime>,
+}
+
+impl Client {
+    /// Create a new blocking client.
+    ///
+    /// # Errors
+    /// This method fails if the TLS backend or the internal runtime fail to initialise
+    #[cfg(any(feature = "reqwest", feature = "hyper"))]
+    pub fn new(url: Url) -> Result<Self, Error> {
+        Ok(Client {
+            inner: crate::Client::new(url)?,
+            runtime: Arc::new(new_runtime()?),
+        })
+    }
+
+    /// Create a new blocking client from a URL string
+    ///
+    /// # Example
+    /// ```
+    /// use chesterfield::blocking::Client;
+    ///
+    /// let client = Client::from_url_str("http://localhost:5984").unwrap();
+    /// ```
+    ///
+    /// # Errors
+    /// This method fails if the TLS backend or the internal runtime fail to initialise,
+    /// or if the URL string cannot be parsed
+    #[cfg(any(feature = "reqwest", feature = "hyper"))]
+    pub fn from_url_str(url: impl AsRef<str>) -> Result<Self, Error> {
+        let url = Url::parse(url.as_ref())?;
+        Client::new(url)
+    }
+
+    /// Create a new blocking client which sends requests using a custom [Transport].
+    ///
+    /// # Errors
+    /// This method fails if the internal runtime fails to initialise
+    pub fn with_transport(url: Url, transport: impl Transport) -> Result<Self, Error> {
+        Ok(Client {
+            inner: crate::Client::with_transport(url, transport),
+            runtime: Arc::new(new_runtime()?),
+        })
+    }
+
+    /// Generate the ids of documents inserted without one on the client.
+    ///
+    /// See [Client::with_id_generator](crate::Client::with_id_generator).
+    pub fn with_id_generator(self, ids: IdGenerator) -> Self {
+        Client {
+            inner: self.inner.with_id_generator(ids),
+            runtime: self.runtime,
+        }
+    }
+
+    /// Create an interface to a CouchDB database.
+    ///
+    /// # Errors
+    /// This method fails if the name is not a valid CouchDB database name
+    pub fn database<N>(&self, name: N) -> Result<Database, Error>
+    where
+        N: TryInto<DatabaseName>,
+        Error: From<N::Error>,
+    {
+        Ok(Database {
+            inner: self.inner.database(name)?,
+            runtime: Arc::clone(&self.runtime),
+        })
+    }
+
+    /// Retrieve the server's version, vendor and enabled features.
+    pub fn server_info(&self) -> Result<ServerInfo, Error> {
+        self.runtime.block_on(self.inner.server_info())
+    }
+
+    /// The server's version, vendor and enabled features, retrieved the first time they're
+    /// needed and cached from then on.
+    ///
+    /// See [Client::detect_server](crate::Client::detect_server).
+    pub fn detect_server(&self) -> Result<&ServerInfo, Error> {
+        self.runtime.block_on(self.inner.detect_server())
+    }
+
+    /// List the databases on the server.
+    ///
+    /// See [Client::all_dbs](crate::Client::all_dbs).
+    pub fn all_dbs(&self) -> AllDbsRequest {
+        AllDbsRequest {
+            inner: self.inner.all_dbs(),
+            runtime: Arc::clone(&self.runtime),
+        }
+    }
+
+    /// Retrieve information about several databases at once.
+    ///
+    /// See [Client::dbs_info](crate::Client::dbs_info).
+    pub fn dbs_info<I, S>(&self, names: I) -> Result<Vec<DbsInfo>, Error>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.runtime.block_on(self.inner.dbs_info(names))
+    }
+
+    /// Check whether the server is up, and able to serve requests.
+    pub fn up(&self) -> Result<UpStatus, Error> {
+        self.runtime.block_on(self.inner.up())
+    }
+
+    /// Generate universally unique identifiers on the server.
+    pub fn uuids(&self, count: usize) -> Result<Vec<String>, Error> {
+        self.runtime.block_on(self.inner.uuids(count))
+    }
+
+    /// List the tasks which are running on the server.
+    pub fn active_tasks(&self) -> Result<Vec<ActiveTask>, Error> {
+        self.runtime.block_on(self.inner.active_tasks())
+    }
+
+    /// The configuration of the node which receives the requests.
+    ///
+    /// See [Client::config](crate::Client::config).
+    pub fn config(&self) -> NodeConfig {
+        NodeConfig {
+            inner: self.inner.config(),
+            runtime: Arc::clone(&self.runtime),
+        }
+    }
+
+    /// The configuration of a named node in the cluster.
+    ///
+    /// # Errors
+    /// This method fails if the name is empty
+    pub fn node_config(&self, node: &str) -> Result<NodeConfig, Error> {
+        Ok(NodeConfig {
+            inner: self.inner.node_config(node)?,
+            runtime: Arc::clone(&self.runtime),
+        })
+    }
+
+    /// A named node in the cluster.
+    ///
+    /// # Errors
+    /// This method fails if the name is empty
+    pub fn node(&self, name: &str) -> Result<Node, Error> {
+        Ok(Node {
+            inner: self.inner.node(name)?,
+            runtime: Arc::clone(&self.runtime),
+        })
+    }
+
+    /// The node which receives the requests.
+    pub fn local_node(&self) -> Node {
+        Node {
+            inner: self.inner.local_node(),
+            runtime: Arc::clone(&self.runtime),
+        }
+    }
+
+    /// Retrieve the statistics of the node which receives the requests.
+    ///
+    /// See [Client::node_stats](crate::Client::node_stats).
+    pub fn node_stats(&self) -> Result<NodeStats, Error> {
+        self.runtime.block_on(self.inner.node_stats())
+    }
+
+    /// An interface to the users in the `_users` database.
+    ///
+    /// See [Client::users](crate::Client::users).
+    pub fn users(&self) -> Users {
+        Users {
+            inner: self.inner.users(),
+            runtime: Arc::clone(&self.runtime),
+        }
+    }
+}
+
+fn new_runtime() -> Result<Runtime, Error> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    Ok(runtime)
+}
+
+/// Blocking interface for interacting with a specific CouchDB database.
+///
+/// See [Database](crate::Database) for details.
+pub struct Database {
+    inner: crate::Database,
+    runtime: Arc<Runtime>,
+}
+
+impl Database {
+    /// Create the database.
+    ///
+    /// See [Database::create](crate::Database::create).
+    pub fn create(&self) -> CreateDatabaseRequest {
+        CreateDatabaseRequest {
+            inner: self.inner.create(),
+            runtime: Arc::clone(&self.runtime),
+        }
+    }
+
+    /// Delete the database, and every document in it.
+    pub fn delete_database(&self) -> Result<(), Error> {
+        self.runtime.block_on(self.inner.delete_database())
+    }
+
+    /// Retrieve information about the database.
+    ///
+    /// See [Database::info](crate::Database::info).
+    pub fn info(&self) -> Result<DatabaseInfo, Error> {
+        self.runtime.block_on(self.inner.info())
+    }
+
+    /// Retrieve the database's security object.
+    pub fn security(&self) -> Result<SecurityObject, Error> {
+        self.runtime.block_on(self.inner.security())
+    }
+
+    /// Replace the database's security object.
+    pub fn set_security(&self, security: &SecurityObject) -> Result<(), Error> {
+        self.runtime.block_on(self.inner.set_security(security))
+    }
+
+    /// Modify the database's security object. This isn't atomic.
+    ///
+    /// See [Database::modify_security](crate::Database::modify_security).
+    pub fn modify_security<F>(&self, modify: F) -> Result<SecurityObject, Error>
+    where
+        F: FnOnce(&mut SecurityObject),
+    {
+        self.runtime.block_on(self.inner.modify_security(modify))
+    }
+
+    /// Compact the database.
+    ///
+    /// See [Database::compact](crate::Database::compact).
+    pub fn compact(&self) -> CompactRequest {
+        CompactRequest {
+            inner: self.inner.compact(),
+            runtime: Arc::clone(&self.runtime),
+        }
+    }
+
+    /// Compact the view indexes of a design document.
+    ///
+    /// See [Database::compact_design](crate::Database::compact_design).
+    pub fn compact_design(&self, design: &str) -> CompactRequest {
+        CompactRequest {
+            inner: self.inner.compact_design(design),
+            runtime: Arc::clone(&self.runtime),
+        }
+    }
+
+    /// Remove the view indexes which are no longer used by any design document.
+    pub fn view_cleanup(&self) -> Result<(), Error> {
+        self.runtime.block_on(self.inner.view_cleanup())
+    }
+
+    /// Ask CouchDB to write any recent changes to disk.
+    ///
+    /// See [Database::ensure_full_commit](crate::Database::ensure_full_commit).
+    pub fn ensure_full_commit(&self) -> Result<(), Error> {
+        self.runtime.block_on(self.inner.ensure_full_commit())
+    }
+
+    /// Check whether the database exists
+    pub fn exists(&self) -> Result<bool, Error> {
+        self.runtime.block_on(self.inner.exists())
+    }
+
+    /// Retrieve a document from a database.
+    pub fn get<I>(&self, id: I) -> GetRequest
+    where
+        I: TryInto<DocId>,
+        Error: From<I::Error>,
+    {
+        GetRequest {
+            inner: self.inner.get(id),
+            runtime: Arc::clone(&self.runtime),
+        }
+    }
+
+    /// Check whether a document exists, without downloading it.
+    ///
+    /// See [Database::head](crate::Database::head).
+    pub fn head<I>(&self, id: I) -> Result<Option<HeadResponse>, Error>
+    where
+        I: TryInto<DocId>,
+        Error: From<I::Error>,
+    {
+        self.runtime.block_on(self.inner.head(id))
+    }
+
+    /// The current revision of a document, or `None` if it doesn't exist.
+    ///
+    /// See [Database::current_rev](crate::Database::current_rev).
+    pub fn current_rev<I>(&self, id: I) -> Result<Option<Revision>, Error>
+    where
+        I: TryInto<DocId>,
+        Error: From<I::Error>,
+    {
+        self.runtime.block_on(self.inner.current_rev(id))
+    }
+
+    /// Insert pretty much anything into the database.
+    ///
+    /// See [Database::insert](crate::Database::insert).
+    pub fn insert<'a, T: Serialize>(
+        &self,
+        document: &'a T,
+        id: impl Into<Option<String>>,
+    ) -> InsertRequest<'a, T> {
+        InsertRequest {
+            inner: self.inner.insert(document, id),
+            runtime: Arc::clone(&self.runtime),
+        }
+    }
+
+    /// Generate an id for a new document.
+    ///
+    /// See [Database::generate_id](crate::Database::generate_id).
+    pub fn generate_id(&self) -> Result<DocId, Error> {
+        self.runtime.block_on(self.inner.generate_id())
+    }
+
+    /// Update an existing document in the database.
+    ///
+    /// See [Database::update](crate::Database::update).
+    pub fn update<'a, T, I, R>(&self, document: &'a T, id: I, rev: R) -> UpdateRequest<'a, T>
+    where
+        T: Serialize,
+        I: TryInto<DocId>,
+        Error: From<I::Error>,
+        R: TryInto<Revision>,
+        Error: From<R::Error>,
+    {
+        UpdateRequest {
+            inner: self.inner.update(document, id, rev),
+            runtime: Arc::clone(&self.runtime),
+        }
+    }
+
+    /// Delete an existing document in the database.
+    pub fn delete<I, R>(&self, id: I, rev: R) -> DeleteRequest
+    where
+        I: TryInto<DocId>,
+        Error: From<I::Error>,
+        R: TryInto<Revision>,
+        Error: From<R::Error>,
+    {
+        DeleteRequest {
+            inner: self.inner.delete(id, rev),
+            runtime: Arc::clone(&self.runtime),
+        }
+    }
+
+    /// Copy a document to a new id.
+    ///
+    /// See [Database::copy](crate::Database::copy).
+    pub fn copy<S, D>(&self, source: S, destination: D) -> CopyRequest
+    where
+        S: TryInto<DocId>,
+        Error: From<S::Error>,
+        D: TryInto<DocId>,
+        Error: From<D::Error>,
+    {
+        CopyRequest {
+            inner: self.inner.copy(source, destination),
+            runtime: Arc::clone(&self.runtime),
+        }
+    }
+
+    /// Save a [Document], inserting or updating it as appropriate.
+    ///
+    /// See [Database::save](crate::Database::save).
+    pub fn save<D: Document>(&self, document: &mut D) -> Result<(), Error> {
+        self.runtime.block_on(self.inner.save(document))
+    }
+
+    /// Modify a document, retrying if somebody else modifies it at the same time.
+    ///
+    /// See [Database::modify](crate::Database::modify).
+    pub fn modify<T, F, I>(&self, id: I, modify: F) -> ModifyRequest<T, F>
+    where
+        T: Serialize + DeserializeOwned,
+        F: FnMut(&mut T),
+        I: TryInto<DocId>,
+        Error: From<I::Error>,
+    {
+        ModifyRequest {
+            inner: self.inner.modify(id, modify),
+            runtime: Arc::clone(&self.runtime),
+        }
+    }
+
+    /// Resolve any conflicts between the revisions of a document.
+    ///
+    /// See [Database::resolve_conflicts](crate::Database::resolve_conflicts).
+    pub fn resolve_conflicts<I, R>(&self, id: I, resolver: R) -> Result<Option<Resolution>, Error>
+    where
+        I: TryInto<DocId>,
+        Error: From<I::Error>,
+        R: ConflictResolver,
+    {
+        self.runtime
+            .block_on(self.inner.resolve_conflicts(id, resolver))
+    }
+}
+
+/// Forward builder methods to the wrapped asynchronous request
+macro_rules! forward {
+    ($($(#[$meta:meta])* $method:ident($($arg:ident: $ty:ty),*);)*) => {
+        $(
+            $(#[$meta])*
+            pub fn $method(mut self, $($arg: $ty),*) -> Self {
+                self.inner = self.inner.$method($($arg),*);
+                self
+            }
+        )*
+    };
+}
+
+/// A blocking request to list the databases on the server.
+///
+/// See [AllDbsRequest](crate::AllDbsRequest) for details of the options.
+pub struct AllDbsRequest {
+    inner: crate::AllDbsRequest,
+    runtime: Arc<Runtime>,
+}
+
+impl AllDbsRequest {
+    forward! {
+        /// List the databases in reverse order.
+        descending(value: bool);
+        /// Start listing at the given name (inclusive)
+        start_key(name: impl AsRef<str>);
+        /// Stop listing at the given name (inclusive)
+        end_key(name: impl AsRef<str>);
+        /// List at most this many databases
+        limit(limit: usize);
+        /// Skip this many databases before listing
+        skip(skip: usize);
+    }
+
+    /// Send the request, and block until the names of the databases are received.
+    pub fn send(self) -> Result<Vec<String>, Error> {
+        self.runtime.block_on(self.inner.send())
+    }
+}
+
+/// A blocking request to create a database.
+///
+/// See [CreateDatabaseRequest](crate::CreateDatabaseRequest) for details of the options.
+pub struct CreateDatabaseRequest {
+    inner: crate::CreateDatabaseRequest,
+    runtime: Arc<Runtime>,
+}
+
+impl CreateDatabaseRequest {
+    forward! {
+        /// The number of shards to split the database into.
+        q(shards: u32);
+        /// The number of replicas of each shard.
+        n(replicas: u32);
+        /// Create a partitioned database (CouchDB 3 and later).
+        partitioned(value: bool);
+    }
+
+    /// Send the request, and block until the database has been created.
+    pub fn send(self) -> Result<(), Error> {
+        self.runtime.block_on(self.inner.send())
+    }
+}
+
+/// A blocking request to retrieve a document from a CouchDB database.
+///
+/// See [GetRequest](crate::GetRequest) for details of the options.
+pub struct GetRequest {
+    inner: crate::GetRequest,
+    runtime: Arc<Runtime>,
+}
+
+impl GetRequest {
+    forward! {
+        /// Includes attachments bodies in response.
+        attachments(value: bool);
+        /// Includes attachment encoding information in response.
+        attachment_encoding_info(value: bool);
+        /// Includes only the attachments since the specified revisions.
+        attachments_since(revisions: impl IntoIterator<Item = impl Into<Revision>>);
+        /// Includes information about conflicts in document.
+        conflicts(value: bool);
+        /// Includes information about deleted conflict revisions.
+        deleted_conflicts(value: bool);
+        /// Forces retrieving latest 'leaf' revision, no matter which revision
+        /// was requested.
+        latest(value: bool);
+        /// Includes last 'update sequence' for this document.
+        local_sequence(value: bool);
+        /// This is the same as setting all of 'conflicts',
+        /// 'deleted_conflicts', and 'revisions_info' to true.
+        meta(value: bool);
+        /// retrieve documents of specified leaf revisions.
+        open_revisions(revisions: impl IntoIterator<Item = impl Into<Revision>>);
+        /// retrieve documents of all leaf revisions.
+        all_open_revisions(value: bool);
+        /// retrieve document of specified revision.
+        revision(revision: impl Into<Revision>);
+        /// Retrieve list of known document revisions.
+        revisions(value: bool);
+        /// included detailed information for all know document revisions.
+        revisions_info(value: bool);
+    }
+
+    /// Only retrieve the document if its revision is no longer the given one.
+    pub fn if_none_match<R>(mut self, rev: R) -> Self
+    where
+        R: TryInto<Revision>,
+        Error: From<R::Error>,
+    {
+        self.inner = self.inner.if_none_match(rev);
+        self
+    }
+
+    /// Send the request, and block until the response is received.
+    pub fn send<T: DeserializeOwned>(self) -> Result<GetResponse<T>, Error> {
+        self.runtime.block_on(self.inner.send())
+    }
+}
+
+/// A blocking request to insert a document into the database
+pub struct InsertRequest<'a, T>
+where
+    T: Serialize,
+{
+    inner: crate::InsertRequest<'a, T>,
+    runtime: Arc<Runtime>,
+}
+
+impl<'a, T> InsertRequest<'a, T>
+where
+    T: Serialize,
+{
+    /// Send the request, and block until the response is received.
+    pub fn send(self) -> Result<InsertResponse, Error> {
+        self.runtime.block_on(self.inner.send())
+    }
+}
+
+/// A blocking request to update an existing document.
+pub struct UpdateRequest<'a, T>
+where
+    T: Serialize,
+{
+    inner: crate::UpdateRequest<'a, T>,
+    runtime: Arc<Runtime>,
+}
+
+impl<'a, T> UpdateRequest<'a, T>
+where
+    T: Serialize,
+{
+    /// Send the request, and block until the response is received.
+    pub fn send(self) -> Result<UpdateResponse, Error> {
+        self.runtime.block_on(self.inner.send())
+    }
+}
+
+/// A blocking request to delete an existing document.
+pub struct DeleteRequest {
+    inner: crate::database::DeleteRequest,
+    runtime: Arc<Runtime>,
+}
+
+impl DeleteRequest {
+    /// Send the request, and block until the response is received.
+    pub fn send(self) -> Result<DeleteResponse, Error> {
+        self.runtime.block_on(self.inner.send())
+    }
+}
+
+/// A blocking request to copy a document.
+///
+/// See [CopyRequest](crate::CopyRequest) for details of the options.
+pub struct CopyRequest {
+    inner: crate::CopyRequest,
+    runtime: Arc<Runtime>,
+}
+
+impl CopyRequest {
+    /// Copy a specific revision of the source document, rather than the latest
+    pub fn rev<R>(mut self, rev: R) -> Self
+    where
+        R: TryInto<Revision>,
+        Error: From<R::Error>,
+    {
+        self.inner = self.inner.rev(rev);
+        self
+    }
+
+    /// Overwrite the given revision of an existing destination document.
+    pub fn destination_rev<R>(mut self, rev: R) -> Self
+    where
+        R: TryInto<Revision>,
+        Error: From<R::Error>,
+    {
+        self.inner = self.inner.destination_rev(rev);
+        self
+    }
+
+    /// Send the request, and block until the response is received.
+    pub fn send(self) -> Result<CopyResponse, Error> {
+        self.runtime.block_on(self.inner.send())
+    }
+}
+
+/// A blocking read-modify-write of a single document, which retries on conflict.
+///
+/// See [ModifyRequest](crate::ModifyRequest) for details of the options.
+pub struct ModifyRequest<T, F> {
+    inner: crate::ModifyRequest<T, F>,
+    runtime: Arc<Runtime>,
+}
+
+impl<T, F> ModifyRequest<T, F>
+where
+    T: Serialize + DeserializeOwned,
+    F: FnMut(&mut T),
+{
+    forward! {
+        /// The number of times to retry after a conflict, before giving up.
+        retries(retries: u32);
+        /// The delay before the first retry. The delay doubles with each subsequent retry.
+        backoff(backoff: Duration);
+        /// If the document doesn't exist, create it from the given function (and then
+        /// modify it) rather than failing.
+        create_with(create: impl Fn() -> T + Send + Sync + 'static);
+    }
+
+    /// If the document doesn't exist, create it from its default value (and then modify
+    /// it) rather than failing.
+    pub fn create_if_missing(mut self) -> Self
+    where
+        T: Default + 'static,
+    {
+        self.inner = self.inner.create_if_missing();
+        self
+    }
+
+    /// Send the request, and block until the document has been modified.
+    pub fn send(self) -> Result<Revision, Error> {
+        self.runtime.block_on(self.inner.send())
+    }
+}
+
+/// A blocking request to compact a database, or the view indexes of a design document.
+///
+/// See [CompactRequest](crate::CompactRequest) for details of the options.
+pub struct CompactRequest {
+    inner: crate::CompactRequest,
+    runtime: Arc<Runtime>,
+}
+
+impl CompactRequest {
+    forward! {
+        /// Wait for compaction to finish, for at most this long.
+        wait(timeout: Duration);
+        /// How often to check whether compaction has finished, while waiting.
+        poll_interval(interval: Duration);
+    }
+
+    /// Send the request, and block until the response is received (or, if waiting, until
+    /// compaction has finished).
+    pub fn send(self) -> Result<(), Error> {
+        self.runtime.block_on(self.inner.send())
+    }
+}
+
+/// A blocking interface to the users in the `_users` database.
+///
+/// See [Users](crate::Users) for details.
+pub struct Users {
+    inner: crate::Users,
+    runtime: Arc<Runtime>,
+}
+
+impl Users {
+    /// Create a new user, returning the revision of their document.
+    pub fn create(&self, user: &User) -> Result<Revision, Error> {
+        self.runtime.block_on(self.inner.create(user))
+    }
+
+    /// Retrieve a user.
+    pub fn get(&self, name: &str) -> Result<User, Error> {
+        self.runtime.block_on(self.inner.get(name))
+    }
+
+    /// Replace a user's password, returning the new revision of their document.
+    pub fn set_password(&self, name: &str, password: &str) -> Result<Revision, Error> {
+        self.runtime
+            .block_on(self.inner.set_password(name, password))
+    }
+
+    /// Replace a user's roles, returning the new revision of their document.
+    pub fn set_roles<I, S>(&self, name: &str, roles: I) -> Result<Revision, Error>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.runtime.block_on(self.inner.set_roles(name, roles))
+    }
+
+    /// Give a user a role, returning the new revision of their document.
+    pub fn add_role(&self, name: &str, role: &str) -> Result<Revision, Error> {
+        self.runtime.block_on(self.inner.add_role(name, role))
+    }
+
+    /// Take a role away from a user, returning the new revision of their document.
+    pub fn remove_role(&self, name: &str, role: &str) -> Result<Revision, Error> {
+        self.runtime.block_on(self.inner.remove_role(name, role))
+    }
+
+    /// Modify a user, retrying if somebody else modifies them at the same time.
+    pub fn modify<F>(&self, name: &str, modify: F) -> Result<Revision, Error>
+    where
+        F: FnMut(&mut User),
+    {
+        self.runtime.block_on(self.inner.modify(name, modify))
+    }
+
+    /// Delete a user.
+    pub fn delete(&self, name: &str) -> Result<(), Error> {
+        self.runtime.block_on(self.inner.delete(name))
+    }
+}
+
+/// A blocking interface to the configuration of a node.
+///
+/// See [NodeConfig](crate::NodeConfig) for details.
+pub struct NodeConfig {
+    inner: crate::NodeConfig,
+    runtime: Arc<Runtime>,
+}
+
+impl NodeConfig {
+    /// Retrieve the whole configuration, by section.
+    pub fn all(&self) -> Result<BTreeMap<String, ConfigSection>, Error> {
+        self.runtime.block_on(self.inner.all())
+    }
+
+    /// Retrieve one section of the configuration.
+    pub fn section(&self, section: &str) -> Result<ConfigSection, Error> {
+        self.runtime.block_on(self.inner.section(section))
+    }
+
+    /// Retrieve a single setting, if it's set.
+    pub fn get(&self, section: &str, key: &str) -> Result<Option<String>, Error> {
+        self.runtime.block_on(self.inner.get(section, key))
+    }
+
+    /// Change a setting, returning its previous value (if it was set).
+    pub fn set(&self, section: &str, key: &str, value: &str) -> Result<Option<String>, Error> {
+        self.runtime.block_on(self.inner.set(section, key, value))
+    }
+
+    /// Remove a setting, returning its previous value.
+    pub fn delete(&self, section: &str, key: &str) -> Result<String, Error> {
+        self.runtime.block_on(self.inner.delete(section, key))
+    }
+
+    /// Reload the configuration from the node's `.ini` files.
+    pub fn reload(&self) -> Result<(), Error> {
+        self.runtime.block_on(self.inner.reload())
+    }
+}
+
+/// A blocking interface to a node in the cluster.
+///
+/// See [Node](crate::Node) for details.
+pub struct Node {
+    inner: crate::Node,
+    runtime: Arc<Runtime>,
+}
+
+impl Node {
+    /// The name of the node, or `_local` for whichever node receives the requests
+    pub fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    /// The configuration of the node
+    pub fn config(&self) -> NodeConfig {
+        NodeConfig {
+            inner: self.inner.config(),
+            runtime: Arc::clone(&self.runtime),
+        }
+    }
+
+    /// Retrieve the node's statistics.
+    pub fn stats(&self) -> Result<NodeStats, Error> {
+        self.runtime.block_on(self.inner.stats())
+    }
+
+    /// Retrieve the state of the node's Erlang VM.
+    pub fn system(&self) -> Result<SystemStats, Error> {
+        self.runtime.block_on(self.inner.system())
+    }
+
+    /// Retrieve the node's statistics in the Prometheus text format.
+    pub fn prometheus_text(&self) -> Result<String, Error> {
+        self.runtime.block_on(self.inner.prometheus_text())
+    }
+
+    /// Retrieve the node's statistics in the Prometheus text format, parsed into metric
+    /// families.
+    pub fn prometheus(&self) -> Result<Vec<MetricFamily>, Error> {
+        self.runtime.block_on(self.inner.prometheus())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Client;
+    use crate::transport::{BoxFuture, Request, Response, Transport};
+    use crate::{Error, Url};
+
+    /// A test double which reports every database as missing
+    struct NotFound;
+
+    impl Transport for NotFound {
+        fn execute(&self, _request: Request) -> BoxFuture<'_, Result<Response, Error>> {
+            Box::pin(async {
+                let mut response = http::Response::new(Vec::new());
+                *response.status_mut() = http::StatusCode::NOT_FOUND;
+                Ok(response)
+            })
+        }
+    }
+
+    #[test]
+    fn drives_requests_to_completion() {
+        let url = Url::parse("http://localhost:5984").unwrap();
+        let client = Client::with_transport(url, NotFound).unwrap();
+        let database = client.database("items").unwrap();
+
+        assert!(!database.exists().unwrap());
+    }
+}
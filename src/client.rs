@@ -1,13 +1,25 @@
 use crate::database::Database;
-use crate::{Error, UrlError};
-use reqwest::Url;
-use std::str::FromStr;
-use std::sync::Arc;
+use crate::path;
+use crate::transport::{RequestBuilder, Transport};
+use crate::{DatabaseName, Error, Url};
+use crate::{IdGenerator, ServerInfo};
+use http::Method;
+use std::convert::TryInto;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
 
 /// An asynchronous CouchDB client
 pub struct Client {
     url: Url,
-    http_client: Arc<reqwest::r#async::Client>,
+    transport: Arc<dyn Transport>,
+    server: Arc<Server>,
+    ids: Option<IdGenerator>,
+}
+
+/// The server a client was created for, shared by every client derived from it
+struct Server {
+    url: Url,
+    info: OnceLock<ServerInfo>,
 }
 
 impl Client {
@@ -22,10 +34,39 @@ impl Client {
     ///
     /// # Errors
     /// This method fails if the TLS backend fails to initialise
+    #[cfg(any(feature = "reqwest", feature = "hyper"))]
     pub fn new(url: Url) -> Result<Self, Error> {
-        let http_client = Arc::new(reqwest::r#async::ClientBuilder::new().build()?);
+        let transport = crate::transport::DefaultTransport::new()?;
 
-        Ok(Client { url, http_client })
+        Ok(Client::with_transport(url, transport))
+    }
+
+    /// Create a new asynchronous client which sends requests using a custom [Transport].
+    ///
+    /// See the [transport](crate::transport) module for details.
+    pub fn with_transport(url: Url, transport: impl Transport) -> Self {
+        let server = Arc::new(Server {
+            url: url.clone(),
+            info: OnceLock::new(),
+        });
+
+        Client {
+            url,
+            transport: Arc::new(transport),
+            server,
+            ids: None,
+        }
+    }
+
+    /// Generate the ids of documents inserted without one on the client, rather than
+    /// letting the server assign them.
+    ///
+    /// Such documents are then inserted with `PUT` rather than `POST`. Each insert still
+    /// generates a new id, so to retry one safely, use an id from
+    /// [Database::generate_id](crate::Database::generate_id). See [IdGenerator] for details.
+    pub fn with_id_generator(mut self, ids: IdGenerator) -> Self {
+        self.ids = Some(ids);
+        self
     }
 
     /// Create a new asynchronous client from a URL string
@@ -39,16 +80,61 @@ impl Client {
     ///
     /// # Errors
     /// This method fails if the TLS backend fails to initialise or if the URL string cannot be parsed
+    #[cfg(any(feature = "reqwest", feature = "hyper"))]
     pub fn from_url_str(url: impl AsRef<str>) -> Result<Self, Error> {
         let url = Url::parse(url.as_ref())?;
         Client::new(url)
     }
 
-    pub(crate) fn join(&self, name: impl AsRef<str>) -> Result<Self, UrlError> {
-        let url = self.url.join(&format!("{}/", name.as_ref()))?;
-        let http_client = Arc::clone(&self.http_client);
+    /// The base URL of the server
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// Create a new client pointing at a sub-path of this one.
+    ///
+    /// Each segment is percent-encoded as a single path segment.
+    pub(crate) fn join<I, S>(&self, segments: I) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let url = path::join(&self.url, segments)?;
+        let transport = Arc::clone(&self.transport);
+        let server = Arc::clone(&self.server);
+        let ids = self.ids.clone();
+
+        Ok(Client {
+            url,
+            transport,
+            server,
+            ids,
+        })
+    }
+
+    /// A client pointing at the server this one was created for
+    pub(crate) fn root(&self) -> Client {
+        Client {
+            url: self.server.url.clone(),
+            transport: Arc::clone(&self.transport),
+            server: Arc::clone(&self.server),
+            ids: self.ids.clone(),
+        }
+    }
+
+    /// The server's welcome message, once it has been detected
+    pub(crate) fn detected(&self) -> &OnceLock<ServerInfo> {
+        &self.server.info
+    }
 
-        Ok(Client { url, http_client })
+    /// Wait for the given duration, using the transport's timer
+    pub(crate) async fn sleep(&self, duration: Duration) {
+        self.transport.sleep(duration).await
+    }
+
+    /// How to generate the ids of documents inserted without one, if not by the server
+    pub(crate) fn id_generator(&self) -> Option<&IdGenerator> {
+        self.ids.as_ref()
     }
 
     /// Create an interface to a CouchDB database.
@@ -61,42 +147,66 @@ impl Client {
     ///
     /// let database = client.database("some_collection").unwrap();
     /// ```
-    pub fn database(&self, name: impl AsRef<str>) -> Result<Database, UrlError> {
-        let client = self.join(name)?;
+    ///
+    /// # Errors
+    /// This method fails if the name is not a valid [DatabaseName]
+    pub fn database<N>(&self, name: N) -> Result<Database, Error>
+    where
+        N: TryInto<DatabaseName>,
+        Error: From<N::Error>,
+    {
+        let name = name.try_into()?;
+        let client = self.join([&name])?;
         Ok(Database::new(client))
     }
 
-    pub(crate) fn get(&self) -> reqwest::r#async::RequestBuilder {
-        self.http_client.get(self.url.clone())
+    fn request(&self, method: Method) -> RequestBuilder {
+        RequestBuilder::new(Arc::clone(&self.transport), method, self.url.clone())
+    }
+
+    pub(crate) fn get(&self) -> RequestBuilder {
+        self.request(Method::GET)
     }
 
-    pub(crate) fn post(&self) -> reqwest::r#async::RequestBuilder {
-        self.http_client.post(self.url.clone())
+    pub(crate) fn post(&self) -> RequestBuilder {
+        self.request(Method::POST)
     }
 
-    pub(crate) fn put(&self) -> reqwest::r#async::RequestBuilder {
-        self.http_client.put(self.url.clone())
+    pub(crate) fn put(&self) -> RequestBuilder {
+        self.request(Method::PUT)
     }
 
-    pub(crate) fn delete(&self) -> reqwest::r#async::RequestBuilder {
-        self.http_client.delete(self.url.clone())
+    pub(crate) fn delete(&self) -> RequestBuilder {
+        self.request(Method::DELETE)
     }
 
-    pub(crate) fn head(&self) -> reqwest::r#async::RequestBuilder {
-        self.http_client.head(self.url.clone())
+    pub(crate) fn head(&self) -> RequestBuilder {
+        self.request(Method::HEAD)
+    }
+
+    pub(crate) fn copy(&self) -> RequestBuilder {
+        self.request(Method::from_bytes(b"COPY").expect("COPY is a valid method"))
     }
 }
 
 impl From<&Client> for Client {
     fn from(client: &Client) -> Client {
         let url = client.url.clone();
-        let http_client = Arc::clone(&client.http_client);
-
-        Client { url, http_client }
+        let transport = Arc::clone(&client.transport);
+        let server = Arc::clone(&client.server);
+        let ids = client.ids.clone();
+
+        Client {
+            url,
+            transport,
+            server,
+            ids,
+        }
     }
 }
 
-impl FromStr for Client {
+#[cfg(any(feature = "reqwest", feature = "hyper"))]
+impl std::str::FromStr for Client {
     type Err = Error;
 
     fn from_str(url: &str) -> Result<Self, Self::Err> {
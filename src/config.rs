@@ -0,0 +1,181 @@
+//! The configuration of a CouchDB node, at `/_node/{node}/_config`.
+
+use http::StatusCode;
+use std::collections::BTreeMap;
+
+use crate::client::Client;
+use crate::{Error, Version};
+
+/// The name which refers to whichever node receives the request
+pub(crate) const LOCAL_NODE: &str = "_local";
+
+/// The settings in one section of the configuration, by key
+pub type ConfigSection = BTreeMap<String, String>;
+
+/// The configuration of a node, from [Client::config] or [Client::node_config].
+///
+/// Every value is a string, whatever it represents. Changes take effect immediately, and
+/// are persisted to the node's `local.ini`. This requires admin privileges.
+///
+/// see [CouchDB API docs](https://docs.couchdb.org/en/stable/api/server/configuration.html)
+/// for details.
+///
+/// # Example
+/// ```no_run
+/// use chesterfield::Client;
+///
+/// # async fn run() {
+/// let client = Client::from_url_str("http://localhost:5984").unwrap();
+/// let config = client.config();
+///
+/// let previous = config
+///     .set("couchdb", "max_document_size", "4294967296")
+///     .await
+///     .unwrap();
+/// println!("the limit was {:?}", previous);
+///
+/// config.set("chttpd", "enable_cors", "true").await.unwrap();
+/// config.set("cors", "origins", "https://example.com").await.unwrap();
+/// # }
+/// ```
+pub struct NodeConfig {
+    client: Client,
+}
+
+impl NodeConfig {
+    pub(crate) fn new(client: &Client, node: &str) -> Result<Self, Error> {
+        Ok(NodeConfig {
+            client: client.root().join(["_node", node, "_config"])?,
+        })
+    }
+
+    /// Retrieve the whole configuration, by section.
+    pub async fn all(&self) -> Result<BTreeMap<String, ConfigSection>, Error> {
+        self.client.get().send().await?.json()
+    }
+
+    /// Retrieve one section of the configuration.
+    ///
+    /// A section with no settings is empty, rather than an error.
+    pub async fn section(&self, section: &str) -> Result<ConfigSection, Error> {
+        self.client.join([section])?.get().send().await?.json()
+    }
+
+    /// Retrieve a single setting, if it's set.
+    pub async fn get(&self, section: &str, key: &str) -> Result<Option<String>, Error> {
+        let response = self.client.join([section, key])?.get().send().await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        response.json().map(Some)
+    }
+
+    /// Change a setting, returning its previous value (if it was set).
+    ///
+    /// CouchDB reports a setting which wasn't set as having been empty, so a setting which
+    /// was set to an empty string is also reported as `None`.
+    pub async fn set(
+        &self,
+        section: &str,
+        key: &str,
+        value: &str,
+    ) -> Result<Option<String>, Error> {
+        let previous: String = self
+            .client
+            .join([section, key])?
+            .put()
+            .json(&value)
+            .send()
+            .await?
+            .json()?;
+        Ok(Some(previous).filter(|previous| !previous.is_empty()))
+    }
+
+    /// Remove a setting, returning its previous value.
+    ///
+    /// # Errors
+    /// If the setting isn't set, this fails with an error for which
+    /// [is_not_found](Error::is_not_found) is true.
+    pub async fn delete(&self, section: &str, key: &str) -> Result<String, Error> {
+        self.client
+            .join([section, key])?
+            .delete()
+            .send()
+            .await?
+            .json()
+    }
+
+    /// Reload the configuration from the node's `.ini` files (CouchDB 3 and later).
+    pub async fn reload(&self) -> Result<(), Error> {
+        self.client
+            .require_version(Version::new(3, 0, 0), "reloading the configuration")
+            .await?;
+        self.client
+            .join(["_reload"])?
+            .post()
+            .send()
+            .await?
+            .error_for_status()
+            .map(|_| ())
+    }
+}
+
+impl Client {
+    /// The configuration of the node which receives the requests.
+    pub fn config(&self) -> NodeConfig {
+        NodeConfig::new(self, LOCAL_NODE).expect("_local is a valid path segment")
+    }
+
+    /// The configuration of a named node in the cluster, such as `couchdb@127.0.0.1`.
+    ///
+    /// # Errors
+    /// This method fails if the name is empty
+    pub fn node_config(&self, node: &str) -> Result<NodeConfig, Error> {
+        NodeConfig::new(self, node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::FakeTransport;
+
+    #[tokio::test]
+    async fn node_config() {
+        let client = FakeTransport::new().client();
+        let config = client.config();
+
+        let all = config.all().await.unwrap();
+        assert_eq!(all["couchdb"]["max_document_size"], "8000000");
+        assert_eq!(config.section("cluster").await.unwrap()["q"], "2");
+        assert!(config.section("nothing").await.unwrap().is_empty());
+
+        let previous = config
+            .set("couchdb", "max_document_size", "4294967296")
+            .await
+            .unwrap();
+        assert_eq!(previous.as_deref(), Some("8000000"));
+        assert_eq!(
+            config
+                .get("couchdb", "max_document_size")
+                .await
+                .unwrap()
+                .as_deref(),
+            Some("4294967296")
+        );
+
+        assert_eq!(config.set("cors", "origins", "*").await.unwrap(), None);
+        assert_eq!(config.delete("cors", "origins").await.unwrap(), "*");
+        assert_eq!(config.get("cors", "origins").await.unwrap(), None);
+        assert!(config
+            .delete("cors", "origins")
+            .await
+            .unwrap_err()
+            .is_not_found());
+
+        config.reload().await.unwrap();
+
+        let named = client.node_config("nonode@nohost").unwrap();
+        assert_eq!(named.section("chttpd").await.unwrap()["port"], "5984");
+        assert!(client.node_config("").is_err());
+    }
+}
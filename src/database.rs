@@ -1,15 +1,38 @@
+mod compact;
+mod conflicts;
+mod copy;
+mod create;
 mod delete;
 mod get;
+mod head;
+mod info;
 mod insert;
+mod modify;
+mod repository;
+mod security;
 mod update;
 //mod replication;
 
 pub use self::{
-    delete::DeleteRequest, get::GetRequest, insert::InsertRequest, update::UpdateRequest,
+    compact::CompactRequest,
+    conflicts::{ConflictResolver, HighestGeneration, LastWriteWins, Leaf, Resolution},
+    copy::{CopyRequest, CopyResponse},
+    create::CreateDatabaseRequest,
+    delete::{DeleteRequest, DeleteResponse},
+    get::{GetRequest, GetResponse, GetResponseMeta},
+    head::HeadResponse,
+    info::{DatabaseCluster, DatabaseInfo, DatabaseProps, DatabaseSizes},
+    insert::{InsertRequest, InsertResponse},
+    modify::ModifyRequest,
+    repository::Repository,
+    security::{SecurityMembers, SecurityObject},
+    update::{UpdateRequest, UpdateResponse},
 };
-use crate::{client::Client, Error};
-use futures::compat::Future01CompatExt;
+use crate::{client::Client, path, DocId, Document, Error, IdGenerator, Revision, Url};
+use http::StatusCode;
+use serde::de::DeserializeOwned;
 use serde::Serialize;
+use std::convert::TryInto;
 
 /// Interface for interacting with a specific CouchDB database within a CouchDB node.
 ///
@@ -32,20 +55,21 @@ impl Database {
         Database { client }
     }
 
-    /// Create the database, if it doesn't exist.
+    /// Create the database.
     ///
     /// Creating the database object itself is lazy- no check is performed
-    /// that the endpoint exists. Call this method if you need to create the endpoint
-    /// (or if you're not sure).
+    /// that the endpoint exists. Call this method if you need to create the endpoint.
+    /// The number of shards and replicas, and whether the database is partitioned, can be
+    /// set on the returned [CreateDatabaseRequest].
     ///
     /// # Example
-    /// ```
-    /// # #![feature(async_await)]
+    #[cfg_attr(docker_tests, doc = "```")]
+    #[cfg_attr(not(docker_tests), doc = "```no_run")]
     /// use chesterfield::Client;
-    /// use tokio;
     /// # use couchdb_container::CouchDbContainer;
     ///
-    /// let future03 = async {
+    /// # #[tokio::main]
+    /// # async fn main() {
     /// # {
     ///     # // pretend we're pointing at default port (it's actually randomised)
     ///     // Create the CouchDB client
@@ -60,41 +84,174 @@ impl Database {
     ///     let database = client.database("items").unwrap();
     ///
     ///     // create the database in the remote CouchDB instance
-    ///     database.create().await.expect("unable to create database!");
+    ///     database.create().q(8).send().await.expect("unable to create database!");
     /// #
     /// # // Clean up CouchDB instance
-    /// # couchdb.delete().await;
-    /// };
-    ///
-    /// // Currently, we must convert 0.3 future to 0.1 future to run on tokio executor
-    /// use futures::future::{FutureExt, TryFutureExt};
-    /// let future01 = future03.unit_error().boxed().compat();
-    ///
-    /// tokio::run(future01);
+    /// # couchdb.delete().await.unwrap();
+    /// # }
     /// ```
-    pub async fn create(&self) -> Result<(), Error> {
+    pub fn create(&self) -> CreateDatabaseRequest {
+        CreateDatabaseRequest::new(&self.client)
+    }
+
+    /// Delete the database, and every document in it.
+    pub async fn delete_database(&self) -> Result<(), Error> {
         self.client
-            .put()
+            .delete()
             .send()
-            .compat()
-            .await
+            .await?
+            .error_for_status()
             .map(|_| ())
-            .map_err(Error::from)
     }
 
-    /// Check whether the database exists
+    /// Retrieve information about the database, such as the number of documents and its
+    /// size on disk.
+    pub async fn info(&self) -> Result<DatabaseInfo, Error> {
+        self.client.get().send().await?.json()
+    }
+
+    /// Retrieve the database's security object: who may administer it, and who may read
+    /// and write it.
+    pub async fn security(&self) -> Result<SecurityObject, Error> {
+        security::get(&self.client).await
+    }
+
+    /// Replace the database's security object.
+    ///
+    /// This requires admin privileges (of the server, or of the database).
+    pub async fn set_security(&self, security: &SecurityObject) -> Result<(), Error> {
+        security::put(&self.client, security).await
+    }
+
+    /// Modify the database's security object, by reading it, applying the closure to it,
+    /// and writing it back (if it changed). Returns the modified security object.
+    ///
+    /// # Not atomic
+    /// This is a plain read followed by a plain write, with no protection against
+    /// concurrent changes. Unlike documents, the security object has no revisions, so a
+    /// change made by somebody else between the read and the write can't be detected- it's
+    /// silently overwritten, and nothing is retried (as [modify](Database::modify) does for
+    /// documents). Only use this when nothing else changes the security object at the same
+    /// time.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use chesterfield::Client;
+    ///
+    /// # async fn run() {
+    /// let client = Client::from_url_str("http://localhost:5984").unwrap();
+    /// let database = client.database("items").unwrap();
+    ///
+    /// database
+    ///     .modify_security(|security| {
+    ///         security.admins.add_role("ops");
+    ///         security.members.add_name("alice");
+    ///         security.members.remove_name("bob");
+    ///     })
+    ///     .await
+    ///     .unwrap();
+    /// # }
+    /// ```
+    pub async fn modify_security<F>(&self, modify: F) -> Result<SecurityObject, Error>
+    where
+        F: FnOnce(&mut SecurityObject),
+    {
+        security::modify(&self.client, modify).await
+    }
+
+    /// Compact the database, reclaiming the space taken by old revisions.
+    ///
+    /// This requires admin privileges.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use chesterfield::Client;
+    /// use std::time::Duration;
+    ///
+    /// # async fn run() {
+    /// let client = Client::from_url_str("http://localhost:5984").unwrap();
+    /// let database = client.database("items").unwrap();
+    ///
+    /// // wait up to ten minutes for compaction to finish
+    /// database
+    ///     .compact()
+    ///     .wait(Duration::from_secs(600))
+    ///     .send()
+    ///     .await
+    ///     .unwrap();
+    /// # }
+    /// ```
+    pub fn compact(&self) -> CompactRequest {
+        CompactRequest::new(&self.client, None)
+    }
+
+    /// Compact the view indexes of a design document.
+    ///
+    /// The name may be given with or without the `_design/` prefix. This requires admin
+    /// privileges.
+    pub fn compact_design(&self, design: &str) -> CompactRequest {
+        CompactRequest::new(&self.client, Some(design))
+    }
+
+    /// Remove the view indexes which are no longer used by any design document.
+    ///
+    /// This requires admin privileges.
+    pub async fn view_cleanup(&self) -> Result<(), Error> {
+        compact::maintain(&self.client, vec!["_view_cleanup"]).await
+    }
+
+    /// Ask CouchDB to write any recent changes to disk.
+    ///
+    /// This is only meaningful for CouchDB 1.x; later versions always commit changes
+    /// before acknowledging them, and treat this as a no-op.
+    pub async fn ensure_full_commit(&self) -> Result<(), Error> {
+        compact::maintain(&self.client, vec!["_ensure_full_commit"]).await
+    }
+
+    /// The URL of the database
+    pub fn url(&self) -> &Url {
+        self.client.url()
+    }
+
+    /// The URL of a document in the database
+    ///
+    /// # Errors
+    /// This method fails if the id is not a valid [DocId]
+    pub fn document_url<I>(&self, id: I) -> Result<Url, Error>
+    where
+        I: TryInto<DocId>,
+        Error: From<I::Error>,
+    {
+        path::join(self.url(), path::document(&id.try_into()?))
+    }
+
+    /// The URL of an attachment of a document in the database
+    ///
+    /// The attachment name is a single path segment, so a name containing `/` (such as
+    /// `img/logo.png`) is percent-encoded rather than split.
+    ///
+    /// # Errors
+    /// This method fails if the id is not a valid [DocId], or if the name can't be
+    /// represented in a URL (it's empty, `.` or `..`)
+    pub fn attachment_url<I>(&self, id: I, name: &str) -> Result<Url, Error>
+    where
+        I: TryInto<DocId>,
+        Error: From<I::Error>,
+    {
+        path::join(self.url(), path::attachment(&id.try_into()?, name))
+    }
+
+    /// Check whether the database exists.
+    ///
+    /// # Errors
+    /// Any response other than success or `404 Not Found` (such as `401 Unauthorized`) is
+    /// an error.
     pub async fn exists(&self) -> Result<bool, Error> {
-        self.client
-            .head()
-            .send()
-            .compat()
-            .await
-            .map(|response| match response.status().as_u16() {
-                200 => true,
-                404 => false,
-                _ => unreachable!(),
-            })
-            .map_err(Error::from)
+        let response = self.client.head().send().await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(false);
+        }
+        response.error_for_status().map(|_| true)
     }
 
     /// Retrieve a document from a database.
@@ -113,10 +270,51 @@ impl Database {
     /// let get_request = database.get(document_id);
     ///
     /// ```
-    pub fn get(&self, id: impl Into<String>) -> GetRequest {
+    pub fn get<I>(&self, id: I) -> GetRequest
+    where
+        I: TryInto<DocId>,
+        Error: From<I::Error>,
+    {
         GetRequest::new(&self.client, id)
     }
 
+    /// Check whether a document exists, and find its current revision and size, without
+    /// downloading it.
+    ///
+    /// Returns `None` if the document doesn't exist, or has been deleted.
+    pub async fn head<I>(&self, id: I) -> Result<Option<HeadResponse>, Error>
+    where
+        I: TryInto<DocId>,
+        Error: From<I::Error>,
+    {
+        head::head(&self.client, &id.try_into()?).await
+    }
+
+    /// The current revision of a document, or `None` if it doesn't exist.
+    ///
+    /// This is handy just before a [delete](Database::delete), for example.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use chesterfield::Client;
+    ///
+    /// # async fn run() {
+    /// let client = Client::from_url_str("http://localhost:5984").unwrap();
+    /// let database = client.database("items").unwrap();
+    ///
+    /// if let Some(rev) = database.current_rev("some-id").await.unwrap() {
+    ///     database.delete("some-id", rev).send().await.unwrap();
+    /// }
+    /// # }
+    /// ```
+    pub async fn current_rev<I>(&self, id: I) -> Result<Option<Revision>, Error>
+    where
+        I: TryInto<DocId>,
+        Error: From<I::Error>,
+    {
+        Ok(self.head(id).await?.map(|head| head.rev))
+    }
+
     /// Insert pretty much anything into the database.
     ///
     /// Provided that is, that it implements [Serialize](serde::Serialize).
@@ -125,8 +323,8 @@ impl Database {
     /// (but you might not like it). The response will contain the ID and the revision.
     ///
     /// # Example
-    /// ```
-    /// # #![feature(async_await)]
+    #[cfg_attr(docker_tests, doc = "```")]
+    #[cfg_attr(not(docker_tests), doc = "```no_run")]
     /// use chesterfield::Client;
     /// # use couchdb_container::CouchDbContainer;
     /// use serde::Serialize;
@@ -152,7 +350,7 @@ impl Database {
     ///     let database = client.database("items").unwrap();
     ///
     ///     // create the database in the remote CouchDB instance
-    ///     database.create().await.expect("unable to create database!");
+    ///     database.create().q(8).send().await.expect("unable to create database!");
     ///
     ///     let doc = MyCoolStruct {
     ///         field1: String::from("some string"),
@@ -165,11 +363,10 @@ impl Database {
     /// #    couchdb.delete().await.unwrap();
     /// }
     ///
-    /// // Currently, we must convert 0.3 future to 0.1 future to run on tokio executor
-    /// use futures::future::{FutureExt, TryFutureExt};
-    /// let future01 = app().unit_error().boxed().compat();
-    ///
-    /// tokio::run(future01);
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     app().await
+    /// }
     /// ```
     pub fn insert<'a, T: Serialize>(
         &self,
@@ -179,6 +376,18 @@ impl Database {
         InsertRequest::new(&self.client, document, id)
     }
 
+    /// Generate an id for a new document, with the client's
+    /// [IdGenerator](crate::IdGenerator) (or a random UUID, if it doesn't have one).
+    ///
+    /// Inserting with an id chosen up front means the insert can be retried without risk
+    /// of creating a duplicate.
+    pub async fn generate_id(&self) -> Result<DocId, Error> {
+        match self.client.id_generator() {
+            Some(ids) => ids.next_id(&self.client).await,
+            None => IdGenerator::random().next_id(&self.client).await,
+        }
+    }
+
     /// Update an existing document in the database.
     ///
     /// You'll need to know the id and current revision of the document
@@ -188,77 +397,414 @@ impl Database {
     /// document fields. Easiest way to do this is probably with a [Value](serde_json::Value).
     ///
     /// # Example
-    /// ```rust,ignore
-    /// # use chesterfield::Client;
-    /// # use serde::Serialize;
-    /// # use tokio::prelude::Future;
-    ///
-    /// # #[derive(Serialize)]
-    /// # struct MyCoolStruct {
-    /// #     field1: String,
-    /// #     field2: u32,
-    /// # }
+    #[cfg_attr(docker_tests, doc = "```")]
+    #[cfg_attr(not(docker_tests), doc = "```no_run")]
+    /// use chesterfield::Client;
+    /// # use couchdb_container::CouchDbContainer;
+    /// use serde::Serialize;
     ///
-    /// let doc = MyCoolStruct {
-    ///     field1: String::from("some string"),
-    ///     field2: 42,
-    /// };
+    /// #[derive(Serialize)]
+    /// struct MyCoolStruct {
+    ///     field1: String,
+    ///     field2: u32,
+    /// }
     ///
+    /// # #[tokio::main]
+    /// # async fn main() {
     /// # {
-    /// #     let client = Client::new("http://localhost:5984").unwrap();
+    /// #     let client = Client::from_url_str("http://localhost:5984").unwrap();
     /// # }
-    ///
-    /// # use chesterfield::CouchDbContainer;
-    /// # let couchdb = CouchDbContainer::default();
+    /// # let couchdb = CouchDbContainer::new().await.unwrap();
     /// # let url = format!("http://localhost:{}", couchdb.port());
-    /// # let client = Client::new(url).unwrap();
-    ///     
-    /// # // Create the database client
+    /// # let client = Client::from_url_str(url).unwrap();
     /// # let database = client.database("items").unwrap();
-    /// #     
-    /// # tokio::run(
-    /// #     // ensure the database exists in the remote
-    /// #     database.create().map_err(|e| panic!("{}", e)),
-    /// # );
-    ///     
-    /// tokio::run(
-    ///     database
-    ///         // insert document into database
-    ///         .insert(&doc, None)
-    ///         .send()
-    ///         // do something with the response
-    ///         .map(|response| assert!(response.ok))
-    ///         // handle any errors
-    ///         .map_err(|e| panic!("{}", e)),
-    /// );
-    ///     
-    /// # couchdb.delete();
+    /// # database.create().send().await.unwrap();
+    /// let mut doc = MyCoolStruct {
+    ///     field1: String::from("some string"),
+    ///     field2: 42,
+    /// };
+    ///
+    /// // insert document into database
+    /// let response = database.insert(&doc, None).send().await.unwrap();
+    ///
+    /// // update it using the returned id and revision
+    /// doc.field2 = 43;
+    /// database
+    ///     .update(&doc, response.id, response.rev)
+    ///     .send()
+    ///     .await
+    ///     .unwrap();
+    /// #
+    /// # couchdb.delete().await.unwrap();
+    /// # }
     /// ```
-    pub fn update<'a, T: Serialize>(
-        &self,
-        document: &'a T,
-        id: impl Into<String>,
-        rev: impl Into<String>,
-    ) -> UpdateRequest<'a, T> {
+    pub fn update<'a, T, I, R>(&self, document: &'a T, id: I, rev: R) -> UpdateRequest<'a, T>
+    where
+        T: Serialize,
+        I: TryInto<DocId>,
+        Error: From<I::Error>,
+        R: TryInto<Revision>,
+        Error: From<R::Error>,
+    {
         UpdateRequest::new(&self.client, document, id, rev)
     }
 
     /// Delete an existing document in the database.
-    pub fn delete(&self, id: impl Into<String>, rev: impl Into<String>) -> DeleteRequest {
+    pub fn delete<I, R>(&self, id: I, rev: R) -> DeleteRequest
+    where
+        I: TryInto<DocId>,
+        Error: From<I::Error>,
+        R: TryInto<Revision>,
+        Error: From<R::Error>,
+    {
         DeleteRequest::new(&self.client, id, rev)
     }
 
-    pub fn replicate_to<S: Into<String>>(url: S) -> Result<Replication, Error> {
-        unimplemented!()
+    /// Copy a document to a new id, without downloading and re-uploading it.
+    ///
+    /// The latest revision of the source is copied, unless a [rev](CopyRequest::rev) is
+    /// given. To copy over an existing document, give its current
+    /// [destination_rev](CopyRequest::destination_rev).
+    ///
+    /// # Example
+    /// ```no_run
+    /// use chesterfield::Client;
+    ///
+    /// # async fn run() {
+    /// let client = Client::from_url_str("http://localhost:5984").unwrap();
+    /// let database = client.database("items").unwrap();
+    ///
+    /// let copy = database.copy("original", "duplicate").send().await.unwrap();
+    /// assert_eq!(copy.id, "duplicate");
+    /// # }
+    /// ```
+    pub fn copy<S, D>(&self, source: S, destination: D) -> CopyRequest
+    where
+        S: TryInto<DocId>,
+        Error: From<S::Error>,
+        D: TryInto<DocId>,
+        Error: From<D::Error>,
+    {
+        CopyRequest::new(&self.client, source, destination)
     }
 
-    pub fn replicate_from<S: Into<String>>(url: S) -> Result<Replication, Error> {
-        unimplemented!()
+    /// Save a [Document], inserting or updating it as appropriate.
+    ///
+    /// If the document has no revision it's inserted (with its own id, if it has one),
+    /// otherwise the revision is updated (or, if it's [deleted](Document::deleted), deleted).
+    /// A document without an id is given one by the client's [IdGenerator](crate::IdGenerator),
+    /// if it has one, or else by the database. Either way, the new revision (and any new id)
+    /// is written back into the document.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "derive")]
+    /// # mod example {
+    /// use chesterfield::{Client, DocId, Document, Revision};
+    /// use serde::{Deserialize, Serialize};
+    ///
+    /// #[derive(Serialize, Deserialize, Document)]
+    /// struct Item {
+    ///     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    ///     id: Option<DocId>,
+    ///     #[serde(rename = "_rev", skip_serializing_if = "Option::is_none")]
+    ///     rev: Option<Revision>,
+    ///     count: u32,
+    /// }
+    ///
+    /// # async fn run() {
+    /// let client = Client::from_url_str("http://localhost:5984").unwrap();
+    /// let database = client.database("items").unwrap();
+    ///
+    /// let mut item = Item { id: None, rev: None, count: 0 };
+    /// database.save(&mut item).await.unwrap();
+    ///
+    /// item.count += 1;
+    /// database.save(&mut item).await.unwrap();
+    ///
+    /// assert_eq!(item.rev.unwrap().generation(), 2);
+    /// # }
+    /// # }
+    /// ```
+    pub async fn save<D: Document>(&self, document: &mut D) -> Result<(), Error> {
+        let body = crate::document::body(&*document)?;
+        let response = self.write(document.id(), &body).await?;
+
+        if document.id().is_none() {
+            document.set_id(DocId::new(response.id)?);
+        }
+        document.set_rev(response.rev);
+        Ok(())
+    }
+
+    /// Write a document, to its id if it has one, or else to one from the client's
+    /// [IdGenerator], or else to one assigned by the database
+    async fn write(
+        &self,
+        id: Option<&DocId>,
+        body: &impl Serialize,
+    ) -> Result<UpdateResponse, Error> {
+        let request = match (id, self.client.id_generator()) {
+            (Some(id), _) => self.client.join(path::document(id))?.put(),
+            (None, Some(ids)) => {
+                let id = ids.next_id(&self.client).await?;
+                self.client.join(path::document(&id))?.put()
+            }
+            (None, None) => self.client.post(),
+        };
+        request.json(body).send().await?.json()
+    }
+
+    /// Access the documents of a single type, such as `item`.
+    ///
+    /// See [Repository] for details.
+    pub fn repository<T: Document>(&self, kind: impl Into<String>) -> Repository<T> {
+        Repository::new(Database::new(Client::from(&self.client)), kind)
+    }
+
+    /// Modify a document, retrying if somebody else modifies it at the same time.
+    ///
+    /// This fetches the current revision of the document, applies the closure to it, and
+    /// writes it back. If the write conflicts, the whole cycle is retried (with
+    /// exponential backoff). Note that this means the closure may be called more than once.
+    ///
+    /// The closure sees the whole document, `_id` and `_rev` included, so a type with
+    /// fields of its own for them (such as a [Document]) is filled in as usual. The
+    /// revision which is written back is always the one that was read.
+    ///
+    /// The backoff waits using the client's [Transport::sleep](crate::transport::Transport::sleep).
+    ///
+    /// # Example
+    /// ```no_run
+    /// use chesterfield::Client;
+    /// use serde::{Deserialize, Serialize};
+    ///
+    /// #[derive(Default, Serialize, Deserialize)]
+    /// struct Counter {
+    ///     count: u64,
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let client = Client::from_url_str("http://localhost:5984").unwrap();
+    /// let database = client.database("items").unwrap();
+    ///
+    /// let rev = database
+    ///     .modify("page-views", |counter: &mut Counter| counter.count += 1)
+    ///     .create_if_missing()
+    ///     .send()
+    ///     .await
+    ///     .unwrap();
+    /// # }
+    /// ```
+    pub fn modify<T, F, I>(&self, id: I, modify: F) -> ModifyRequest<T, F>
+    where
+        T: Serialize + DeserializeOwned,
+        F: FnMut(&mut T),
+        I: TryInto<DocId>,
+        Error: From<I::Error>,
+    {
+        ModifyRequest::new(&self.client, id, modify)
     }
 
-    pub fn replicate_sync<S: Into<String>>(url: S) -> Result<Replication, Error> {
-        unimplemented!()
+    /// Resolve any conflicts between the revisions of a document.
+    ///
+    /// This fetches all of the live 'leaf' revisions of the document, and hands them to
+    /// the [ConflictResolver]. The document it returns is written over the current
+    /// winning revision, and every other leaf is deleted, in a single `_bulk_docs` request.
+    ///
+    /// Returns `None` if the document isn't conflicted.
+    ///
+    /// # Errors
+    /// As well as the usual errors, this fails if any of the writes is rejected- if
+    /// another client has edited the document in the meantime, for example. Since
+    /// `_bulk_docs` isn't transactional, some of the writes may have succeeded.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use chesterfield::{Client, LastWriteWins};
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let client = Client::from_url_str("http://localhost:5984").unwrap();
+    /// let database = client.database("items").unwrap();
+    ///
+    /// let resolution = database
+    ///     .resolve_conflicts("some-id", LastWriteWins::by_field("updated_at"))
+    ///     .await
+    ///     .unwrap();
+    ///
+    /// if let Some(resolution) = resolution {
+    ///     println!("resolved as {}", resolution.rev);
+    /// }
+    /// # }
+    /// ```
+    pub async fn resolve_conflicts<I, R>(
+        &self,
+        id: I,
+        resolver: R,
+    ) -> Result<Option<Resolution>, Error>
+    where
+        I: TryInto<DocId>,
+        Error: From<I::Error>,
+        R: ConflictResolver,
+    {
+        let id = id.try_into()?;
+        conflicts::resolve(&self.client, id, &resolver).await
     }
 }
 
-pub struct Replication {}
+#[cfg(test)]
+mod tests {
+    use crate::testing::{fake_database, Item};
+    use crate::transport::{BoxFuture, Request, Response, Transport};
+    use crate::{Client, DocId, Error, Url};
+    use http::StatusCode;
+    use serde_json::{json, Value};
+
+    /// A test double which answers every request with the same error
+    struct Failing(StatusCode, &'static str);
+
+    impl Transport for Failing {
+        fn execute(&self, _request: Request) -> BoxFuture<'_, Result<Response, Error>> {
+            let mut response = http::Response::new(self.1.as_bytes().to_vec());
+            *response.status_mut() = self.0;
+            Box::pin(async { Ok(response) })
+        }
+    }
+
+    fn database(status: StatusCode, body: &'static str) -> crate::Database {
+        let url = Url::parse("http://couch/").unwrap();
+        Client::with_transport(url, Failing(status, body))
+            .database("items")
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn error_responses_are_errors() {
+        let missing = database(
+            StatusCode::NOT_FOUND,
+            r#"{"error":"not_found","reason":"missing"}"#,
+        );
+        // this used to succeed, with the error body as the document
+        let error = missing.get("a").send::<Value>().await.unwrap_err();
+        assert!(error.is_not_found());
+
+        let conflicted = database(
+            StatusCode::CONFLICT,
+            r#"{"error":"conflict","reason":"Document update conflict."}"#,
+        );
+        let doc = json!({ "field": 1 });
+        let error = conflicted.insert(&doc, None).send().await.unwrap_err();
+        assert!(error.is_conflict());
+        let result = conflicted.update(&doc, "a", "1-abc").send().await;
+        assert!(matches!(result, Err(error) if error.is_conflict()));
+        let result = conflicted.delete("a", "1-abc").send().await;
+        assert!(matches!(result, Err(error) if error.is_conflict()));
+
+        let forbidden = database(
+            StatusCode::FORBIDDEN,
+            r#"{"error":"forbidden","reason":"only admins may do that"}"#,
+        );
+        match forbidden.insert(&doc, None).send().await.unwrap_err() {
+            Error::CouchDb(status, body) => {
+                assert_eq!(status, StatusCode::FORBIDDEN);
+                assert_eq!(body.reason, "only admins may do that");
+            }
+            error => panic!("unexpected error: {}", error),
+        }
+    }
+
+    #[tokio::test]
+    async fn database_exists() {
+        let missing = database(StatusCode::NOT_FOUND, "");
+        assert!(!missing.exists().await.unwrap());
+
+        let unauthorized = database(StatusCode::UNAUTHORIZED, "");
+        let error = unauthorized.exists().await.unwrap_err();
+        assert_eq!(error.status(), Some(StatusCode::UNAUTHORIZED));
+    }
+
+    #[tokio::test]
+    async fn save_documents() {
+        let (_, database) = fake_database("items").await;
+
+        let mut item = Item::new(0);
+        database.save(&mut item).await.unwrap();
+        assert!(item.id.is_some());
+        assert_eq!(item.rev.as_ref().unwrap().generation(), 1);
+
+        item.count += 1;
+        database.save(&mut item).await.unwrap();
+        assert_eq!(item.rev.as_ref().unwrap().generation(), 2);
+
+        let fetched: Item = database
+            .get(item.id.as_ref().unwrap())
+            .send()
+            .await
+            .unwrap()
+            .into_inner()
+            .unwrap();
+        assert_eq!(fetched.rev, item.rev);
+        assert_eq!(fetched.count, 1);
+
+        let mut named = Item {
+            id: Some(DocId::new("named").unwrap()),
+            ..Item::default()
+        };
+        database.save(&mut named).await.unwrap();
+        assert_eq!(named.id.unwrap(), "named");
+
+        // saving a stale copy conflicts
+        let mut stale = fetched;
+        stale.rev = Some("1-stale".parse().unwrap());
+        assert!(database.save(&mut stale).await.unwrap_err().is_conflict());
+
+        // saving a deleted document deletes it
+        item.deleted = true;
+        database.save(&mut item).await.unwrap();
+        assert_eq!(item.rev.as_ref().unwrap().generation(), 3);
+        let missing = database.get(item.id.as_ref().unwrap()).send::<Item>().await;
+        assert!(missing.unwrap_err().is_not_found());
+    }
+
+    #[tokio::test]
+    async fn database_info() {
+        let (client, database) = fake_database("items").await;
+
+        let exists = database.create().send().await.unwrap_err();
+        assert!(exists.is_file_exists());
+
+        database
+            .insert(&json!({ "field": 1 }), String::from("a"))
+            .send()
+            .await
+            .unwrap();
+        let info = database.info().await.unwrap();
+        assert_eq!(info.db_name, "items");
+        assert_eq!(info.doc_count, 1);
+        assert_eq!(info.doc_del_count, 0);
+        assert_eq!(info.update_seq, "1");
+        assert!(info.sizes.external > 0);
+        assert!(!info.props.partitioned);
+
+        let sharded = client.database("sharded").unwrap();
+        sharded
+            .create()
+            .q(8)
+            .n(3)
+            .partitioned(true)
+            .send()
+            .await
+            .unwrap();
+        let info = sharded.info().await.unwrap();
+        let cluster = info.cluster.unwrap();
+        assert_eq!((cluster.q, cluster.n), (8, 3));
+        assert!(info.props.partitioned);
+
+        database.delete_database().await.unwrap();
+        assert!(!database.exists().await.unwrap());
+        assert!(database.info().await.unwrap_err().is_not_found());
+        assert!(database.delete_database().await.unwrap_err().is_not_found());
+    }
+}
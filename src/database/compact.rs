@@ -0,0 +1,363 @@
+use http::header::{HeaderValue, CONTENT_TYPE};
+use http::StatusCode;
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+
+use crate::client::Client;
+use crate::path;
+use crate::{ActiveTask, DatabaseInfo, Error};
+
+/// How often to check whether compaction has finished, by default
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A request to compact a database, or the view indexes of one of its design documents.
+///
+/// Compaction runs in the background. By default the request returns as soon as CouchDB
+/// has started it; call [wait](CompactRequest::wait) to return once it has finished.
+///
+/// The request is lazy- it doesn't do a thing until you call its '[send](CompactRequest::send)'
+/// method.
+///
+/// see [CouchDB API docs](https://docs.couchdb.org/en/stable/api/database/compact.html)
+/// for details.
+pub struct CompactRequest {
+    client: Client,
+    design: Option<String>,
+    timeout: Option<Duration>,
+    poll_interval: Duration,
+}
+
+impl CompactRequest {
+    pub(crate) fn new(client: &Client, design: Option<&str>) -> Self {
+        CompactRequest {
+            client: client.into(),
+            design: design.map(String::from),
+            timeout: None,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+        }
+    }
+
+    /// Wait for compaction to finish, for at most this long.
+    ///
+    /// Compaction is finished once neither the database (or design document) nor the
+    /// server's active tasks say that it's running. The active tasks are only visible to
+    /// server admins; for anyone else, the database's word is taken.
+    ///
+    /// Default is not to wait.
+    pub fn wait(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// How often to check whether compaction has finished, while waiting.
+    ///
+    /// Default is 1 second.
+    pub fn poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Send the request.
+    ///
+    /// This requires admin privileges.
+    ///
+    /// # Errors
+    /// If the request waits and compaction is still running after the timeout, this fails
+    /// with [Timeout](Error::Timeout). Compaction carries on regardless.
+    pub async fn send(self) -> Result<(), Error> {
+        let mut segments = vec!["_compact"];
+        if let Some(design) = &self.design {
+            segments.extend(path::design_document(design).into_iter().skip(1));
+        }
+        maintain(&self.client, segments).await?;
+
+        let timeout = match self.timeout {
+            Some(timeout) => timeout,
+            None => return Ok(()),
+        };
+        let deadline = Instant::now() + timeout;
+        while self.is_running().await? {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(Error::Timeout(format!(
+                    "compaction of {} was still running after {:?}",
+                    self.client.url(),
+                    timeout
+                )));
+            }
+            self.client.sleep(self.poll_interval.min(remaining)).await;
+        }
+        Ok(())
+    }
+
+    /// Whether compaction is running, according to either the database (or design
+    /// document) itself or the server's active tasks.
+    ///
+    /// Only server admins can see the active tasks, so a database admin relies on the
+    /// database alone.
+    async fn is_running(&self) -> Result<bool, Error> {
+        let running = match &self.design {
+            None => {
+                let info: DatabaseInfo = self.client.get().send().await?.json()?;
+                info.compact_running
+            }
+            Some(design) => {
+                let mut segments = path::design_document(design);
+                segments.push("_info");
+                let info: DesignInfo = self.client.join(segments)?.get().send().await?.json()?;
+                info.view_index.compact_running
+            }
+        };
+        if running {
+            return Ok(true);
+        }
+
+        let name = database_name(&self.client);
+        let tasks = match self.client.root().active_tasks().await {
+            Ok(tasks) => tasks,
+            Err(error)
+                if matches!(
+                    error.status(),
+                    Some(StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN)
+                ) =>
+            {
+                return Ok(false)
+            }
+            Err(error) => return Err(error),
+        };
+        Ok(tasks.iter().any(|task| self.is_compacting(task, &name)))
+    }
+
+    /// Whether a task is this compaction
+    fn is_compacting(&self, task: &ActiveTask, name: &str) -> bool {
+        let database = task.database.as_deref().map(shard_database);
+        if database != Some(name) {
+            return false;
+        }
+        match &self.design {
+            None => task.kind == "database_compaction",
+            Some(design) => {
+                let id = format!("_design/{}", path::design_document(design)[1]);
+                task.kind == "view_compaction"
+                    && task
+                        .details
+                        .get("design_document")
+                        .and_then(|id| id.as_str())
+                        == Some(id.as_str())
+            }
+        }
+    }
+}
+
+/// Information about a design document's view index, from `GET /{db}/_design/{ddoc}/_info`
+#[derive(Deserialize)]
+struct DesignInfo {
+    view_index: ViewIndexInfo,
+}
+
+#[derive(Deserialize)]
+struct ViewIndexInfo {
+    compact_running: bool,
+}
+
+/// Send one of the maintenance requests, which are POSTs without a body
+pub(crate) async fn maintain(client: &Client, segments: Vec<&str>) -> Result<(), Error> {
+    client
+        .join(segments)?
+        .post()
+        .header(CONTENT_TYPE, HeaderValue::from_static("application/json"))
+        .send()
+        .await?
+        .error_for_status()
+        .map(|_| ())
+}
+
+/// The (decoded) name of the database a client points at
+fn database_name(client: &Client) -> String {
+    let segment = client
+        .url()
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .unwrap_or_default();
+    percent_encoding::percent_decode_str(segment)
+        .decode_utf8_lossy()
+        .into_owned()
+}
+
+/// The name of the database a task refers to, which is a shard file in CouchDB 2 and later
+/// (such as `shards/00000000-7fffffff/items.1518525394`)
+fn shard_database(database: &str) -> &str {
+    match database.strip_prefix("shards/") {
+        Some(shard) => {
+            let file = shard.split_once('/').map_or(shard, |(_, file)| file);
+            file.rsplit_once('.').map_or(file, |(name, _)| name)
+        }
+        None => database,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fake_database;
+    use crate::transport::{BoxFuture, Request, Response, Transport};
+    use crate::Url;
+    use http::Method;
+    use serde_json::json;
+
+    /// A test double whose database is forever being compacted
+    struct Compacting;
+
+    impl Transport for Compacting {
+        fn execute(&self, request: Request) -> BoxFuture<'_, Result<Response, Error>> {
+            let body = if request.method() == Method::POST {
+                json!({ "ok": true })
+            } else if request.uri().path() == "/_active_tasks" {
+                json!([])
+            } else {
+                json!({
+                    "db_name": "items",
+                    "doc_count": 0,
+                    "doc_del_count": 0,
+                    "update_seq": "0",
+                    "purge_seq": "0",
+                    "compact_running": true,
+                    "instance_start_time": "0",
+                })
+            };
+            Box::pin(async move {
+                let mut response = http::Response::new(body.to_string().into_bytes());
+                if request.method() == Method::POST {
+                    *response.status_mut() = StatusCode::ACCEPTED;
+                }
+                Ok(response)
+            })
+        }
+    }
+
+    /// A test double for a database admin who isn't a server admin, whose database has
+    /// finished compacting
+    struct DatabaseAdmin;
+
+    impl Transport for DatabaseAdmin {
+        fn execute(&self, request: Request) -> BoxFuture<'_, Result<Response, Error>> {
+            let (status, body) = if request.method() == Method::POST {
+                (StatusCode::ACCEPTED, json!({ "ok": true }))
+            } else if request.uri().path() == "/_active_tasks" {
+                (
+                    StatusCode::UNAUTHORIZED,
+                    json!({ "error": "unauthorized", "reason": "You are not a server admin." }),
+                )
+            } else {
+                (
+                    StatusCode::OK,
+                    json!({
+                        "db_name": "items",
+                        "doc_count": 0,
+                        "doc_del_count": 0,
+                        "update_seq": "0",
+                        "purge_seq": "0",
+                        "compact_running": false,
+                        "instance_start_time": "0",
+                    }),
+                )
+            };
+            Box::pin(async move {
+                let mut response = http::Response::new(body.to_string().into_bytes());
+                *response.status_mut() = status;
+                Ok(response)
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn waiting_without_active_tasks() {
+        let url = Url::parse("http://couch/").unwrap();
+        let client = Client::with_transport(url, DatabaseAdmin)
+            .join(["items"])
+            .unwrap();
+
+        CompactRequest::new(&client, None)
+            .wait(Duration::from_millis(30))
+            .poll_interval(Duration::from_millis(5))
+            .send()
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn waiting_times_out() {
+        let url = Url::parse("http://couch/").unwrap();
+        let client = Client::with_transport(url, Compacting)
+            .join(["items"])
+            .unwrap();
+
+        let result = CompactRequest::new(&client, None)
+            .wait(Duration::from_millis(30))
+            .poll_interval(Duration::from_millis(5))
+            .send()
+            .await;
+        assert!(matches!(result, Err(Error::Timeout(_))));
+    }
+
+    #[test]
+    fn compaction_tasks() {
+        let task: ActiveTask = serde_json::from_value(json!({
+            "database": "shards/00000000-7fffffff/items.1518525394",
+            "design_document": "_design/views",
+            "pid": "<0.2381.0>",
+            "started_on": 1518525421,
+            "type": "view_compaction",
+            "updated_on": 1518525423,
+        }))
+        .unwrap();
+        let client = Client::with_transport(Url::parse("http://couch/").unwrap(), Compacting);
+
+        assert!(CompactRequest::new(&client, Some("views")).is_compacting(&task, "items"));
+        assert!(!CompactRequest::new(&client, Some("other")).is_compacting(&task, "items"));
+        assert!(!CompactRequest::new(&client, None).is_compacting(&task, "items"));
+        assert!(!CompactRequest::new(&client, Some("views")).is_compacting(&task, "other"));
+    }
+
+    #[test]
+    fn shard_databases() {
+        assert_eq!(
+            shard_database("shards/00000000-7fffffff/items.1518525394"),
+            "items"
+        );
+        assert_eq!(
+            shard_database("shards/00000000-7fffffff/a/b.c.1518525394"),
+            "a/b.c"
+        );
+        assert_eq!(shard_database("items"), "items");
+    }
+
+    #[tokio::test]
+    async fn maintenance() {
+        let (client, database) = fake_database("items").await;
+        database
+            .insert(&json!({ "views": {} }), String::from("_design/views"))
+            .send()
+            .await
+            .unwrap();
+
+        let wait = std::time::Duration::from_secs(5);
+        database.compact().send().await.unwrap();
+        database.compact().wait(wait).send().await.unwrap();
+        database
+            .compact_design("_design/views")
+            .wait(wait)
+            .send()
+            .await
+            .unwrap();
+
+        let missing = database.compact_design("missing").send().await;
+        assert!(missing.err().unwrap().is_not_found());
+
+        database.view_cleanup().await.unwrap();
+        database.ensure_full_commit().await.unwrap();
+
+        let gone = client.database("gone").unwrap();
+        assert!(gone.compact().send().await.err().unwrap().is_not_found());
+    }
+}
@@ -0,0 +1,353 @@
+use crate::client::Client;
+use crate::error::ErrorResponse;
+use crate::path;
+use crate::{DocId, Error, Revision};
+use http::header::{HeaderValue, ACCEPT};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::cmp::Ordering;
+
+/// A live 'leaf' revision of a conflicted document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Leaf {
+    /// The revision of the leaf
+    pub rev: Revision,
+
+    /// The body of the document at this revision (without its `_id` and `_rev`)
+    pub document: Map<String, Value>,
+}
+
+/// A strategy for resolving conflicts between the leaf revisions of a document.
+///
+/// Any closure which takes the leaves and returns the resolved document can be used
+/// as a resolver, for merging the conflicting revisions by hand.
+///
+/// # Example
+/// ```
+/// use chesterfield::{ConflictResolver, Leaf};
+/// use serde_json::{json, Value};
+///
+/// // keep the union of all the tags
+/// let merge_tags = |leaves: &[Leaf]| {
+///     let mut tags: Vec<Value> = leaves
+///         .iter()
+///         .filter_map(|leaf| leaf.document.get("tags")?.as_array().cloned())
+///         .flatten()
+///         .collect();
+///     tags.sort_by_key(|tag| tag.to_string());
+///     tags.dedup();
+///     Ok(json!({ "tags": tags }))
+/// };
+/// # fn assert_resolver(_: impl ConflictResolver) {}
+/// # assert_resolver(merge_tags);
+/// ```
+pub trait ConflictResolver {
+    /// Pick (or build) the document which should replace all of the conflicting leaves.
+    ///
+    /// The leaves are sorted in CouchDB's order of preference, so the first is the
+    /// revision CouchDB currently considers the 'winner'. There are always at least two.
+    ///
+    /// # Errors
+    /// Any error returned here aborts the resolution, without writing anything.
+    fn resolve(&self, leaves: &[Leaf]) -> Result<Value, Error>;
+}
+
+impl<F> ConflictResolver for F
+where
+    F: Fn(&[Leaf]) -> Result<Value, Error>,
+{
+    fn resolve(&self, leaves: &[Leaf]) -> Result<Value, Error> {
+        self(leaves)
+    }
+}
+
+/// Keep the leaf with the highest revision.
+///
+/// This makes CouchDB's own (deterministic, but arbitrary) choice of winner permanent.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HighestGeneration;
+
+impl ConflictResolver for HighestGeneration {
+    fn resolve(&self, leaves: &[Leaf]) -> Result<Value, Error> {
+        Ok(Value::Object(leaves[0].document.clone()))
+    }
+}
+
+/// Keep the leaf with the greatest value of a given field, such as an `updated_at`
+/// timestamp.
+///
+/// Numbers are compared numerically, and strings lexically (so RFC 3339 timestamps
+/// sort correctly). A leaf with the field beats one without it. Ties go to the leaf
+/// with the highest revision.
+#[derive(Debug, Clone)]
+pub struct LastWriteWins {
+    field: String,
+}
+
+impl LastWriteWins {
+    /// Compare leaves by the value of `field`
+    pub fn by_field(field: impl Into<String>) -> Self {
+        LastWriteWins {
+            field: field.into(),
+        }
+    }
+}
+
+impl ConflictResolver for LastWriteWins {
+    fn resolve(&self, leaves: &[Leaf]) -> Result<Value, Error> {
+        // iterate in reverse, since 'max_by' keeps the last of equal elements
+        let winner = leaves
+            .iter()
+            .rev()
+            .max_by(|a, b| compare_fields(a.document.get(&self.field), b.document.get(&self.field)))
+            .unwrap_or(&leaves[0]);
+        Ok(Value::Object(winner.document.clone()))
+    }
+}
+
+fn compare_fields(a: Option<&Value>, b: Option<&Value>) -> Ordering {
+    match (a, b) {
+        (Some(Value::Number(a)), Some(Value::Number(b))) => a
+            .as_f64()
+            .partial_cmp(&b.as_f64())
+            .unwrap_or(Ordering::Equal),
+        (Some(Value::String(a)), Some(Value::String(b))) => a.cmp(b),
+        (Some(_), None) => Ordering::Greater,
+        (None, Some(_)) => Ordering::Less,
+        _ => Ordering::Equal,
+    }
+}
+
+/// The outcome of resolving a conflicted document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resolution {
+    /// The new revision of the resolved document
+    pub rev: Revision,
+
+    /// The losing leaf revisions, which have been deleted
+    pub deleted: Vec<Revision>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum OpenRev {
+    Ok(Map<String, Value>),
+    Missing(Revision),
+}
+
+/// The result of writing one document with `_bulk_docs`
+#[derive(Deserialize)]
+struct BulkDocResult {
+    #[serde(default)]
+    rev: Option<Revision>,
+    #[serde(default)]
+    error: Option<String>,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+impl BulkDocResult {
+    /// The new revision, unless the write failed (a failed write can still have a `rev`)
+    fn into_result(self) -> Result<Revision, Error> {
+        if let Some(error) = self.error {
+            let reason = self.reason.unwrap_or_default();
+            return Err(ErrorResponse { error, reason }.into());
+        }
+        self.rev
+            .ok_or_else(|| Error::Json(serde::de::Error::missing_field("rev")))
+    }
+}
+
+#[derive(Serialize)]
+struct BulkDocs {
+    docs: Vec<Value>,
+}
+
+pub(crate) async fn resolve(
+    client: &Client,
+    id: DocId,
+    resolver: &impl ConflictResolver,
+) -> Result<Option<Resolution>, Error> {
+    let leaves = fetch_leaves(client, &id).await?;
+    if leaves.len() < 2 {
+        return Ok(None);
+    }
+
+    let mut resolved = match resolver.resolve(&leaves)? {
+        Value::Object(document) => document,
+        _ => {
+            return Err(Error::Json(serde::ser::Error::custom(
+                "the resolved document must be a JSON object",
+            )))
+        }
+    };
+    resolved.insert("_id".to_string(), json!(id));
+    resolved.insert("_rev".to_string(), json!(leaves[0].rev));
+
+    let losers: Vec<Revision> = leaves.into_iter().skip(1).map(|leaf| leaf.rev).collect();
+    let docs = std::iter::once(Value::Object(resolved))
+        .chain(
+            losers
+                .iter()
+                .map(|rev| json!({ "_id": id, "_rev": rev, "_deleted": true })),
+        )
+        .collect();
+
+    let results: Vec<BulkDocResult> = client
+        .join(["_bulk_docs"])?
+        .post()
+        .json(&BulkDocs { docs })
+        .send()
+        .await?
+        .json()?;
+
+    let revs = results
+        .into_iter()
+        .map(BulkDocResult::into_result)
+        .collect::<Result<Vec<_>, _>>()?;
+    let rev = revs.into_iter().next().ok_or_else(|| {
+        Error::Json(serde::de::Error::invalid_length(
+            0,
+            &"a result for each document",
+        ))
+    })?;
+
+    Ok(Some(Resolution {
+        rev,
+        deleted: losers,
+    }))
+}
+
+/// Fetch the live leaves of a document, in CouchDB's order of preference
+async fn fetch_leaves(client: &Client, id: &DocId) -> Result<Vec<Leaf>, Error> {
+    let open_revs: Vec<OpenRev> = client
+        .join(path::document(id))?
+        .get()
+        .query(&[("open_revs", "all")])
+        .header(ACCEPT, HeaderValue::from_static("application/json"))
+        .send()
+        .await?
+        .json()?;
+
+    let mut leaves = Vec::new();
+    for open_rev in open_revs {
+        let mut document = match open_rev {
+            OpenRev::Ok(document) => document,
+            OpenRev::Missing(rev) => {
+                log::debug!("revision {} of '{}' is missing", rev, id);
+                continue;
+            }
+        };
+        if document.get("_deleted") == Some(&Value::Bool(true)) {
+            continue;
+        }
+        let rev = document.remove("_rev").unwrap_or(Value::Null);
+        document.remove("_id");
+        leaves.push(Leaf {
+            rev: serde_json::from_value(rev)?,
+            document,
+        });
+    }
+    leaves.sort_by(|a, b| b.rev.cmp(&a.rev));
+    Ok(leaves)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::{BoxFuture, Request, Response, Transport};
+    use crate::Url;
+    use http::Method;
+
+    /// A test double with a conflicted document, which answers `_bulk_docs` with a
+    /// canned response
+    struct Conflicted(&'static str);
+
+    impl Transport for Conflicted {
+        fn execute(&self, request: Request) -> BoxFuture<'_, Result<Response, Error>> {
+            let body = if request.method() == Method::POST {
+                self.0.to_string()
+            } else {
+                json!([
+                    { "ok": { "_id": "a", "_rev": "2-b", "n": 1 } },
+                    { "ok": { "_id": "a", "_rev": "2-a", "n": 2 } },
+                ])
+                .to_string()
+            };
+            Box::pin(async move { Ok(http::Response::new(body.into_bytes())) })
+        }
+    }
+
+    async fn resolve_with(bulk_docs: &'static str) -> Result<Option<Resolution>, Error> {
+        let url = Url::parse("http://couch/").unwrap();
+        let client = Client::with_transport(url, Conflicted(bulk_docs))
+            .join(["items"])
+            .unwrap();
+        resolve(&client, DocId::new("a").unwrap(), &HighestGeneration).await
+    }
+
+    #[tokio::test]
+    async fn bulk_docs_results() {
+        let resolution = resolve_with(r#"[{"id":"a","rev":"3-c"},{"id":"a","rev":"3-d"}]"#)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(resolution.rev.to_string(), "3-c");
+        assert_eq!(resolution.deleted[0].to_string(), "2-a");
+
+        // an error, even with a revision, fails the resolution
+        let error = resolve_with(
+            r#"[{"id":"a","rev":"3-c","error":"forbidden","reason":"read only"},{"id":"a","rev":"3-d"}]"#,
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(error.status(), Some(http::StatusCode::FORBIDDEN));
+
+        assert!(matches!(resolve_with("[]").await, Err(Error::Json(_))));
+    }
+
+    fn leaf(rev: &str, document: Value) -> Leaf {
+        Leaf {
+            rev: rev.parse().unwrap(),
+            document: serde_json::from_value(document).unwrap(),
+        }
+    }
+
+    #[test]
+    fn highest_generation() {
+        let leaves = [leaf("2-b", json!({"n": 1})), leaf("2-a", json!({"n": 2}))];
+        assert_eq!(HighestGeneration.resolve(&leaves).unwrap(), json!({"n": 1}));
+    }
+
+    #[test]
+    fn last_write_wins() {
+        let resolver = LastWriteWins::by_field("updated");
+
+        let leaves = [
+            leaf("3-a", json!({"updated": "2019-01-01T00:00:00Z"})),
+            leaf("2-b", json!({"updated": "2019-06-01T00:00:00Z"})),
+            leaf("2-a", json!({})),
+        ];
+        assert_eq!(
+            resolver.resolve(&leaves).unwrap(),
+            json!({"updated": "2019-06-01T00:00:00Z"})
+        );
+
+        let leaves = [
+            leaf("3-a", json!({"updated": 5, "n": 1})),
+            leaf("2-b", json!({"updated": 5, "n": 2})),
+            leaf("2-a", json!({"updated": 4.5, "n": 3})),
+        ];
+        assert_eq!(
+            resolver.resolve(&leaves).unwrap(),
+            json!({"updated": 5, "n": 1})
+        );
+    }
+
+    #[test]
+    fn closures() {
+        let count = |leaves: &[Leaf]| Ok(json!({ "count": leaves.len() }));
+        let leaves = [leaf("1-b", json!({})), leaf("1-a", json!({}))];
+        assert_eq!(count.resolve(&leaves).unwrap(), json!({"count": 2}));
+    }
+}
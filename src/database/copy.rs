@@ -0,0 +1,163 @@
+use http::header::{HeaderName, HeaderValue};
+use serde::{Deserialize, Serialize};
+use std::convert::TryInto;
+
+use crate::client::Client;
+use crate::path;
+use crate::{DocId, Error, Revision};
+
+/// A request to copy a document to a new id, or over an existing document.
+///
+/// The request is lazy- it doesn't do a thing until you call its '[send](CopyRequest::send)'
+/// method.
+pub struct CopyRequest {
+    client: Client,
+    source: Result<DocId, Error>,
+    destination: Result<DocId, Error>,
+    rev: Option<Result<Revision, Error>>,
+    destination_rev: Option<Result<Revision, Error>>,
+}
+
+impl CopyRequest {
+    pub(crate) fn new<S, D>(client: &Client, source: S, destination: D) -> Self
+    where
+        S: TryInto<DocId>,
+        Error: From<S::Error>,
+        D: TryInto<DocId>,
+        Error: From<D::Error>,
+    {
+        CopyRequest {
+            client: client.into(),
+            source: source.try_into().map_err(Error::from),
+            destination: destination.try_into().map_err(Error::from),
+            rev: None,
+            destination_rev: None,
+        }
+    }
+
+    /// Copy a specific revision of the source document, rather than the latest
+    pub fn rev<R>(mut self, rev: R) -> Self
+    where
+        R: TryInto<Revision>,
+        Error: From<R::Error>,
+    {
+        self.rev = Some(rev.try_into().map_err(Error::from));
+        self
+    }
+
+    /// Overwrite the given revision of an existing destination document.
+    ///
+    /// Without this, the copy fails with a conflict if the destination already exists.
+    pub fn destination_rev<R>(mut self, rev: R) -> Self
+    where
+        R: TryInto<Revision>,
+        Error: From<R::Error>,
+    {
+        self.destination_rev = Some(rev.try_into().map_err(Error::from));
+        self
+    }
+
+    /// Consume the copy request and send it to the remote
+    pub async fn send(self) -> Result<CopyResponse, Error> {
+        let rev = self.rev.transpose()?;
+        let destination_rev = self.destination_rev.transpose()?;
+        let destination = path::destination(
+            &self.destination?,
+            destination_rev.map(String::from).as_deref(),
+        )?;
+
+        let response = self
+            .client
+            .join(path::document(&self.source?))?
+            .copy()
+            .query(&CopyQuery { rev })
+            .header(
+                HeaderName::from_static("destination"),
+                HeaderValue::from_bytes(destination.as_bytes()).map_err(http::Error::from)?,
+            )
+            .send()
+            .await?
+            .json()?;
+        Ok(response)
+    }
+}
+
+#[derive(Serialize)]
+struct CopyQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rev: Option<Revision>,
+}
+
+/// Reponse from the CouchDB database after copying a document
+#[derive(Deserialize)]
+pub struct CopyResponse {
+    /// The _id of the new (or overwritten) document
+    pub id: String,
+
+    /// Copy operation status
+    pub ok: bool,
+
+    /// The revision of the new (or overwritten) document
+    pub rev: Revision,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::fake_database;
+    use crate::{Error, GetResponse};
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn copy_documents() {
+        let (_, database) = fake_database("items").await;
+
+        let first = database
+            .insert(&json!({ "count": 1 }), "original".to_string())
+            .send()
+            .await
+            .unwrap();
+        let second = database
+            .update(&json!({ "count": 2 }), "original", first.rev.clone())
+            .send()
+            .await
+            .unwrap();
+
+        let copy = database.copy("original", "a/copy").send().await.unwrap();
+        assert_eq!(copy.id, "a/copy");
+        assert_eq!(copy.rev.generation(), 1);
+        let doc: GetResponse = database.get("a/copy").send().await.unwrap();
+        assert_eq!(doc.into_inner().unwrap()["count"], 2);
+
+        // the destination exists now, so copying over it needs its revision
+        let conflict = database.copy("original", "a/copy").send().await;
+        assert!(conflict.err().unwrap().is_conflict());
+
+        let overwritten = database
+            .copy("original", "a/copy")
+            .rev(first.rev)
+            .destination_rev(copy.rev)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(overwritten.rev.generation(), 2);
+        let doc: GetResponse = database.get("a/copy").send().await.unwrap();
+        assert_eq!(doc.into_inner().unwrap()["count"], 1);
+
+        // the source is untouched
+        let doc: GetResponse = database.get("original").send().await.unwrap();
+        assert_eq!(doc.meta_data()._rev, second.rev);
+
+        let missing = database.copy("missing", "elsewhere").send().await;
+        assert!(missing.err().unwrap().is_not_found());
+
+        // the destination id is sent as-is, so it mustn't contain a '?'
+        for id in ["100%", "a b", "naïve"] {
+            let copy = database.copy("original", id).send().await.unwrap();
+            assert_eq!(copy.id, id);
+            let doc: GetResponse = database.get(id).send().await.unwrap();
+            assert_eq!(doc.into_inner().unwrap()["count"], 2);
+        }
+        let invalid = database.copy("original", "what?").send().await;
+        assert!(matches!(invalid, Err(Error::InvalidPath(_))));
+    }
+}
@@ -0,0 +1,81 @@
+use serde::Serialize;
+
+use crate::client::Client;
+use crate::Error;
+
+/// A request to create a database.
+///
+/// The request is lazy- it doesn't do a thing until you call its
+/// '[send](CreateDatabaseRequest::send)' method.
+///
+/// see [CouchDB API docs](https://docs.couchdb.org/en/stable/api/database/common.html#put--db)
+/// for details.
+pub struct CreateDatabaseRequest {
+    client: Client,
+    query: CreateDatabaseQuery,
+}
+
+impl CreateDatabaseRequest {
+    pub(crate) fn new(client: &Client) -> Self {
+        CreateDatabaseRequest {
+            client: client.into(),
+            query: CreateDatabaseQuery::default(),
+        }
+    }
+
+    /// The number of shards to split the database into.
+    ///
+    /// Default is the server's `cluster/q` setting.
+    pub fn q(mut self, shards: u32) -> Self {
+        self.query.q = Some(shards);
+        self
+    }
+
+    /// The number of replicas of each shard.
+    ///
+    /// Default is the server's `cluster/n` setting.
+    pub fn n(mut self, replicas: u32) -> Self {
+        self.query.n = Some(replicas);
+        self
+    }
+
+    /// Create a partitioned database (CouchDB 3 and later).
+    ///
+    /// Default is false. If the server doesn't have the `partitioned` feature enabled,
+    /// [send](CreateDatabaseRequest::send) fails with [Unsupported](Error::Unsupported).
+    pub fn partitioned(mut self, value: bool) -> Self {
+        self.query.partitioned = value;
+        self
+    }
+
+    /// Send the request.
+    ///
+    /// # Errors
+    /// If the database already exists, this fails with an error for which
+    /// [is_file_exists](Error::is_file_exists) is true.
+    pub async fn send(self) -> Result<(), Error> {
+        if self.query.partitioned {
+            self.client
+                .require_feature("partitioned", "a partitioned database")
+                .await?;
+        }
+
+        self.client
+            .put()
+            .query(&self.query)
+            .send()
+            .await?
+            .error_for_status()
+            .map(|_| ())
+    }
+}
+
+#[derive(Serialize, Default)]
+struct CreateDatabaseQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    q: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    n: Option<u32>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    partitioned: bool,
+}
@@ -1,39 +1,48 @@
 use serde::{Deserialize, Serialize};
 
 use crate::client::Client;
-use crate::Error;
-use futures::compat::Future01CompatExt;
+use crate::path;
+use crate::{DocId, Error, Revision};
+use std::convert::TryInto;
 
+/// A request to delete an existing document.
 pub struct DeleteRequest {
-    id: String,
+    id: Result<DocId, Error>,
     client: Client,
-    query: DeleteRequestQuery,
+    query: Result<DeleteRequestQuery, Error>,
 }
 
 impl DeleteRequest {
-    pub(crate) fn new(client: &Client, id: impl Into<String>, rev: impl Into<String>) -> Self {
+    pub(crate) fn new<I, R>(client: &Client, id: I, rev: R) -> Self
+    where
+        I: TryInto<DocId>,
+        Error: From<I::Error>,
+        R: TryInto<Revision>,
+        Error: From<R::Error>,
+    {
         DeleteRequest {
-            id: id.into(),
+            id: id.try_into().map_err(Error::from),
             client: client.into(),
-            query: DeleteRequestQuery::new(rev),
+            query: rev
+                .try_into()
+                .map_err(Error::from)
+                .map(DeleteRequestQuery::new),
         }
     }
 
+    /// Consume the delete request and send it to the remote
     pub async fn send(self) -> Result<DeleteResponse, Error> {
         let response = self
             .client
             // create a new client pointing at "<database>/documentId"
-            .join(&self.id)?
+            .join(path::document(&self.id?))?
             // construct the delete request, and send it
             .delete()
-            .query(&self.query)
+            .query(&self.query?)
             .send()
-            .compat()
             .await?
             // extract the JSON blob
-            .json()
-            .compat()
-            .await?;
+            .json()?;
         Ok(response)
     }
 }
@@ -43,23 +52,26 @@ pub struct DeleteRequestQuery {
     #[serde(skip_serializing_if = "Option::is_none")]
     batch: Option<String>,
 
-    rev: String,
+    rev: Revision,
 }
 
 impl DeleteRequestQuery {
-    fn new(rev: impl Into<String>) -> Self {
-        DeleteRequestQuery {
-            batch: None,
-            rev: rev.into(),
-        }
+    fn new(rev: Revision) -> Self {
+        DeleteRequestQuery { batch: None, rev }
     }
 }
 
+/// Reponse from the CouchDB database after deleting a document
 #[derive(Deserialize)]
 pub struct DeleteResponse {
+    /// The _id of the deleted document
     pub id: String,
+
+    /// Delete operation status
     pub ok: bool,
-    pub rev: String,
+
+    /// The revision of the deletion 'tombstone'
+    pub rev: Revision,
 }
 
 #[cfg(test)]
@@ -1,10 +1,14 @@
 use crate::client::Client;
-use serde::{Deserialize, Serialize};
+use crate::path;
+use crate::transport::Cacheable;
+use http::header::{HeaderValue, IF_NONE_MATCH};
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde_json::Value;
 
-use crate::Error;
-use futures::compat::Future01CompatExt;
+use crate::{DocId, Error, RevInfo, Revision, RevisionHistory};
 use serde::de::DeserializeOwned;
+use std::convert::TryInto;
 
 /// A request to retrieve a document from a CouchDB database.
 ///
@@ -14,17 +18,23 @@ use serde::de::DeserializeOwned;
 /// see [CouchDB API docs](https://docs.couchdb.org/en/stable/api/document/common.html#get--db-docid)
 /// for details.
 pub struct GetRequest {
-    id: String,
+    id: Result<DocId, Error>,
     client: Client,
     query: GetRequestQuery,
+    if_none_match: Option<Result<Revision, Error>>,
 }
 
 impl GetRequest {
-    pub(crate) fn new(client: &Client, id: impl Into<String>) -> Self {
+    pub(crate) fn new<I>(client: &Client, id: I) -> Self
+    where
+        I: TryInto<DocId>,
+        Error: From<I::Error>,
+    {
         GetRequest {
-            id: id.into(),
+            id: id.try_into().map_err(Error::from),
             client: client.into(),
             query: GetRequestQuery::default(),
+            if_none_match: None,
         }
     }
 
@@ -48,8 +58,11 @@ impl GetRequest {
     /// Doesn’t include attachments for specified revisions
     ///
     /// Default is false.
-    pub fn attachments_since(mut self, revisions: impl Into<Vec<String>>) -> Self {
-        self.query.atts_since = revisions.into();
+    pub fn attachments_since(
+        mut self,
+        revisions: impl IntoIterator<Item = impl Into<Revision>>,
+    ) -> Self {
+        self.query.atts_since = revisions.into_iter().map(Into::into).collect();
         self
     }
 
@@ -99,8 +112,12 @@ impl GetRequest {
     }
 
     /// retrieve documents of specified leaf revisions.
-    pub fn open_revisions(mut self, revisions: impl Into<Vec<String>>) -> Self {
-        self.query.open_revs = Some(OpenRevs::Revisions(revisions.into()));
+    pub fn open_revisions(
+        mut self,
+        revisions: impl IntoIterator<Item = impl Into<Revision>>,
+    ) -> Self {
+        let revisions = revisions.into_iter().map(Into::into).collect();
+        self.query.open_revs = Some(OpenRevs::Revisions(revisions));
         self
     }
 
@@ -117,8 +134,8 @@ impl GetRequest {
     }
 
     /// retrieve document of specified revision.
-    pub fn revision(mut self, revision: impl Into<Option<String>>) -> Self {
-        self.query.rev = revision.into();
+    pub fn revision(mut self, revision: impl Into<Revision>) -> Self {
+        self.query.rev = Some(revision.into());
         self
     }
 
@@ -138,33 +155,47 @@ impl GetRequest {
         self
     }
 
+    /// Only retrieve the document if its revision is no longer the given one.
+    ///
+    /// If the document hasn't changed, [send](GetRequest::send) fails with an error for
+    /// which [is_not_modified](Error::is_not_modified) is true. To avoid downloading
+    /// unchanged documents without handling this yourself, use a
+    /// [CachingTransport](crate::transport::CachingTransport).
+    pub fn if_none_match<R>(mut self, rev: R) -> Self
+    where
+        R: TryInto<Revision>,
+        Error: From<R::Error>,
+    {
+        self.if_none_match = Some(rev.try_into().map_err(Error::from));
+        self
+    }
+
     /// Send the request.
     ///
     /// This will consume the 'get' request and return a [GetResponse](GetResponse).
     /// The response is generic, so occasionally you might need type annotations.
     pub async fn send<T: DeserializeOwned>(self) -> Result<GetResponse<T>, Error> {
-        let response = self
+        let mut request = self
             .client
-            .join(&self.id)?
+            .join(path::document(&self.id?))?
             .get()
             .query(&self.query)
-            .send()
-            .compat()
-            .await?
-            .json()
-            .compat()
-            .await?;
-
-        Ok(response)
+            .extension(Cacheable);
+        if let Some(rev) = self.if_none_match.transpose()? {
+            let etag = HeaderValue::from_str(&format!("\"{}\"", rev)).map_err(http::Error::from)?;
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+
+        request.send().await?.json()
     }
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Clone, Default)]
 pub struct GetRequestQuery {
     attachments: bool,
     att_encoding_info: bool,
-    #[serde(skip_serializing_if = "Vec::is_empty")]
-    atts_since: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty", serialize_with = "json")]
+    atts_since: Vec<Revision>,
     conflicts: bool,
     deleted_conflicts: bool,
     latest: bool,
@@ -173,7 +204,7 @@ pub struct GetRequestQuery {
     #[serde(skip_serializing_if = "Option::is_none")]
     open_revs: Option<OpenRevs>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    rev: Option<String>,
+    rev: Option<Revision>,
     revs: bool,
     revs_info: bool,
 }
@@ -181,54 +212,90 @@ pub struct GetRequestQuery {
 #[derive(Serialize, Clone, Debug)]
 #[serde(untagged)]
 enum OpenRevs {
-    Revisions(Vec<String>),
+    Revisions(#[serde(serialize_with = "json")] Vec<Revision>),
     All(&'static str),
 }
 
-impl Default for GetRequestQuery {
-    fn default() -> Self {
-        GetRequestQuery {
-            attachments: false,
-            att_encoding_info: false,
-            atts_since: Vec::default(),
-            conflicts: false,
-            deleted_conflicts: false,
-            latest: false,
-            local_seq: false,
-            meta: false,
-            open_revs: None,
-            rev: None,
-            revs: false,
-            revs_info: false,
-        }
-    }
+/// Serialise a query parameter as JSON, which is how CouchDB expects lists of revisions
+fn json<T: Serialize, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+    let json = serde_json::to_string(value).map_err(serde::ser::Error::custom)?;
+    serializer.serialize_str(&json)
 }
 
+/// The metadata fields of a document, returned alongside it by a [GetRequest].
 #[derive(Debug, Deserialize)]
 pub struct GetResponseMeta {
+    /// The id of the document
     pub _id: String,
-    pub _rev: String,
 
+    /// The revision of the returned document
+    pub _rev: Revision,
+
+    /// Whether the returned revision is a deletion 'tombstone'
     #[serde(skip_serializing_if = "Option::is_none")]
     pub _deleted: Option<bool>,
 
+    /// Attachment stubs (or bodies, if requested)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub _attachments: Option<Value>,
 
+    /// Conflicting live revisions, if requested
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub _conflicts: Option<Vec<Value>>,
+    pub _conflicts: Option<Vec<Revision>>,
 
+    /// Conflicting deleted revisions, if requested
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub _deleted_conflicts: Option<Vec<Value>>,
+    pub _deleted_conflicts: Option<Vec<Revision>>,
 
+    /// The document's update sequence on this node, if requested
     #[serde(skip_serializing_if = "Option::is_none")]
     pub _local_seq: Option<String>,
 
+    /// The status of each revision in the document's history, if requested
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub _revs_info: Option<Vec<Value>>,
+    pub _revs_info: Option<Vec<RevInfo>>,
 
+    /// The document's revision history, if requested
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub _revisions: Option<Value>,
+    pub _revisions: Option<RevisionHistory>,
+}
+
+impl GetResponseMeta {
+    /// The live 'leaf' revisions of the document- the returned revision, and any
+    /// conflicting revisions.
+    ///
+    /// The conflicts are only known if the request set [conflicts](GetRequest::conflicts)
+    /// (or [meta](GetRequest::meta)). The revisions are returned in CouchDB's order of
+    /// preference, so the first is the 'winner'.
+    pub fn leaves(&self) -> Vec<&Revision> {
+        let mut leaves: Vec<&Revision> = std::iter::once(&self._rev)
+            .chain(self._conflicts.iter().flatten())
+            .collect();
+        leaves.sort_by(|a, b| b.cmp(a));
+        leaves
+    }
+
+    /// The ancestry of the returned revision, newest first.
+    ///
+    /// This is only available if the request set [revisions](GetRequest::revisions).
+    ///
+    /// # Errors
+    /// This method fails if the history returned by the database is malformed.
+    pub fn ancestry(&self) -> Option<Result<Vec<Revision>, Error>> {
+        self._revisions.as_ref().map(RevisionHistory::ancestry)
+    }
+
+    /// The previous revisions of the document whose bodies can still be retrieved.
+    ///
+    /// This is only available if the request set
+    /// [revisions_info](GetRequest::revisions_info) (or [meta](GetRequest::meta)).
+    pub fn available_revisions(&self) -> impl Iterator<Item = &Revision> {
+        self._revs_info
+            .iter()
+            .flatten()
+            .filter(|info| info.is_available())
+            .map(|info| &info.rev)
+    }
 }
 
 /// A response from a GetRequest.
@@ -241,15 +308,32 @@ pub struct GetResponseMeta {
 /// The GetResponse implements Deref with respect to the returned document.
 /// You can also consume the response and retrieve the document with the [into_inner](GetResponse::into_inner)
 /// method.
-#[derive(Debug, Deserialize)]
+#[derive(Debug)]
 pub struct GetResponse<T = Value> {
-    #[serde(flatten)]
     document: Option<T>,
-
-    #[serde(flatten)]
     meta_data: GetResponseMeta,
 }
 
+// Not derived, because flattening both fields would hand each key to only one of them-
+// and a document type is free to declare `_id` and `_rev` fields of its own.
+impl<'de, T> Deserialize<'de> for GetResponse<T>
+where
+    T: DeserializeOwned,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = Value::deserialize(deserializer)?;
+        let meta_data = GetResponseMeta::deserialize(&value).map_err(D::Error::custom)?;
+        let document = T::deserialize(value).map_err(D::Error::custom)?;
+        Ok(GetResponse {
+            document: Some(document),
+            meta_data,
+        })
+    }
+}
+
 impl<T> GetResponse<T> {
     /// Return metadata about the response.
     ///
@@ -264,3 +348,53 @@ impl<T> GetResponse<T> {
         self.document
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::{BoxFuture, Request, Response, Transport};
+    use crate::Url;
+    use std::sync::{Arc, Mutex};
+
+    /// A test double which records the requested URIs, and returns a document
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<String>>);
+
+    impl Transport for Recorder {
+        fn execute(&self, request: Request) -> BoxFuture<'_, Result<Response, Error>> {
+            self.0.lock().unwrap().push(request.uri().to_string());
+            let body = br#"{"_id":"a","_rev":"2-def"}"#.to_vec();
+            Box::pin(async { Ok(http::Response::new(body)) })
+        }
+    }
+
+    #[tokio::test]
+    async fn typed_revisions() {
+        let recorder = Arc::new(Recorder::default());
+        let url = Url::parse("http://couch/").unwrap();
+        let database = Client::with_transport(url, recorder.clone())
+            .database("items")
+            .unwrap();
+        let first: Revision = "1-abc".parse().unwrap();
+        let second: Revision = "2-def".parse().unwrap();
+
+        database
+            .get("a")
+            .revision(&second)
+            .send::<Value>()
+            .await
+            .unwrap();
+        database
+            .get("a")
+            .open_revisions([&first, &second])
+            .attachments_since(vec![first.clone()])
+            .send::<Value>()
+            .await
+            .unwrap();
+
+        let uris = recorder.0.lock().unwrap();
+        assert!(uris[0].ends_with("&rev=2-def&revs=false&revs_info=false"));
+        assert!(uris[1].contains("atts_since=%5B%221-abc%22%5D"));
+        assert!(uris[1].contains("open_revs=%5B%221-abc%22%2C%222-def%22%5D"));
+    }
+}
@@ -0,0 +1,64 @@
+use http::StatusCode;
+
+use crate::client::Client;
+use crate::path;
+use crate::{DocId, Error, Revision};
+
+/// What a `HEAD` request reveals about a document, without downloading it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeadResponse {
+    /// The current (winning) revision of the document
+    pub rev: Revision,
+
+    /// The size of the document's JSON body, in bytes, if the server reported it
+    pub content_length: Option<u64>,
+}
+
+/// Send a `HEAD` request for a document, returning `None` if it doesn't exist (or has
+/// been deleted).
+pub(crate) async fn head(client: &Client, id: &DocId) -> Result<Option<HeadResponse>, Error> {
+    let response = client.join(path::document(id))?.head().send().await?;
+    if response.status() == StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    let response = response.error_for_status()?;
+
+    let rev = response
+        .etag()
+        .ok_or_else(|| Error::InvalidRevision("the response had no ETag".to_string()))?
+        .parse()?;
+    Ok(Some(HeadResponse {
+        rev,
+        content_length: response.content_length(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::fake_database;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn head_documents() {
+        let (client, database) = fake_database("items").await;
+
+        assert_eq!(database.head("some-id").await.unwrap(), None);
+
+        let doc = json!({ "field": 1 });
+        let inserted = database
+            .insert(&doc, String::from("some-id"))
+            .send()
+            .await
+            .unwrap();
+        let head = database.head("some-id").await.unwrap().unwrap();
+        assert_eq!(head.rev, inserted.rev);
+        assert!(head.content_length.unwrap() > 0);
+
+        let rev = database.current_rev("some-id").await.unwrap().unwrap();
+        database.delete("some-id", rev).send().await.unwrap();
+        assert_eq!(database.current_rev("some-id").await.unwrap(), None);
+
+        let missing = client.database("missing").unwrap();
+        assert_eq!(missing.current_rev("some-id").await.unwrap(), None);
+    }
+}
@@ -0,0 +1,133 @@
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+
+/// Information about a database, as returned by [Database::info](crate::Database::info).
+///
+/// see [CouchDB API docs](https://docs.couchdb.org/en/stable/api/database/common.html#get--db)
+/// for details.
+#[derive(Debug, Clone, Deserialize)]
+pub struct DatabaseInfo {
+    /// The name of the database
+    pub db_name: String,
+
+    /// The number of live documents
+    pub doc_count: u64,
+
+    /// The number of deleted documents
+    pub doc_del_count: u64,
+
+    /// An opaque token for the current update sequence.
+    ///
+    /// CouchDB 1.x reports a plain number, which is returned as a string.
+    #[serde(deserialize_with = "sequence")]
+    pub update_seq: String,
+
+    /// An opaque token for the current purge sequence
+    #[serde(deserialize_with = "sequence")]
+    pub purge_seq: String,
+
+    /// Whether the database is being compacted
+    pub compact_running: bool,
+
+    /// The sizes of the database, in bytes
+    #[serde(default)]
+    pub sizes: DatabaseSizes,
+
+    /// How the database is sharded and replicated (CouchDB 2 and later)
+    pub cluster: Option<DatabaseCluster>,
+
+    /// The properties the database was created with
+    #[serde(default)]
+    pub props: DatabaseProps,
+
+    /// When the database was opened, in microseconds since the epoch (or `0`)
+    pub instance_start_time: String,
+}
+
+/// The sizes of a database, in bytes
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct DatabaseSizes {
+    /// The size of the live data in the database file
+    pub active: u64,
+
+    /// The uncompressed size of the database contents
+    pub external: u64,
+
+    /// The size of the database file on disk
+    pub file: u64,
+}
+
+/// The sharding and quorum settings of a database
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct DatabaseCluster {
+    /// The number of shards
+    pub q: u32,
+
+    /// The number of replicas of each shard
+    pub n: u32,
+
+    /// The write quorum
+    pub w: u32,
+
+    /// The read quorum
+    pub r: u32,
+}
+
+/// The properties a database was created with
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct DatabaseProps {
+    /// Whether the database is partitioned
+    #[serde(default)]
+    pub partitioned: bool,
+}
+
+/// Deserialise a sequence, which is a string in CouchDB 2 and later but a number before
+fn sequence<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    match Value::deserialize(deserializer)? {
+        Value::String(seq) => Ok(seq),
+        other => Ok(other.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn deserialize() {
+        let info: DatabaseInfo = serde_json::from_value(json!({
+            "db_name": "items",
+            "doc_count": 2,
+            "doc_del_count": 1,
+            "update_seq": "3-g1AAAAFTeJzLYWBg4MhgTmHgz8tPSTV0MDQy1zMAQsMcoARTIkMSg_z_",
+            "purge_seq": "0-g1AAAAFTeJzLYWBg4MhgTmHgz8tPSTV0MDQy1zMAQsMcoARTIkMSg_z_",
+            "compact_running": false,
+            "sizes": { "active": 10, "external": 20, "file": 30 },
+            "cluster": { "q": 2, "n": 1, "w": 1, "r": 1 },
+            "props": { "partitioned": true },
+            "instance_start_time": "0",
+        }))
+        .unwrap();
+        assert_eq!(info.doc_count, 2);
+        assert!(info.update_seq.starts_with("3-"));
+        assert_eq!(info.sizes.file, 30);
+        assert_eq!(info.cluster.unwrap().q, 2);
+        assert!(info.props.partitioned);
+
+        // CouchDB 1.x
+        let info: DatabaseInfo = serde_json::from_value(json!({
+            "db_name": "items",
+            "doc_count": 0,
+            "doc_del_count": 0,
+            "update_seq": 7,
+            "purge_seq": 0,
+            "compact_running": false,
+            "instance_start_time": "1500000000000000",
+        }))
+        .unwrap();
+        assert_eq!(info.update_seq, "7");
+        assert!(info.cluster.is_none());
+        assert!(!info.props.partitioned);
+    }
+}
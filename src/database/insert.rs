@@ -1,6 +1,6 @@
 use crate::client::Client;
-use crate::Error;
-use futures::compat::Future01CompatExt;
+use crate::path;
+use crate::{DocId, Error, Revision};
 use serde::{Deserialize, Serialize};
 
 /// A Request to insert a document into the database
@@ -9,7 +9,7 @@ where
     T: Serialize,
 {
     client: Client,
-    payload: InsertPayload<'a, T>,
+    payload: Result<InsertPayload<'a, T>, Error>,
     query: InsertRequestQuery,
 }
 
@@ -18,31 +18,49 @@ where
     T: Serialize,
 {
     pub(crate) fn new(client: &Client, document: &'a T, id: impl Into<Option<String>>) -> Self {
+        let payload = id
+            .into()
+            .map(DocId::new)
+            .transpose()
+            .map(|_id| InsertPayload {
+                _id,
+                payload: document,
+            });
+
         InsertRequest {
             client: client.into(),
-            payload: InsertPayload {
-                _id: id.into(),
-                payload: document,
-            },
+            payload,
             query: InsertRequestQuery::default(),
         }
     }
 
     /// Consume the request and send it to the database.
     ///
+    /// If the document has no id and the client has an
+    /// [IdGenerator](crate::IdGenerator), an id is generated and the document is inserted
+    /// with `PUT`; otherwise it's inserted with `POST`, and the server assigns the id.
+    /// Either way, sending the insert again makes another document- to retry an insert,
+    /// give it an id from [Database::generate_id](crate::Database::generate_id).
+    ///
     /// Returns a future that resolves to an InsertResponse.
     pub async fn send(self) -> Result<InsertResponse, Error> {
-        let response = self
-            .client
-            .post()
-            .json(&self.payload)
+        let mut payload = self.payload?;
+        let request = match (&payload._id, self.client.id_generator()) {
+            (None, Some(ids)) => {
+                let id = ids.next_id(&self.client).await?;
+                let request = self.client.join(path::document(&id))?.put();
+                payload._id = Some(id);
+                request
+            }
+            _ => self.client.post(),
+        };
+
+        let response = request
+            .json(&payload)
             .query(&self.query)
             .send()
-            .compat()
             .await?
-            .json()
-            .compat()
-            .await?;
+            .json()?;
         Ok(response)
     }
 }
@@ -50,7 +68,7 @@ where
 #[derive(Serialize)]
 pub struct InsertPayload<'a, T> {
     #[serde(skip_serializing_if = "Option::is_none")]
-    _id: Option<String>,
+    _id: Option<DocId>,
 
     #[serde(flatten)]
     payload: &'a T,
@@ -71,5 +89,5 @@ pub struct InsertResponse {
     pub ok: bool,
 
     /// The current revision of the inserted document
-    pub rev: String,
+    pub rev: Revision,
 }
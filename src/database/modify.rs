@@ -0,0 +1,273 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::convert::TryInto;
+use std::time::Duration;
+
+use super::UpdateResponse;
+use crate::client::Client;
+use crate::path;
+use crate::{DocId, Error, Revision};
+
+/// The number of times a conflicting write is retried, by default
+const DEFAULT_RETRIES: u32 = 5;
+
+/// The delay before the first retry, by default
+const DEFAULT_BACKOFF: Duration = Duration::from_millis(10);
+
+/// A read-modify-write of a single document, which retries on conflict.
+///
+/// The request is lazy- it doesn't do a thing until you call its '[send](ModifyRequest::send)'
+/// method.
+pub struct ModifyRequest<T, F> {
+    client: Client,
+    id: Result<DocId, Error>,
+    modify: F,
+    create: Option<Box<dyn Fn() -> T + Send + Sync>>,
+    retries: u32,
+    backoff: Duration,
+}
+
+impl<T, F> ModifyRequest<T, F>
+where
+    T: Serialize + DeserializeOwned,
+    F: FnMut(&mut T),
+{
+    pub(crate) fn new<I>(client: &Client, id: I, modify: F) -> Self
+    where
+        I: TryInto<DocId>,
+        Error: From<I::Error>,
+    {
+        ModifyRequest {
+            client: client.into(),
+            id: id.try_into().map_err(Error::from),
+            modify,
+            create: None,
+            retries: DEFAULT_RETRIES,
+            backoff: DEFAULT_BACKOFF,
+        }
+    }
+
+    /// The number of times to retry after a conflict, before giving up.
+    ///
+    /// Default is 5.
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// The delay before the first retry. The delay doubles with each subsequent retry.
+    ///
+    /// Default is 10 milliseconds.
+    pub fn backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// If the document doesn't exist, create it from the given function (and then modify
+    /// it) rather than failing.
+    pub fn create_with(mut self, create: impl Fn() -> T + Send + Sync + 'static) -> Self {
+        self.create = Some(Box::new(create));
+        self
+    }
+
+    /// If the document doesn't exist, create it from its default value (and then modify
+    /// it) rather than failing.
+    pub fn create_if_missing(self) -> Self
+    where
+        T: Default + 'static,
+    {
+        self.create_with(T::default)
+    }
+
+    /// Send the request.
+    ///
+    /// Returns the revision of the document after the modification.
+    ///
+    /// # Errors
+    /// This fails if the document doesn't exist (and wasn't to be created), or if the
+    /// write still conflicts after all of the retries.
+    pub async fn send(mut self) -> Result<Revision, Error> {
+        let id = self.id?;
+        let mut attempt = 0;
+
+        loop {
+            match attempt_modify(&self.client, &id, &mut self.modify, self.create.as_deref()).await
+            {
+                Err(e) if e.is_conflict() && attempt < self.retries => {
+                    let delay = self.backoff.saturating_mul(2u32.saturating_pow(attempt));
+                    log::debug!("conflict modifying '{}', retrying in {:?}", id, delay);
+                    self.client.sleep(delay).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+async fn attempt_modify<T, F>(
+    client: &Client,
+    id: &DocId,
+    modify: &mut F,
+    create: Option<&(dyn Fn() -> T + Send + Sync)>,
+) -> Result<Revision, Error>
+where
+    T: Serialize + DeserializeOwned,
+    F: FnMut(&mut T),
+{
+    let current = client
+        .join(path::document(id))?
+        .get()
+        .send()
+        .await?
+        .json::<Value>();
+
+    match (current, create) {
+        (Ok(current), _) => {
+            // the document is read from the whole body, so that a type with a `_rev` field
+            // of its own sees it (as with GetResponse)
+            let rev = Revision::deserialize(current.get("_rev").unwrap_or(&Value::Null))?;
+            let mut document = T::deserialize(current)?;
+            modify(&mut document);
+            write(client, id, &document, Some(rev)).await
+        }
+        (Err(e), Some(create)) if e.is_not_found() => {
+            let mut document = create();
+            modify(&mut document);
+            write(client, id, &document, None).await
+        }
+        (Err(e), _) => Err(e),
+    }
+}
+
+/// Write the document, with `_rev` set in its body- replacing any revision it carries
+/// itself, rather than sending a second `_rev` alongside it
+async fn write<T: Serialize>(
+    client: &Client,
+    id: &DocId,
+    document: &T,
+    rev: Option<Revision>,
+) -> Result<Revision, Error> {
+    let mut body = match serde_json::to_value(document)? {
+        Value::Object(body) => body,
+        _ => {
+            return Err(Error::Json(serde::ser::Error::custom(
+                "the document must be a JSON object",
+            )))
+        }
+    };
+    match rev {
+        Some(rev) => body.insert("_rev".to_string(), serde_json::to_value(rev)?),
+        None => body.remove("_rev"),
+    };
+
+    let response: UpdateResponse = client
+        .join(path::document(id))?
+        .put()
+        .json(&body)
+        .send()
+        .await?
+        .json()?;
+    Ok(response.rev)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{fake_database, Item};
+    use serde_json::{json, Value};
+
+    #[tokio::test]
+    async fn modify_documents() {
+        let (_, database) = fake_database("items").await;
+
+        let mut seen = None;
+        let first = database
+            .modify("item", |item: &mut Item| {
+                seen = Some(item.rev.clone());
+                item.count += 1;
+            })
+            .create_with(|| Item::new(0))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(seen, Some(None));
+
+        let second = database
+            .modify("item", |item: &mut Item| {
+                seen = Some(item.rev.clone());
+                item.count += 1;
+            })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(seen, Some(Some(first)));
+        assert_eq!(second.generation(), 2);
+
+        let item: Item = database
+            .get("item")
+            .send()
+            .await
+            .unwrap()
+            .into_inner()
+            .unwrap();
+        assert_eq!(item.rev.as_ref(), Some(&second));
+        assert_eq!(item.count, 2);
+
+        // a stale revision carried by the document itself is replaced by the current one
+        let third = database
+            .modify("item", |current: &mut Item| {
+                current.rev = Some("1-stale".parse().unwrap());
+            })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(third.generation(), 3);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn modify() {
+        let (client, database) = fake_database("items").await;
+
+        let increment = |doc: &mut Value| doc["count"] = json!(doc["count"].as_u64().unwrap() + 1);
+
+        let error = database
+            .modify("counter", increment)
+            .send()
+            .await
+            .unwrap_err();
+        assert!(error.is_not_found());
+
+        let rev = database
+            .modify("counter", increment)
+            .create_with(|| json!({ "count": 0 }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(rev.generation(), 1);
+
+        // write a competing edit the first time round, to force a conflict
+        let handle = tokio::runtime::Handle::current();
+        let competitor = client.database("items").unwrap();
+        let mut calls = 0;
+        let rev = database
+            .modify("counter", |doc: &mut Value| {
+                calls += 1;
+                if calls == 1 {
+                    tokio::task::block_in_place(|| {
+                        handle.block_on(competitor.modify("counter", increment).retries(0).send())
+                    })
+                    .unwrap();
+                }
+                increment(doc);
+            })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(calls, 2);
+        assert_eq!(rev.generation(), 3);
+
+        let doc = database.get("counter").send::<Value>().await.unwrap();
+        assert_eq!(doc.into_inner().unwrap()["count"], 3);
+    }
+}
@@ -0,0 +1,356 @@
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::convert::TryInto;
+use std::marker::PhantomData;
+
+use super::{Database, DeleteRequest};
+use crate::path;
+use crate::{DocId, Document, Error, Revision, Version};
+
+/// The field which holds the type of each document, by default
+const DEFAULT_TYPE_FIELD: &str = "type";
+
+/// The number of documents fetched per `_find` request
+const FIND_PAGE_SIZE: usize = 100;
+
+/// Access to the documents of a single type, within a database shared with other types.
+///
+/// Every document written through the repository is tagged with a type field (`type`, by
+/// default), and every document read through it is checked for that tag. Documents are
+/// given ids prefixed with the type, such as `item:4f9e1d...`, so that the documents of
+/// each type can be listed cheaply.
+///
+/// # Example
+/// ```no_run
+/// # #[cfg(feature = "derive")]
+/// # mod example {
+/// use chesterfield::{Client, DocId, Document, Revision};
+/// use serde::{Deserialize, Serialize};
+/// use serde_json::json;
+///
+/// #[derive(Serialize, Deserialize, Document)]
+/// struct Item {
+///     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+///     id: Option<DocId>,
+///     #[serde(rename = "_rev", skip_serializing_if = "Option::is_none")]
+///     rev: Option<Revision>,
+///     price: u32,
+/// }
+///
+/// # async fn run() {
+/// let client = Client::from_url_str("http://localhost:5984").unwrap();
+/// let items = client.database("shop").unwrap().repository::<Item>("item");
+///
+/// let mut item = Item { id: None, rev: None, price: 10 };
+/// items.save(&mut item).await.unwrap();
+///
+/// let cheap: Vec<Item> = items.find(json!({ "price": { "$lt": 20 } })).await.unwrap();
+/// # }
+/// # }
+/// ```
+pub struct Repository<T> {
+    database: Database,
+    kind: String,
+    field: String,
+    document: PhantomData<fn() -> T>,
+}
+
+impl<T: Document> Repository<T> {
+    pub(crate) fn new(database: Database, kind: impl Into<String>) -> Self {
+        Repository {
+            database,
+            kind: kind.into(),
+            field: DEFAULT_TYPE_FIELD.to_string(),
+            document: PhantomData,
+        }
+    }
+
+    /// Use a different field to hold the type of each document.
+    ///
+    /// Default is `type`.
+    pub fn type_field(mut self, field: impl Into<String>) -> Self {
+        self.field = field.into();
+        self
+    }
+
+    /// The type of the documents in this repository
+    pub fn kind(&self) -> &str {
+        &self.kind
+    }
+
+    /// The id of the document with the given key, such as `item:{key}`.
+    ///
+    /// # Errors
+    /// This method fails if the resulting id is not a valid document id
+    pub fn id(&self, key: impl AsRef<str>) -> Result<DocId, Error> {
+        DocId::new(format!("{}:{}", self.kind, key.as_ref()))
+    }
+
+    /// A new, random document id, such as `item:4f9e1d2c...`.
+    ///
+    /// # Errors
+    /// This method fails if the resulting id is not a valid document id
+    pub fn new_id(&self) -> Result<DocId, Error> {
+        self.id(uuid::Uuid::new_v4().simple().to_string())
+    }
+
+    /// Retrieve a document by id.
+    ///
+    /// # Errors
+    /// As well as the usual errors, this fails if the document is of another type
+    pub async fn get<I>(&self, id: I) -> Result<T, Error>
+    where
+        I: TryInto<DocId>,
+        Error: From<I::Error>,
+    {
+        let id = id.try_into()?;
+        let document = self
+            .database
+            .client
+            .join(path::document(&id))?
+            .get()
+            .send()
+            .await?
+            .json()?;
+        self.untag(document)
+    }
+
+    /// Save a document, inserting or updating it as appropriate.
+    ///
+    /// A document without an id is given a [new_id](Repository::new_id). The new revision
+    /// (and id) is written back into the document.
+    pub async fn save(&self, document: &mut T) -> Result<(), Error> {
+        if document.id().is_none() {
+            document.set_id(self.new_id()?);
+        }
+        let body = self.tag(document)?;
+        let response = self.database.write(document.id(), &body).await?;
+        document.set_rev(response.rev);
+        Ok(())
+    }
+
+    /// Delete a document.
+    ///
+    /// Returns the revision of the deletion 'tombstone'.
+    ///
+    /// # Errors
+    /// As well as the usual errors, this fails if the document has never been saved
+    pub async fn delete(&self, document: &T) -> Result<Revision, Error> {
+        let id = document
+            .id()
+            .ok_or_else(|| Error::InvalidDocId("the document has no id".to_string()))?;
+        let rev = document.rev().ok_or_else(|| {
+            Error::InvalidRevision("the document has never been saved".to_string())
+        })?;
+        let response = DeleteRequest::new(&self.database.client, id, rev)
+            .send()
+            .await?;
+        Ok(response.rev)
+    }
+
+    /// Retrieve every document of this type, in id order.
+    ///
+    /// This relies on the documents having ids prefixed by their type.
+    ///
+    /// # Errors
+    /// As well as the usual errors, this fails if any of the documents is of another type
+    pub async fn list(&self) -> Result<Vec<T>, Error> {
+        let prefix = format!("{}:", self.kind);
+        let start_key = serde_json::to_string(&prefix)?;
+        let end_key = serde_json::to_string(&format!("{}\u{fff0}", prefix))?;
+
+        let response: AllDocs = self
+            .database
+            .client
+            .join(["_all_docs"])?
+            .get()
+            .query(&[
+                ("include_docs", "true"),
+                ("startkey", &start_key),
+                ("endkey", &end_key),
+            ])
+            .send()
+            .await?
+            .json()?;
+
+        response
+            .rows
+            .into_iter()
+            .filter_map(|row| row.doc)
+            .map(|doc| self.untag(doc))
+            .collect()
+    }
+
+    /// Retrieve the documents of this type which match a Mango selector.
+    ///
+    /// See [CouchDB API docs](https://docs.couchdb.org/en/stable/api/database/find.html#selector-syntax)
+    /// for the selector syntax. Paging through the results needs CouchDB 2.1 or later; older
+    /// servers fail with [Unsupported](Error::Unsupported).
+    pub async fn find(&self, selector: Value) -> Result<Vec<T>, Error> {
+        self.database
+            .client
+            .require_version(Version::new(2, 1, 0), "_find with bookmarks")
+            .await?;
+
+        let selector = json!({ "$and": [selector, { self.field.as_str(): self.kind }] });
+        let mut bookmark: Option<String> = None;
+        let mut documents = Vec::new();
+
+        loop {
+            let mut body = json!({ "selector": selector, "limit": FIND_PAGE_SIZE });
+            if let Some(bookmark) = &bookmark {
+                body["bookmark"] = json!(bookmark);
+            }
+            let page: FindResponse = self
+                .database
+                .client
+                .join(["_find"])?
+                .post()
+                .json(&body)
+                .send()
+                .await?
+                .json()?;
+
+            let count = page.docs.len();
+            for doc in page.docs {
+                documents.push(self.untag(doc)?);
+            }
+            match page.bookmark {
+                Some(next) if count == FIND_PAGE_SIZE => bookmark = Some(next),
+                _ => return Ok(documents),
+            }
+        }
+    }
+
+    /// Serialise a document, and tag it with its type
+    fn tag(&self, document: &T) -> Result<Map<String, Value>, Error> {
+        let mut map = crate::document::body(document)?;
+        map.insert(self.field.clone(), Value::from(self.kind.as_str()));
+        Ok(map)
+    }
+
+    /// Check the type of a document, and deserialise it
+    fn untag(&self, mut document: Value) -> Result<T, Error> {
+        let kind = document
+            .as_object_mut()
+            .and_then(|map| map.remove(&self.field));
+        match kind {
+            Some(Value::String(kind)) if kind == self.kind => Ok(serde_json::from_value(document)?),
+            kind => Err(Error::WrongDocumentType(format!(
+                "expected '{}' to be '{}', found {}",
+                self.field,
+                self.kind,
+                kind.map(|kind| kind.to_string())
+                    .unwrap_or_else(|| "nothing".to_string()),
+            ))),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct AllDocs {
+    rows: Vec<AllDocsRow>,
+}
+
+#[derive(Deserialize)]
+struct AllDocsRow {
+    doc: Option<Value>,
+}
+
+#[derive(Deserialize)]
+struct FindResponse {
+    docs: Vec<Value>,
+    bookmark: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{fake_database, Item};
+    use crate::GetResponse;
+
+    #[tokio::test]
+    async fn repository() {
+        let (_, database) = fake_database("shop").await;
+        let items = database.repository::<Item>("item");
+
+        let mut cheap = Item::new(5);
+        items.save(&mut cheap).await.unwrap();
+        assert!(cheap.id.as_ref().unwrap().starts_with("item:"));
+
+        let mut dear = Item {
+            id: Some(items.id("dear").unwrap()),
+            ..Item::new(500)
+        };
+        items.save(&mut dear).await.unwrap();
+        assert_eq!(dear.id.as_ref().unwrap(), "item:dear");
+
+        // the type field is written, but not exposed
+        let raw: Value = database
+            .get("item:dear")
+            .send()
+            .await
+            .unwrap()
+            .into_inner()
+            .unwrap();
+        assert_eq!(raw["type"], "item");
+        let fetched = items.get("item:dear").await.unwrap();
+        assert_eq!(fetched.count, 500);
+        assert_eq!(fetched.rev, dear.rev);
+
+        // documents of other types are rejected, and excluded
+        database
+            .insert(
+                &json!({ "type": "user", "count": 1 }),
+                "user:ada".to_string(),
+            )
+            .send()
+            .await
+            .unwrap();
+        database
+            .insert(
+                &json!({ "type": "user", "count": 2 }),
+                "item:impostor".to_string(),
+            )
+            .send()
+            .await
+            .unwrap();
+        assert!(matches!(
+            items.get("user:ada").await,
+            Err(Error::WrongDocumentType(_))
+        ));
+        assert!(matches!(
+            items.list().await,
+            Err(Error::WrongDocumentType(_))
+        ));
+        let impostor: GetResponse = database.get("item:impostor").send().await.unwrap();
+        database
+            .delete("item:impostor", &impostor.meta_data()._rev)
+            .send()
+            .await
+            .unwrap();
+
+        let listed = items.list().await.unwrap();
+        assert_eq!(listed.len(), 2);
+
+        let found = items
+            .find(json!({ "count": { "$lt": 100 } }))
+            .await
+            .unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].id, cheap.id);
+        assert!(items
+            .find(json!({ "count": { "$lt": 2 } }))
+            .await
+            .unwrap()
+            .is_empty());
+
+        items.delete(&cheap).await.unwrap();
+        assert!(items
+            .get(cheap.id.unwrap())
+            .await
+            .unwrap_err()
+            .is_not_found());
+        assert_eq!(items.list().await.unwrap().len(), 1);
+    }
+}
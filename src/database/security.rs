@@ -0,0 +1,181 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::client::Client;
+use crate::Error;
+
+/// Who may administer, and who may read and write, a database.
+///
+/// A database with no member names or roles is public: anybody may read and write it.
+///
+/// see [CouchDB API docs](https://docs.couchdb.org/en/stable/api/database/security.html)
+/// for details.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SecurityObject {
+    /// The users and roles who may administer the database, such as by changing its design
+    /// documents or its security object
+    #[serde(default)]
+    pub admins: SecurityMembers,
+
+    /// The users and roles who may read and write the database
+    #[serde(default)]
+    pub members: SecurityMembers,
+
+    /// Every other field of the security object, which CouchDB keeps but ignores
+    #[serde(flatten)]
+    pub other: Map<String, Value>,
+}
+
+impl SecurityObject {
+    /// Whether anybody may read and write the database
+    pub fn is_public(&self) -> bool {
+        self.members.is_empty()
+    }
+}
+
+/// The user names and roles in one section of a [SecurityObject]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SecurityMembers {
+    /// The names of individual users
+    #[serde(default)]
+    pub names: Vec<String>,
+
+    /// The roles, any user with one of which is included
+    #[serde(default)]
+    pub roles: Vec<String>,
+}
+
+impl SecurityMembers {
+    /// Whether there are no names or roles
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty() && self.roles.is_empty()
+    }
+
+    /// Add a user, returning false if they were already present
+    pub fn add_name(&mut self, name: impl Into<String>) -> bool {
+        add(&mut self.names, name.into())
+    }
+
+    /// Remove a user, returning false if they weren't present
+    pub fn remove_name(&mut self, name: &str) -> bool {
+        remove(&mut self.names, name)
+    }
+
+    /// Add a role, returning false if it was already present
+    pub fn add_role(&mut self, role: impl Into<String>) -> bool {
+        add(&mut self.roles, role.into())
+    }
+
+    /// Remove a role, returning false if it wasn't present
+    pub fn remove_role(&mut self, role: &str) -> bool {
+        remove(&mut self.roles, role)
+    }
+}
+
+fn add(values: &mut Vec<String>, value: String) -> bool {
+    if values.contains(&value) {
+        return false;
+    }
+    values.push(value);
+    true
+}
+
+fn remove(values: &mut Vec<String>, value: &str) -> bool {
+    let before = values.len();
+    values.retain(|existing| existing != value);
+    values.len() != before
+}
+
+pub(crate) async fn get(client: &Client) -> Result<SecurityObject, Error> {
+    client.join(["_security"])?.get().send().await?.json()
+}
+
+pub(crate) async fn put(client: &Client, security: &SecurityObject) -> Result<(), Error> {
+    client
+        .join(["_security"])?
+        .put()
+        .json(security)
+        .send()
+        .await?
+        .error_for_status()
+        .map(|_| ())
+}
+
+/// Read the security object, modify it, and write it back if it changed.
+///
+/// This isn't atomic: a change made by somebody else in between is overwritten.
+pub(crate) async fn modify<F>(client: &Client, modify: F) -> Result<SecurityObject, Error>
+where
+    F: FnOnce(&mut SecurityObject),
+{
+    let current = get(client).await?;
+    let mut security = current.clone();
+    modify(&mut security);
+    if security != current {
+        put(client, &security).await?;
+    }
+    Ok(security)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fake_database;
+    use serde_json::json;
+
+    #[test]
+    fn deserialize() {
+        let security: SecurityObject = serde_json::from_value(json!({})).unwrap();
+        assert!(security.is_public());
+        assert_eq!(
+            serde_json::to_value(&security).unwrap(),
+            json!({
+                "admins": { "names": [], "roles": [] },
+                "members": { "names": [], "roles": [] },
+            })
+        );
+
+        let mut security: SecurityObject = serde_json::from_value(json!({
+            "admins": { "names": ["alice"] },
+            "members": { "roles": ["staff"] },
+            "couchdb_auth_only": true,
+        }))
+        .unwrap();
+        assert!(!security.is_public());
+        assert_eq!(security.other["couchdb_auth_only"], true);
+
+        assert!(!security.admins.add_name("alice"));
+        assert!(security.admins.add_role("ops"));
+        assert!(security.members.remove_role("staff"));
+        assert!(!security.members.remove_role("staff"));
+        assert!(security.is_public());
+    }
+
+    #[tokio::test]
+    async fn security() {
+        let (_, database) = fake_database("items").await;
+
+        assert!(database.security().await.unwrap().is_public());
+
+        let mut security = crate::SecurityObject::default();
+        security.admins.add_name("alice");
+        security.members.add_role("staff");
+        database.set_security(&security).await.unwrap();
+        assert_eq!(database.security().await.unwrap(), security);
+
+        let modified = database
+            .modify_security(|security| {
+                security.admins.remove_name("alice");
+                security.admins.add_role("ops");
+                security.members.add_name("bob");
+            })
+            .await
+            .unwrap();
+        let stored = database.security().await.unwrap();
+        assert_eq!(stored, modified);
+        assert!(stored.admins.names.is_empty());
+        assert_eq!(stored.admins.roles, vec!["ops"]);
+        assert_eq!(stored.members.names, vec!["bob"]);
+        assert_eq!(stored.members.roles, vec!["staff"]);
+    }
+}
@@ -1,8 +1,9 @@
 use serde::{Deserialize, Serialize};
 
 use crate::client::Client;
-use crate::Error;
-use futures::compat::Future01CompatExt;
+use crate::path;
+use crate::{DocId, Error, Revision};
+use std::convert::TryInto;
 
 /// A request to update an existing document.
 pub struct UpdateRequest<'a, T>
@@ -10,27 +11,33 @@ where
     T: Serialize,
 {
     client: Client,
-    _id: String,
-    payload: UpdatePayload<'a, T>,
+    _id: Result<DocId, Error>,
+    payload: Result<UpdatePayload<'a, T>, Error>,
 }
 
 impl<'a, T> UpdateRequest<'a, T>
 where
     T: Serialize,
 {
-    pub(crate) fn new(
-        client: &Client,
-        document: &'a T,
-        id: impl Into<String>,
-        rev: impl Into<String>,
-    ) -> Self {
+    pub(crate) fn new<I, R>(client: &Client, document: &'a T, id: I, rev: R) -> Self
+    where
+        I: TryInto<DocId>,
+        Error: From<I::Error>,
+        R: TryInto<Revision>,
+        Error: From<R::Error>,
+    {
+        let payload = rev
+            .try_into()
+            .map_err(Error::from)
+            .map(|_rev| UpdatePayload {
+                _rev,
+                payload: document,
+            });
+
         UpdateRequest {
             client: client.into(),
-            _id: id.into(),
-            payload: UpdatePayload {
-                _rev: rev.into(),
-                payload: document,
-            },
+            _id: id.try_into().map_err(Error::from),
+            payload,
         }
     }
 
@@ -38,30 +45,33 @@ where
     pub async fn send(self) -> Result<UpdateResponse, Error> {
         let response = self
             .client
-            .join(&self._id)?
+            .join(path::document(&self._id?))?
             .put()
-            .json(&self.payload)
+            .json(&self.payload?)
             .send()
-            .compat()
             .await?
-            .json()
-            .compat()
-            .await?;
+            .json()?;
         Ok(response)
     }
 }
 
 #[derive(Serialize)]
 pub struct UpdatePayload<'a, T> {
-    _rev: String,
+    _rev: Revision,
 
     #[serde(flatten)]
     payload: &'a T,
 }
 
+/// Reponse from the CouchDB database after updating a document
 #[derive(Deserialize)]
 pub struct UpdateResponse {
+    /// The _id of the updated document
     pub id: String,
+
+    /// Update operation status
     pub ok: bool,
-    pub rev: String,
+
+    /// The new revision of the updated document
+    pub rev: Revision,
 }
@@ -0,0 +1,266 @@
+//! Documents which carry their own id and revision.
+
+use crate::{DocId, Error, Revision};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// A type which is stored as a CouchDB document, and which carries its own `_id` and `_rev`.
+///
+/// Implementing this trait (usually with `#[derive(Document)]`, behind the `derive` feature)
+/// lets the document be passed to [Database::save](crate::Database::save), which inserts
+/// or updates it as appropriate and writes the new revision back into it.
+///
+/// The type is serialised as-is, so the id and revision fields must be (de)serialised as
+/// `_id` and `_rev`, and should be skipped when they're empty.
+///
+/// # Example
+/// ```
+/// use chesterfield::{DocId, Document, Revision};
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Item {
+///     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+///     id: Option<DocId>,
+///     #[serde(rename = "_rev", skip_serializing_if = "Option::is_none")]
+///     rev: Option<Revision>,
+///     name: String,
+/// }
+///
+/// impl Document for Item {
+///     fn id(&self) -> Option<&DocId> {
+///         self.id.as_ref()
+///     }
+///
+///     fn set_id(&mut self, id: DocId) {
+///         self.id = Some(id);
+///     }
+///
+///     fn rev(&self) -> Option<&Revision> {
+///         self.rev.as_ref()
+///     }
+///
+///     fn set_rev(&mut self, rev: Revision) {
+///         self.rev = Some(rev);
+///     }
+/// }
+/// ```
+pub trait Document: Serialize + DeserializeOwned {
+    /// The id of the document.
+    ///
+    /// This may be `None` for a new document, in which case the database assigns one.
+    fn id(&self) -> Option<&DocId>;
+
+    /// Set the id of the document (after the database has assigned one)
+    fn set_id(&mut self, id: DocId);
+
+    /// The current revision of the document, or `None` if it hasn't been saved yet
+    fn rev(&self) -> Option<&Revision>;
+
+    /// Set the revision of the document (after it has been saved)
+    fn set_rev(&mut self, rev: Revision);
+
+    /// Whether the document is a deletion 'tombstone'.
+    ///
+    /// [Database::save](crate::Database::save) writes such a document with
+    /// `"_deleted": true`, which deletes it.
+    fn deleted(&self) -> bool {
+        false
+    }
+
+    /// The `_attachments` of the document, if it has any
+    fn attachments(&self) -> Option<&Value> {
+        None
+    }
+}
+
+/// The body to write for a document, marked `_deleted` if it's a tombstone
+pub(crate) fn body<D: Document>(document: &D) -> Result<Map<String, Value>, Error> {
+    let mut body = match serde_json::to_value(document)? {
+        Value::Object(body) => body,
+        _ => {
+            return Err(Error::Json(serde::ser::Error::custom(
+                "documents must serialise to a JSON object",
+            )))
+        }
+    };
+    if document.deleted() {
+        body.insert("_deleted".to_string(), Value::Bool(true));
+    }
+    Ok(body)
+}
+
+/// Helpers for the code generated by `#[derive(Document)]`. Not public API.
+#[doc(hidden)]
+pub mod __private {
+    use crate::{DocId, Revision};
+    pub use serde_json::Value;
+
+    /// A field which can hold a value which may be absent
+    pub trait Field<T> {
+        fn get(&self) -> Option<&T>;
+        fn set(&mut self, value: T);
+    }
+
+    impl<T> Field<T> for T {
+        fn get(&self) -> Option<&T> {
+            Some(self)
+        }
+
+        fn set(&mut self, value: T) {
+            *self = value;
+        }
+    }
+
+    impl<T> Field<T> for Option<T> {
+        fn get(&self) -> Option<&T> {
+            self.as_ref()
+        }
+
+        fn set(&mut self, value: T) {
+            *self = Some(value);
+        }
+    }
+
+    pub fn id(field: &impl Field<DocId>) -> Option<&DocId> {
+        field.get()
+    }
+
+    pub fn set_id(field: &mut impl Field<DocId>, id: DocId) {
+        field.set(id)
+    }
+
+    pub fn rev(field: &impl Field<Revision>) -> Option<&Revision> {
+        field.get()
+    }
+
+    pub fn set_rev(field: &mut impl Field<Revision>, rev: Revision) {
+        field.set(rev)
+    }
+
+    pub fn deleted(field: &impl Field<bool>) -> bool {
+        field.get().copied().unwrap_or(false)
+    }
+
+    pub fn attachments(field: &impl Field<Value>) -> Option<&Value> {
+        field.get()
+    }
+}
+
+#[cfg(all(test, feature = "derive"))]
+mod tests {
+    use crate::Document;
+    use crate::{DocId, Revision};
+    use serde::{Deserialize, Serialize};
+    use serde_json::{json, Value};
+
+    #[derive(Serialize, Deserialize, Document)]
+    struct Item {
+        #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+        id: Option<DocId>,
+        #[serde(rename = "_rev", skip_serializing_if = "Option::is_none")]
+        rev: Option<Revision>,
+        name: String,
+    }
+
+    #[derive(Serialize, Deserialize, Document)]
+    struct Named {
+        _id: DocId,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        _rev: Option<Revision>,
+        #[serde(default)]
+        _deleted: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        _attachments: Option<Value>,
+        // ordinary fields, despite their names
+        deleted: bool,
+        attachments: u32,
+    }
+
+    #[derive(Serialize, Deserialize, Document)]
+    struct Tagged {
+        #[document(id)]
+        #[serde(rename = "_id")]
+        key: DocId,
+        #[document(rev)]
+        #[serde(rename = "_rev", skip_serializing_if = "Option::is_none")]
+        version: Option<Revision>,
+        #[document(deleted)]
+        #[serde(rename = "_deleted", default)]
+        gone: bool,
+        #[document(attachments)]
+        #[serde(rename = "_attachments", skip_serializing_if = "Option::is_none")]
+        files: Option<Value>,
+    }
+
+    #[test]
+    fn derive_by_name() {
+        let mut item = Item {
+            id: None,
+            rev: None,
+            name: String::from("thing"),
+        };
+        assert_eq!(item.id(), None);
+        assert!(!item.deleted());
+
+        item.set_id(DocId::new("a").unwrap());
+        item.set_rev("1-abc".parse().unwrap());
+        assert_eq!(item.id().unwrap(), "a");
+        assert_eq!(item.rev().unwrap().generation(), 1);
+        assert_eq!(
+            serde_json::to_value(&item).unwrap(),
+            json!({ "_id": "a", "_rev": "1-abc", "name": "thing" })
+        );
+    }
+
+    #[test]
+    fn derive_metadata_by_name() {
+        let named: Named = serde_json::from_value(json!({
+            "_id": "a",
+            "_deleted": true,
+            "_attachments": { "a.txt": {} },
+            "deleted": false,
+            "attachments": 0,
+        }))
+        .unwrap();
+        assert!(named.deleted());
+        assert!(named.attachments().is_some());
+
+        let named: Named = serde_json::from_value(json!({
+            "_id": "a",
+            "deleted": true,
+            "attachments": 1,
+        }))
+        .unwrap();
+        assert!(!named.deleted());
+        assert!(named.attachments().is_none());
+    }
+
+    #[test]
+    fn derive_by_attribute() {
+        let mut tagged: Tagged = serde_json::from_value(json!({
+            "_id": "b",
+            "_deleted": true,
+            "_attachments": { "a.txt": {} },
+        }))
+        .unwrap();
+        assert_eq!(tagged.id().unwrap(), "b");
+        assert_eq!(tagged.rev(), None);
+        assert!(tagged.deleted());
+        assert!(tagged.attachments().is_some());
+
+        tagged.set_id(DocId::new("c").unwrap());
+        tagged.set_rev("2-def".parse().unwrap());
+        assert_eq!(tagged.key, "c");
+        assert_eq!(
+            serde_json::to_value(&tagged).unwrap(),
+            json!({
+                "_id": "c",
+                "_rev": "2-def",
+                "_deleted": true,
+                "_attachments": { "a.txt": {} },
+            })
+        );
+    }
+}
@@ -1,33 +1,199 @@
-pub use reqwest::UrlError;
+use crate::UrlError;
+use http::StatusCode;
+use serde::Deserialize;
 
 #[derive(Debug)]
 /// A catch-all error type for everything that can (and does, currently)
 /// go wrong with this library
 pub enum ChesterfieldError {
     /// An error reported by the underlying reqwest library.
+    #[cfg(feature = "reqwest")]
     Reqwest(reqwest::Error),
 
+    /// An error reported by a (non-reqwest) HTTP transport.
+    Transport(Box<dyn std::error::Error + Send + Sync>),
+
     /// An error related to the parsing of a URL.
-    Url(reqwest::UrlError),
+    Url(UrlError),
+
+    /// A name or id which can't be represented in a URL path.
+    InvalidPath(String),
+
+    /// A database name which CouchDB would reject.
+    InvalidDatabaseName(String),
+
+    /// A document id which CouchDB would reject.
+    InvalidDocId(String),
+
+    /// A document revision which couldn't be parsed.
+    InvalidRevision(String),
+
+    /// A document which isn't of the type a [Repository](crate::Repository) expected.
+    WrongDocumentType(String),
+
+    /// A request which the server's version of CouchDB doesn't support.
+    Unsupported(String),
+
+    /// An operation which didn't finish in the time allowed.
+    Timeout(String),
+
+    /// Metrics in the Prometheus text format which couldn't be parsed.
+    InvalidMetrics(String),
+
+    /// An error constructing an HTTP request.
+    Http(http::Error),
+
+    /// An error serialising query parameters.
+    Query(serde_urlencoded::ser::Error),
+
+    /// An error serialising or deserialising JSON.
+    Json(serde_json::Error),
+
+    /// An I/O error.
+    Io(std::io::Error),
+
+    /// An error response from CouchDB itself.
+    CouchDb(StatusCode, ErrorResponse),
+}
+
+impl ChesterfieldError {
+    /// The HTTP status of the CouchDB error response, if this is one
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            ChesterfieldError::CouchDb(status, _) => Some(*status),
+            _ => None,
+        }
+    }
+
+    /// Whether this is a `409 Conflict` response- usually because the revision of a
+    /// document was out of date
+    pub fn is_conflict(&self) -> bool {
+        self.status() == Some(StatusCode::CONFLICT)
+    }
+
+    /// Whether this is a `404 Not Found` response
+    pub fn is_not_found(&self) -> bool {
+        self.status() == Some(StatusCode::NOT_FOUND)
+    }
+
+    /// Whether this is a `412 file_exists` response- from creating a database which
+    /// already exists
+    pub fn is_file_exists(&self) -> bool {
+        match self {
+            ChesterfieldError::CouchDb(status, body) => {
+                *status == StatusCode::PRECONDITION_FAILED && body.error == "file_exists"
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether this is a `304 Not Modified` response to a conditional request, such as
+    /// one made with [GetRequest::if_none_match](crate::GetRequest::if_none_match)
+    pub fn is_not_modified(&self) -> bool {
+        self.status() == Some(StatusCode::NOT_MODIFIED)
+    }
+}
+
+/// The body of an error response from CouchDB.
+///
+/// See [CouchDB API docs](https://docs.couchdb.org/en/stable/api/basics.html#http-status-codes)
+/// for the errors it can return.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ErrorResponse {
+    /// The kind of error, such as `conflict` or `not_found`
+    pub error: String,
+
+    /// A human-readable description of the error
+    pub reason: String,
+}
+
+impl ErrorResponse {
+    /// The status CouchDB uses for this kind of error.
+    ///
+    /// Errors which are reported per-document (in the response to a `_bulk_docs`
+    /// request, for example) don't come with their own status.
+    pub(crate) fn status(&self) -> StatusCode {
+        match self.error.as_str() {
+            "bad_request" => StatusCode::BAD_REQUEST,
+            "unauthorized" => StatusCode::UNAUTHORIZED,
+            "forbidden" => StatusCode::FORBIDDEN,
+            "not_found" => StatusCode::NOT_FOUND,
+            "conflict" => StatusCode::CONFLICT,
+            "file_exists" => StatusCode::PRECONDITION_FAILED,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
 }
 
+impl From<ErrorResponse> for ChesterfieldError {
+    fn from(e: ErrorResponse) -> Self {
+        ChesterfieldError::CouchDb(e.status(), e)
+    }
+}
+
+#[cfg(feature = "reqwest")]
 impl From<reqwest::Error> for ChesterfieldError {
     fn from(e: reqwest::Error) -> Self {
         ChesterfieldError::Reqwest(e)
     }
 }
 
-impl From<reqwest::UrlError> for ChesterfieldError {
-    fn from(e: reqwest::UrlError) -> Self {
+impl From<std::convert::Infallible> for ChesterfieldError {
+    fn from(e: std::convert::Infallible) -> Self {
+        match e {}
+    }
+}
+
+impl From<UrlError> for ChesterfieldError {
+    fn from(e: UrlError) -> Self {
         ChesterfieldError::Url(e)
     }
 }
 
+impl From<http::Error> for ChesterfieldError {
+    fn from(e: http::Error) -> Self {
+        ChesterfieldError::Http(e)
+    }
+}
+
+impl From<serde_urlencoded::ser::Error> for ChesterfieldError {
+    fn from(e: serde_urlencoded::ser::Error) -> Self {
+        ChesterfieldError::Query(e)
+    }
+}
+
+impl From<serde_json::Error> for ChesterfieldError {
+    fn from(e: serde_json::Error) -> Self {
+        ChesterfieldError::Json(e)
+    }
+}
+
+impl From<std::io::Error> for ChesterfieldError {
+    fn from(e: std::io::Error) -> Self {
+        ChesterfieldError::Io(e)
+    }
+}
+
 impl std::error::Error for ChesterfieldError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
+            #[cfg(feature = "reqwest")]
             ChesterfieldError::Reqwest(e) => Some(e),
+            ChesterfieldError::Transport(e) => Some(e.as_ref()),
             ChesterfieldError::Url(e) => Some(e),
+            ChesterfieldError::InvalidPath(_) => None,
+            ChesterfieldError::InvalidDatabaseName(_) => None,
+            ChesterfieldError::InvalidDocId(_) => None,
+            ChesterfieldError::InvalidRevision(_) => None,
+            ChesterfieldError::WrongDocumentType(_) => None,
+            ChesterfieldError::Unsupported(_) => None,
+            ChesterfieldError::Timeout(_) => None,
+            ChesterfieldError::InvalidMetrics(_) => None,
+            ChesterfieldError::Http(e) => Some(e),
+            ChesterfieldError::Query(e) => Some(e),
+            ChesterfieldError::Json(e) => Some(e),
+            ChesterfieldError::Io(e) => Some(e),
+            ChesterfieldError::CouchDb(..) => None,
         }
     }
 }
@@ -35,8 +201,25 @@ impl std::error::Error for ChesterfieldError {
 impl std::fmt::Display for ChesterfieldError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
+            #[cfg(feature = "reqwest")]
             ChesterfieldError::Reqwest(e) => write!(f, "reqwest error: {}", e),
+            ChesterfieldError::Transport(e) => write!(f, "transport error: {}", e),
             ChesterfieldError::Url(e) => write!(f, "url error: {}", e),
+            ChesterfieldError::InvalidPath(e) => write!(f, "invalid path: {}", e),
+            ChesterfieldError::InvalidDatabaseName(e) => write!(f, "invalid database name: {}", e),
+            ChesterfieldError::InvalidDocId(e) => write!(f, "invalid document id: {}", e),
+            ChesterfieldError::InvalidRevision(e) => write!(f, "invalid revision: {}", e),
+            ChesterfieldError::WrongDocumentType(e) => write!(f, "wrong document type: {}", e),
+            ChesterfieldError::Unsupported(e) => write!(f, "unsupported by server: {}", e),
+            ChesterfieldError::Timeout(e) => write!(f, "timed out: {}", e),
+            ChesterfieldError::InvalidMetrics(e) => write!(f, "invalid metrics: {}", e),
+            ChesterfieldError::Http(e) => write!(f, "http error: {}", e),
+            ChesterfieldError::Query(e) => write!(f, "query error: {}", e),
+            ChesterfieldError::Json(e) => write!(f, "json error: {}", e),
+            ChesterfieldError::Io(e) => write!(f, "io error: {}", e),
+            ChesterfieldError::CouchDb(status, e) => {
+                write!(f, "couchdb error ({}): {}: {}", status, e.error, e.reason)
+            }
         }
     }
 }
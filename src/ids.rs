@@ -0,0 +1,279 @@
+//! Generating document ids on the client, so that inserts can use `PUT`.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::client::Client;
+use crate::{DocId, Error};
+
+/// The largest value of a sequential id's suffix, before a new prefix is chosen
+const SEQUENCE_LIMIT: u32 = 0xfff000;
+
+/// The most a sequential id's suffix is incremented by
+const SEQUENCE_STEP: u32 = 0xffe;
+
+/// The number of ids fetched from `_uuids` at a time, by default
+const DEFAULT_BATCH_SIZE: usize = 100;
+
+/// How to generate the ids of documents inserted without one.
+///
+/// Without a generator, such documents are inserted with `POST`, and CouchDB assigns their
+/// ids. With a generator, the id is chosen on the client and the document is inserted with
+/// `PUT`- which lets you pick the algorithm (and avoids a round trip for each `_uuids`).
+///
+/// A fresh id is generated each time such an insert is sent, so this alone doesn't make
+/// inserts safe to retry: if the response is lost and the insert is sent again, the
+/// document is inserted twice. To retry safely, choose the id first with
+/// [Database::generate_id](crate::Database::generate_id) and insert the document with it;
+/// a retried insert then fails with a conflict rather than making a duplicate.
+///
+/// The generator is set for a whole [Client] with
+/// [with_id_generator](Client::with_id_generator). It's cheap to clone, and clones share
+/// their state.
+///
+/// # Example
+/// ```no_run
+/// use chesterfield::{Client, IdGenerator};
+///
+/// # async fn run() {
+/// let client = Client::from_url_str("http://localhost:5984")
+///     .unwrap()
+///     .with_id_generator(IdGenerator::sequential());
+///
+/// let database = client.database("items").unwrap();
+///
+/// // choose the id up front, so the insert can be retried with the same one
+/// let id = database.generate_id().await.unwrap();
+/// let response = database
+///     .insert(&serde_json::json!({ "name": "chair" }), id.to_string())
+///     .send()
+///     .await
+///     .unwrap();
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct IdGenerator {
+    kind: Kind,
+}
+
+#[derive(Clone)]
+enum Kind {
+    Random,
+    Sequential(Arc<Mutex<Sequence>>),
+    UtcRandom,
+    Server {
+        batch_size: usize,
+        ids: Arc<Mutex<VecDeque<String>>>,
+    },
+}
+
+/// The state of CouchDB's `sequential` algorithm
+struct Sequence {
+    prefix: String,
+    suffix: u32,
+}
+
+impl Sequence {
+    fn new() -> Self {
+        Sequence {
+            prefix: random_hex(26),
+            suffix: random() as u32 % SEQUENCE_LIMIT,
+        }
+    }
+
+    fn next(&mut self) -> String {
+        self.suffix += 1 + random() as u32 % SEQUENCE_STEP;
+        if self.suffix >= SEQUENCE_LIMIT {
+            *self = Sequence::new();
+        }
+        format!("{}{:06x}", self.prefix, self.suffix)
+    }
+}
+
+impl IdGenerator {
+    /// Random (version 4) UUIDs, like CouchDB's `random` algorithm
+    pub fn random() -> Self {
+        IdGenerator { kind: Kind::Random }
+    }
+
+    /// Ids which increase monotonically, like CouchDB's `sequential` algorithm.
+    ///
+    /// Each id is a random 26 character prefix followed by a 6 character suffix, which
+    /// increases by a random amount with each id; a new prefix is chosen when the suffix
+    /// overflows. Ids which sort together are kinder to CouchDB's B-trees than random ones.
+    pub fn sequential() -> Self {
+        IdGenerator {
+            kind: Kind::Sequential(Arc::new(Mutex::new(Sequence::new()))),
+        }
+    }
+
+    /// Ids which are ordered by time, like CouchDB's `utc_random` algorithm.
+    ///
+    /// Each id is the current time, in microseconds since the epoch, as 14 hex characters,
+    /// followed by 18 random hex characters.
+    pub fn utc_random() -> Self {
+        IdGenerator {
+            kind: Kind::UtcRandom,
+        }
+    }
+
+    /// Ids from the server's `GET /_uuids`, fetched in batches of the given size.
+    ///
+    /// These follow whichever algorithm the server is configured with.
+    pub fn server(batch_size: usize) -> Self {
+        IdGenerator {
+            kind: Kind::Server {
+                batch_size: batch_size.max(1),
+                ids: Arc::new(Mutex::new(VecDeque::new())),
+            },
+        }
+    }
+
+    /// Ids from the server's `GET /_uuids`, fetched 100 at a time
+    pub fn server_batches() -> Self {
+        IdGenerator::server(DEFAULT_BATCH_SIZE)
+    }
+
+    /// Generate the next id, fetching a batch from the server if need be.
+    pub(crate) async fn next_id(&self, client: &Client) -> Result<DocId, Error> {
+        let id = match &self.kind {
+            Kind::Random => uuid::Uuid::new_v4().simple().to_string(),
+            Kind::Sequential(sequence) => sequence.lock().expect("poisoned").next(),
+            Kind::UtcRandom => {
+                let micros = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|elapsed| elapsed.as_micros())
+                    .unwrap_or_default();
+                format!("{:014x}{}", micros, random_hex(18))
+            }
+            Kind::Server { batch_size, ids } => loop {
+                if let Some(id) = ids.lock().expect("poisoned").pop_front() {
+                    break id;
+                }
+                let batch = client.root().uuids(*batch_size).await?;
+                if batch.is_empty() {
+                    return Err(Error::InvalidDocId(
+                        "the server returned no uuids".to_string(),
+                    ));
+                }
+                ids.lock().expect("poisoned").extend(batch);
+            },
+        };
+        DocId::new(id)
+    }
+}
+
+/// 64 random bits
+fn random() -> u64 {
+    uuid::Uuid::new_v4().as_u64_pair().0
+}
+
+/// A string of random hex characters
+fn random_hex(len: usize) -> String {
+    let mut hex = String::with_capacity(len + 32);
+    while hex.len() < len {
+        hex.push_str(&uuid::Uuid::new_v4().simple().to_string());
+    }
+    hex.truncate(len);
+    hex
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::{BoxFuture, Request, Response, Transport};
+
+    /// A test double for ids which shouldn't need the server
+    struct Unreachable;
+
+    impl Transport for Unreachable {
+        fn execute(&self, _request: Request) -> BoxFuture<'_, Result<Response, Error>> {
+            panic!("no request should be sent")
+        }
+    }
+
+    #[test]
+    fn sequential_ids_increase() {
+        let mut sequence = Sequence::new();
+        let mut previous = sequence.next();
+        for _ in 0..1000 {
+            let id = sequence.next();
+            assert_eq!(id.len(), 32);
+            if id[..26] == previous[..26] {
+                assert!(id > previous);
+            }
+            previous = id;
+        }
+
+        sequence.suffix = SEQUENCE_LIMIT - 1;
+        let prefix = sequence.prefix.clone();
+        sequence.next();
+        assert_ne!(sequence.prefix, prefix);
+    }
+
+    #[tokio::test]
+    async fn client_side_ids() {
+        let url = crate::Url::parse("http://couch/").unwrap();
+        let client = Client::with_transport(url, Unreachable);
+
+        let random = IdGenerator::random().next_id(&client).await.unwrap();
+        assert_eq!(random.len(), 32);
+
+        let utc = IdGenerator::utc_random();
+        let first = utc.next_id(&client).await.unwrap();
+        let second = utc.next_id(&client).await.unwrap();
+        assert_eq!(first.len(), 32);
+        assert!(first[..14] <= second[..14]);
+    }
+
+    #[tokio::test]
+    async fn generated_ids() {
+        use crate::testing::{fake_database, Item};
+        use serde_json::json;
+
+        let (client, _) = fake_database("items").await;
+        let client = client.with_id_generator(crate::IdGenerator::server(2));
+        let database = client.database("items").unwrap();
+
+        let mut ids = Vec::new();
+        for n in 0..3 {
+            let response = database
+                .insert(&json!({ "n": n }), None)
+                .send()
+                .await
+                .unwrap();
+            ids.push(response.id);
+        }
+        ids.dedup();
+        assert_eq!(ids.len(), 3);
+
+        // retrying an insert with the same id conflicts, rather than duplicating it
+        let id = database.generate_id().await.unwrap();
+        let doc = json!({ "n": 3 });
+        database.insert(&doc, id.to_string()).send().await.unwrap();
+        let retry = database.insert(&doc, id.to_string()).send().await;
+        assert!(retry.err().unwrap().is_conflict());
+
+        let client = client.with_id_generator(crate::IdGenerator::sequential());
+        let database = client.database("items").unwrap();
+        let first = database.insert(&doc, None).send().await.unwrap();
+        let second = database.insert(&doc, None).send().await.unwrap();
+        assert_ne!(first.id, second.id);
+        assert_eq!(first.id.len(), 32);
+        assert_eq!(database.info().await.unwrap().doc_count, 6);
+
+        // saving a document without an id uses the generator too
+        let sequence = Sequence {
+            prefix: "a".repeat(26),
+            suffix: 0,
+        };
+        let ids = IdGenerator {
+            kind: Kind::Sequential(Arc::new(Mutex::new(sequence))),
+        };
+        let database = client.with_id_generator(ids).database("items").unwrap();
+        let mut item = Item::new(0);
+        database.save(&mut item).await.unwrap();
+        assert!(item.id.unwrap().starts_with(&"a".repeat(26)));
+    }
+}
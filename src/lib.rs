@@ -14,15 +14,58 @@
 #![allow(unknown_lints)]
 #![warn(clippy::all)]
 #![warn(missing_docs)]
-#![feature(async_await)]
 
+// lets the code generated by `#[derive(Document)]` refer to this crate by name, in its own tests
+extern crate self as chesterfield;
+
+#[cfg(feature = "blocking")]
+pub mod blocking;
 mod client;
+mod config;
 mod database;
+mod document;
 mod error;
+mod ids;
+mod names;
+mod path;
+mod revision;
+mod server;
+mod stats;
+#[cfg(any(test, feature = "fake-server", feature = "fixtures"))]
+pub mod testing;
+pub mod transport;
+mod users;
 
 pub use crate::client::Client;
-pub use crate::database::{Database, GetRequest, InsertRequest, UpdateRequest};
+pub use crate::config::{ConfigSection, NodeConfig};
+pub use crate::database::{
+    CompactRequest, ConflictResolver, CopyRequest, CopyResponse, CreateDatabaseRequest, Database,
+    DatabaseCluster, DatabaseInfo, DatabaseProps, DatabaseSizes, DeleteRequest, DeleteResponse,
+    GetRequest, GetResponse, GetResponseMeta, HeadResponse, HighestGeneration, InsertRequest,
+    InsertResponse, LastWriteWins, Leaf, ModifyRequest, Repository, Resolution, SecurityMembers,
+    SecurityObject, UpdateRequest, UpdateResponse,
+};
+
+#[doc(hidden)]
+pub use crate::document::__private;
+pub use crate::document::Document;
+pub use crate::error::{ChesterfieldError as Error, ErrorResponse};
+pub use crate::ids::IdGenerator;
+pub use crate::names::{DatabaseName, DocId};
+pub use crate::revision::{RevInfo, RevStatus, Revision, RevisionHistory};
+pub use crate::server::{
+    ActiveTask, AllDbsRequest, DbsInfo, ServerInfo, UpStatus, Vendor, Version,
+};
+pub use crate::stats::{
+    Histogram, MemoryStats, MessageQueue, Metric, MetricFamily, MetricType, MetricValue, Node,
+    NodeStats, Sample, SystemStats,
+};
+pub use crate::users::{User, Users};
+pub use url::ParseError as UrlError;
+pub use url::Url;
 
-pub use crate::error::ChesterfieldError as Error;
-pub use reqwest::Url;
-pub use reqwest::UrlError;
+/// Derive [Document] for a struct.
+///
+/// Requires the `derive` feature.
+#[cfg(feature = "derive")]
+pub use chesterfield_derive::Document;
@@ -0,0 +1,308 @@
+//! Validated database names and document ids.
+
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::Deref;
+use std::str::FromStr;
+
+/// Databases which CouchDB creates for itself, and which don't follow the usual naming rules
+const SYSTEM_DATABASES: &[&str] = &["_users", "_replicator", "_global_changes"];
+
+/// The longest database name CouchDB will accept
+const MAX_DATABASE_NAME_LENGTH: usize = 238;
+
+/// The id prefixes which are allowed to start with an underscore
+const RESERVED_PREFIXES: &[&str] = &["_design/", "_local/"];
+
+/// The name of a CouchDB database.
+///
+/// Database names must match `^[a-z][a-z0-9_$()+/-]*$` (or be one of the system databases,
+/// such as `_users`), and may be at most 238 characters long. The name is validated
+/// when it's constructed.
+///
+/// # Example
+/// ```
+/// use chesterfield::DatabaseName;
+///
+/// let name: DatabaseName = "items".parse().unwrap();
+///
+/// assert!(DatabaseName::new("Items").is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct DatabaseName(String);
+
+impl DatabaseName {
+    /// Create a new database name.
+    ///
+    /// # Errors
+    /// This method fails if the name is not a valid CouchDB database name
+    pub fn new(name: impl Into<String>) -> Result<Self, Error> {
+        let name = name.into();
+        match database_name_error(&name) {
+            None => Ok(DatabaseName(name)),
+            Some(reason) => Err(Error::InvalidDatabaseName(format!("'{}' {}", name, reason))),
+        }
+    }
+
+    /// The name as a string slice
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+fn database_name_error(name: &str) -> Option<&'static str> {
+    if SYSTEM_DATABASES.contains(&name) {
+        return None;
+    }
+    if name.len() > MAX_DATABASE_NAME_LENGTH {
+        return Some("is too long (database names can be at most 238 characters)");
+    }
+    let mut chars = name.chars();
+    match chars.next() {
+        None => return Some("is empty"),
+        Some(c) if !c.is_ascii_lowercase() => {
+            return Some("must begin with a lowercase letter (a-z)")
+        }
+        Some(_) => (),
+    }
+    if !chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "_$()+-/".contains(c)) {
+        return Some("may only contain lowercase letters (a-z), digits (0-9), and any of _$()+-/");
+    }
+    None
+}
+
+/// The id of a CouchDB document.
+///
+/// Document ids must not be empty, and may only begin with an underscore if they're
+/// design documents (`_design/...`) or local documents (`_local/...`). The id is validated
+/// when it's constructed.
+///
+/// # Example
+/// ```
+/// use chesterfield::DocId;
+///
+/// let id: DocId = "some-unique-id".parse().unwrap();
+/// let design: DocId = "_design/views".parse().unwrap();
+///
+/// assert!(DocId::new("_reserved").is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct DocId(String);
+
+impl DocId {
+    /// Create a new document id.
+    ///
+    /// # Errors
+    /// This method fails if the id is not a valid CouchDB document id
+    pub fn new(id: impl Into<String>) -> Result<Self, Error> {
+        let id = id.into();
+        match doc_id_error(&id) {
+            None => Ok(DocId(id)),
+            Some(reason) => Err(Error::InvalidDocId(format!("'{}' {}", id, reason))),
+        }
+    }
+
+    /// The id as a string slice
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Whether this is the id of a design document
+    pub fn is_design(&self) -> bool {
+        self.0.starts_with("_design/")
+    }
+
+    /// Whether this is the id of a local (non-replicating) document
+    pub fn is_local(&self) -> bool {
+        self.0.starts_with("_local/")
+    }
+}
+
+fn doc_id_error(id: &str) -> Option<&'static str> {
+    if id.is_empty() {
+        return Some("is empty");
+    }
+    if id.starts_with('_') {
+        let reserved = RESERVED_PREFIXES
+            .iter()
+            .find(|prefix| id.starts_with(*prefix));
+        return match reserved {
+            Some(prefix) if id.len() == prefix.len() => Some("is missing a name after the prefix"),
+            Some(_) => None,
+            None => Some("may only begin with an underscore if it starts with _design/ or _local/"),
+        };
+    }
+    None
+}
+
+macro_rules! impl_conversions {
+    ($name:ident) => {
+        impl FromStr for $name {
+            type Err = Error;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                $name::new(s)
+            }
+        }
+
+        impl TryFrom<&str> for $name {
+            type Error = Error;
+
+            fn try_from(s: &str) -> Result<Self, Self::Error> {
+                $name::new(s)
+            }
+        }
+
+        impl TryFrom<String> for $name {
+            type Error = Error;
+
+            fn try_from(s: String) -> Result<Self, Self::Error> {
+                $name::new(s)
+            }
+        }
+
+        impl TryFrom<&String> for $name {
+            type Error = Error;
+
+            fn try_from(s: &String) -> Result<Self, Self::Error> {
+                $name::new(s.as_str())
+            }
+        }
+
+        impl From<&$name> for $name {
+            fn from(name: &$name) -> Self {
+                name.clone()
+            }
+        }
+
+        impl From<$name> for String {
+            fn from(name: $name) -> Self {
+                name.0
+            }
+        }
+
+        impl AsRef<str> for $name {
+            fn as_ref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl Deref for $name {
+            type Target = str;
+
+            fn deref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
+
+        impl PartialEq<str> for $name {
+            fn eq(&self, other: &str) -> bool {
+                self.0 == other
+            }
+        }
+
+        impl PartialEq<&str> for $name {
+            fn eq(&self, other: &&str) -> bool {
+                self.0 == *other
+            }
+        }
+    };
+}
+
+impl_conversions!(DatabaseName);
+impl_conversions!(DocId);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn valid_database_names() {
+        for name in &["a", "items", "a1", "a_$()+-/b", "_users", "_replicator"] {
+            assert!(DatabaseName::new(*name).is_ok(), "{}", name);
+        }
+        assert!(DatabaseName::new("a".repeat(238)).is_ok());
+    }
+
+    #[test]
+    fn invalid_database_names() {
+        let cases = [
+            ("", "'' is empty"),
+            ("Items", "'Items' must begin with a lowercase letter (a-z)"),
+            ("1items", "'1items' must begin with a lowercase letter (a-z)"),
+            ("_private", "'_private' must begin with a lowercase letter (a-z)"),
+            (
+                "items!",
+                "'items!' may only contain lowercase letters (a-z), digits (0-9), and any of _$()+-/",
+            ),
+            (
+                "itEms",
+                "'itEms' may only contain lowercase letters (a-z), digits (0-9), and any of _$()+-/",
+            ),
+        ];
+        for (name, message) in &cases {
+            let error = DatabaseName::new(*name).unwrap_err();
+            assert_eq!(
+                error.to_string(),
+                format!("invalid database name: {}", message)
+            );
+        }
+        assert!(DatabaseName::new("a".repeat(239)).is_err());
+    }
+
+    #[test]
+    fn valid_doc_ids() {
+        for id in &[
+            "a",
+            "A b/c?d",
+            "_design/views",
+            "_local/checkpoint",
+            "..",
+            "😀",
+        ] {
+            assert!(DocId::new(*id).is_ok(), "{}", id);
+        }
+    }
+
+    #[test]
+    fn invalid_doc_ids() {
+        let cases = [
+            ("", "'' is empty"),
+            (
+                "_reserved",
+                "'_reserved' may only begin with an underscore if it starts with _design/ or _local/",
+            ),
+            (
+                "_designer/x",
+                "'_designer/x' may only begin with an underscore if it starts with _design/ or _local/",
+            ),
+            ("_design/", "'_design/' is missing a name after the prefix"),
+            ("_local/", "'_local/' is missing a name after the prefix"),
+        ];
+        for (id, message) in &cases {
+            let error = DocId::new(*id).unwrap_err();
+            assert_eq!(
+                error.to_string(),
+                format!("invalid document id: {}", message)
+            );
+        }
+    }
+
+    #[test]
+    fn serde() {
+        let id: DocId = serde_json::from_str(r#""_design/views""#).unwrap();
+        assert!(id.is_design());
+        assert_eq!(serde_json::to_string(&id).unwrap(), r#""_design/views""#);
+        assert!(serde_json::from_str::<DocId>(r#""_nope""#).is_err());
+    }
+}
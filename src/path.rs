@@ -0,0 +1,263 @@
+//! Building request URLs.
+//!
+//! Database names, document ids and attachment names come from users, and can contain
+//! pretty much anything. Each one is percent-encoded as a *single* path segment, so that
+//! an id containing `/`, `?`, `#` or `%` addresses the document it names, rather than
+//! some other endpoint entirely.
+//!
+//! The exceptions are design and local documents, where the slash after the
+//! `_design`/`_local` prefix is part of the path CouchDB expects.
+
+use crate::{Error, Url};
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+
+/// Characters which must be encoded within a single path segment.
+///
+/// This is the WHATWG path segment set, plus `/` and `%` (so segments can't be split or
+/// decoded into something else), and a few characters which proxies and CouchDB itself
+/// are known to treat specially.
+const SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'&')
+    .add(b'+')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'[')
+    .add(b'\\')
+    .add(b']')
+    .add(b'^')
+    .add(b'`')
+    .add(b'{')
+    .add(b'|')
+    .add(b'}');
+
+const DESIGN_PREFIX: &str = "_design/";
+const LOCAL_PREFIX: &str = "_local/";
+
+/// The path segments of a document, relative to its database.
+///
+/// This keeps the slash in `_design/` and `_local/` ids.
+pub(crate) fn document(id: &str) -> Vec<&str> {
+    if let Some(name) = id.strip_prefix(DESIGN_PREFIX) {
+        vec!["_design", name]
+    } else if let Some(name) = id.strip_prefix(LOCAL_PREFIX) {
+        vec!["_local", name]
+    } else {
+        vec![id]
+    }
+}
+
+/// The path segments of a design document, relative to its database.
+///
+/// The name may be given with or without the `_design/` prefix.
+pub(crate) fn design_document(name: &str) -> Vec<&str> {
+    vec!["_design", name.strip_prefix(DESIGN_PREFIX).unwrap_or(name)]
+}
+
+/// The path segments of a document attachment, relative to its database.
+pub(crate) fn attachment<'a>(id: &'a str, name: &'a str) -> Vec<&'a str> {
+    let mut segments = document(id);
+    segments.push(name);
+    segments
+}
+
+/// The value of a `Destination` header (for `COPY`), naming a document and, optionally,
+/// the revision of it to overwrite.
+///
+/// CouchDB takes the id from the header as-is- it isn't percent-decoded- up to the first
+/// `?`, which starts the revision.
+///
+/// # Errors
+/// An id containing `?` can't be told apart from a revision, so it's rejected.
+pub(crate) fn destination(id: &str, rev: Option<&str>) -> Result<String, Error> {
+    if id.contains('?') {
+        return Err(Error::InvalidPath(format!(
+            "'{}' cannot be used as a COPY destination",
+            id
+        )));
+    }
+    let mut destination = id.to_string();
+    if let Some(rev) = rev {
+        destination.push_str("?rev=");
+        destination.push_str(rev);
+    }
+    Ok(destination)
+}
+
+/// Append percent-encoded segments to the path of a URL.
+///
+/// # Errors
+/// Empty segments, and the segments `.` and `..`, can't be represented in a URL
+/// (they'd be normalised away), so they're rejected.
+pub(crate) fn join<I, S>(url: &Url, segments: I) -> Result<Url, Error>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut path = url.path().trim_end_matches('/').to_string();
+
+    for segment in segments {
+        let segment = segment.as_ref();
+        if matches!(segment, "" | "." | "..") {
+            return Err(Error::InvalidPath(format!(
+                "'{}' cannot be used as a path segment",
+                segment
+            )));
+        }
+        path.push('/');
+        path.extend(utf8_percent_encode(segment, SEGMENT));
+    }
+
+    let mut url = url.clone();
+    url.set_path(&path);
+    Ok(url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base() -> Url {
+        Url::parse("http://localhost:5984/").unwrap()
+    }
+
+    fn document_url(id: &str) -> String {
+        let database = join(&base(), ["items"]).unwrap();
+        join(&database, document(id)).unwrap().to_string()
+    }
+
+    #[test]
+    fn plain_ids() {
+        assert_eq!(document_url("abc"), "http://localhost:5984/items/abc");
+        assert_eq!(
+            document_url("a-b_c.d~e"),
+            "http://localhost:5984/items/a-b_c.d~e"
+        );
+    }
+
+    #[test]
+    fn awkward_ids() {
+        let cases = [
+            ("a/b", "a%2Fb"),
+            ("/leading", "%2Fleading"),
+            ("trailing/", "trailing%2F"),
+            ("what?", "what%3F"),
+            ("a?rev=1-abc", "a%3Frev=1-abc"),
+            ("#hash", "%23hash"),
+            ("100%", "100%25"),
+            ("%2F", "%252F"),
+            ("a b", "a%20b"),
+            ("a+b", "a%2Bb"),
+            ("a&b", "a%26b"),
+            ("..foo", "..foo"),
+            ("../_all_dbs", "..%2F_all_dbs"),
+            ("a\\b", "a%5Cb"),
+            ("{json}", "%7Bjson%7D"),
+            ("[0]", "%5B0%5D"),
+            ("naïve", "na%C3%AFve"),
+            ("😀", "%F0%9F%98%80"),
+            ("tab\there", "tab%09here"),
+        ];
+
+        for (id, encoded) in &cases {
+            assert_eq!(
+                document_url(id),
+                format!("http://localhost:5984/items/{}", encoded),
+                "id: {:?}",
+                id
+            );
+        }
+    }
+
+    #[test]
+    fn awkward_attachment_names() {
+        let database = join(&base(), ["items"]).unwrap();
+        let cases = [
+            ("a", "logo.png", "a/logo.png"),
+            ("_design/foo", "img/logo.png", "_design/foo/img%2Flogo.png"),
+            ("a/b", "../c", "a%2Fb/..%2Fc"),
+            ("a", "what?.txt", "a/what%3F.txt"),
+            ("a", "#1 100%.txt", "a/%231%20100%25.txt"),
+            ("a", "naïve.txt", "a/na%C3%AFve.txt"),
+        ];
+
+        for (id, name, encoded) in &cases {
+            let url = join(&database, attachment(id, name)).unwrap();
+            assert_eq!(
+                url.as_str(),
+                format!("http://localhost:5984/items/{}", encoded),
+                "attachment: {:?}",
+                name
+            );
+        }
+        for name in &["", ".", ".."] {
+            assert!(join(&database, attachment("a", name)).is_err());
+        }
+    }
+
+    #[test]
+    fn reserved_prefixes() {
+        assert_eq!(
+            document_url("_design/foo"),
+            "http://localhost:5984/items/_design/foo"
+        );
+        assert_eq!(
+            document_url("_design/foo/bar"),
+            "http://localhost:5984/items/_design/foo%2Fbar"
+        );
+        assert_eq!(
+            document_url("_local/foo"),
+            "http://localhost:5984/items/_local/foo"
+        );
+        assert_eq!(
+            document_url("_design"),
+            "http://localhost:5984/items/_design"
+        );
+        assert_eq!(
+            document_url("_designer/foo"),
+            "http://localhost:5984/items/_designer%2Ffoo"
+        );
+
+        assert_eq!(design_document("foo"), vec!["_design", "foo"]);
+        assert_eq!(design_document("_design/foo"), vec!["_design", "foo"]);
+    }
+
+    #[test]
+    fn destinations() {
+        assert_eq!(destination("abc", None).unwrap(), "abc");
+        assert_eq!(destination("a/b c", None).unwrap(), "a/b c");
+        assert_eq!(destination("100%", None).unwrap(), "100%");
+        assert_eq!(destination("_design/foo", None).unwrap(), "_design/foo");
+        assert_eq!(destination("a/b", Some("2-abc")).unwrap(), "a/b?rev=2-abc");
+        assert!(destination("a?b", None).is_err());
+    }
+
+    #[test]
+    fn database_names() {
+        let url = join(&base(), ["a/b$c(d)+e-f_g"]).unwrap();
+        assert_eq!(url.as_str(), "http://localhost:5984/a%2Fb$c(d)%2Be-f_g");
+    }
+
+    #[test]
+    fn preserves_base_path() {
+        let base = Url::parse("http://localhost/couchdb/").unwrap();
+        let url = join(&base, ["items", "doc"]).unwrap();
+        assert_eq!(url.as_str(), "http://localhost/couchdb/items/doc");
+
+        let base = Url::parse("http://localhost/couchdb?x=1").unwrap();
+        let url = join(&base, ["items"]).unwrap();
+        assert_eq!(url.as_str(), "http://localhost/couchdb/items?x=1");
+    }
+
+    #[test]
+    fn unrepresentable_segments() {
+        for segment in &["", ".", ".."] {
+            assert!(join(&base(), [segment]).is_err(), "{:?}", segment);
+        }
+    }
+}
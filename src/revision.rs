@@ -0,0 +1,395 @@
+//! Document revisions.
+
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+/// A document revision, such as `3-917fa2381192822767f010b95b45325b`.
+///
+/// A revision is made up of a *generation* (the number of times the document has been
+/// edited) and a *digest* which identifies that particular edit. Revisions order by
+/// generation first, and then by digest- the same rule CouchDB uses to pick a winner
+/// between conflicting revisions.
+///
+/// # Example
+/// ```
+/// use chesterfield::Revision;
+///
+/// let rev: Revision = "2-7051cbe5c8faecd085a3fa619e6e6337".parse().unwrap();
+///
+/// assert_eq!(rev.generation(), 2);
+/// assert_eq!(rev.digest(), "7051cbe5c8faecd085a3fa619e6e6337");
+/// assert_eq!(rev.to_string(), "2-7051cbe5c8faecd085a3fa619e6e6337");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Revision {
+    generation: u64,
+    digest: String,
+}
+
+impl Revision {
+    /// Create a revision from its generation and digest.
+    ///
+    /// # Errors
+    /// This method fails if the digest is empty, or contains a `-`
+    pub fn new(generation: u64, digest: impl Into<String>) -> Result<Self, Error> {
+        let digest = digest.into();
+        if digest.is_empty() || digest.contains('-') {
+            return Err(Error::InvalidRevision(format!(
+                "'{}-{}' has an invalid digest",
+                generation, digest
+            )));
+        }
+        Ok(Revision { generation, digest })
+    }
+
+    /// The generation of the revision.
+    ///
+    /// This is the number of times the document has been edited, including its creation.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// The digest which identifies this particular edit
+    pub fn digest(&self) -> &str {
+        &self.digest
+    }
+
+    /// Build the ancestry of a revision from the `start` and `ids` of a `_revisions`
+    /// object.
+    ///
+    /// The returned list begins with the newest revision, and works backwards.
+    ///
+    /// # Errors
+    /// This method fails if any of the ids is not a valid digest, or if there are more
+    /// ids than generations
+    ///
+    /// # Example
+    /// ```
+    /// use chesterfield::Revision;
+    ///
+    /// let ancestry = Revision::ancestry(3, ["c", "b", "a"]).unwrap();
+    ///
+    /// assert_eq!(ancestry[0].to_string(), "3-c");
+    /// assert_eq!(ancestry[2].to_string(), "1-a");
+    /// ```
+    pub fn ancestry<I, S>(start: u64, ids: I) -> Result<Vec<Revision>, Error>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        ids.into_iter()
+            .enumerate()
+            .map(|(offset, id)| {
+                // generations start at 1, so there can be at most `start` of them
+                let generation = start
+                    .checked_sub(offset as u64)
+                    .filter(|generation| *generation > 0)
+                    .ok_or_else(|| {
+                        Error::InvalidRevision(format!(
+                            "revision history starting at generation {} is too long",
+                            start
+                        ))
+                    })?;
+                Revision::new(generation, id.as_ref())
+            })
+            .collect()
+    }
+}
+
+impl PartialOrd for Revision {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Revision {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.generation
+            .cmp(&other.generation)
+            .then_with(|| self.digest.cmp(&other.digest))
+    }
+}
+
+impl FromStr for Revision {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (generation, digest) = s
+            .split_once('-')
+            .ok_or_else(|| Error::InvalidRevision(format!("'{}' is not of the form N-hash", s)))?;
+        let generation = generation.parse().map_err(|_| {
+            Error::InvalidRevision(format!("'{}' does not begin with a generation number", s))
+        })?;
+        Revision::new(generation, digest)
+    }
+}
+
+impl TryFrom<&str> for Revision {
+    type Error = Error;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl TryFrom<String> for Revision {
+    type Error = Error;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl TryFrom<&String> for Revision {
+    type Error = Error;
+
+    fn try_from(s: &String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<&Revision> for Revision {
+    fn from(rev: &Revision) -> Self {
+        rev.clone()
+    }
+}
+
+impl From<Revision> for String {
+    fn from(rev: Revision) -> Self {
+        rev.to_string()
+    }
+}
+
+impl fmt::Display for Revision {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-{}", self.generation, self.digest)
+    }
+}
+
+/// The `_revisions` object returned when requesting a document's revision history.
+///
+/// The `ids` are the digests of the document's revisions, newest first. The first of
+/// them has generation `start`, and each subsequent digest is one generation older.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RevisionHistory {
+    /// The generation of the newest revision
+    pub start: u64,
+
+    /// The digests of each revision, newest first
+    pub ids: Vec<String>,
+}
+
+impl RevisionHistory {
+    /// The full ancestry of the document, newest first.
+    ///
+    /// # Errors
+    /// This method fails if the history is malformed.
+    /// See [Revision::ancestry].
+    pub fn ancestry(&self) -> Result<Vec<Revision>, Error> {
+        Revision::ancestry(self.start, &self.ids)
+    }
+
+    /// The newest revision in the history
+    pub fn head(&self) -> Option<Revision> {
+        let digest = self.ids.first()?;
+        Revision::new(self.start, digest.as_str()).ok()
+    }
+
+    /// Whether the given revision appears in this history
+    pub fn contains(&self, rev: &Revision) -> bool {
+        self.digest_at(rev.generation()) == Some(rev.digest())
+    }
+
+    /// The revision which the given revision was edited from.
+    ///
+    /// Returns `None` if the revision isn't in this history, or if its parent is
+    /// older than the history goes back (CouchDB only keeps a limited number of
+    /// revisions).
+    pub fn parent(&self, rev: &Revision) -> Option<Revision> {
+        if !self.contains(rev) {
+            return None;
+        }
+        let generation = rev.generation().checked_sub(1)?;
+        let digest = self.digest_at(generation)?;
+        Revision::new(generation, digest).ok()
+    }
+
+    /// The newest revision which appears in both this history and `other`.
+    ///
+    /// For two conflicting branches of a document, this is the revision at which they
+    /// diverged.
+    pub fn common_ancestor(&self, other: &RevisionHistory) -> Option<Revision> {
+        self.ids
+            .iter()
+            .zip((0..=self.start).rev())
+            .filter(|(_, generation)| *generation <= other.start)
+            .find(|(digest, generation)| other.digest_at(*generation) == Some(digest.as_str()))
+            .and_then(|(digest, generation)| Revision::new(generation, digest.as_str()).ok())
+    }
+
+    fn digest_at(&self, generation: u64) -> Option<&str> {
+        let offset = self.start.checked_sub(generation)?;
+        let offset = usize::try_from(offset).ok()?;
+        self.ids.get(offset).map(String::as_str)
+    }
+}
+
+/// Whether the body of a revision is still held by the database
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RevStatus {
+    /// The revision's body can be retrieved
+    Available,
+
+    /// The revision's body has been removed (by compaction, for example)
+    Missing,
+
+    /// The revision is a deletion 'tombstone'
+    Deleted,
+}
+
+/// An entry in the `_revs_info` list returned when requesting a document with
+/// `revs_info=true`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RevInfo {
+    /// The revision
+    pub rev: Revision,
+
+    /// Whether the body of the revision is still available
+    pub status: RevStatus,
+}
+
+impl RevInfo {
+    /// Whether the body of this revision can still be retrieved
+    pub fn is_available(&self) -> bool {
+        self.status == RevStatus::Available
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let rev: Revision = "10-abc".parse().unwrap();
+        assert_eq!(rev.generation(), 10);
+        assert_eq!(rev.digest(), "abc");
+
+        let local: Revision = "0-1".parse().unwrap();
+        assert_eq!(local.generation(), 0);
+    }
+
+    #[test]
+    fn invalid() {
+        let cases = [
+            ("", "'' is not of the form N-hash"),
+            ("abc", "'abc' is not of the form N-hash"),
+            ("x-abc", "'x-abc' does not begin with a generation number"),
+            ("-abc", "'-abc' does not begin with a generation number"),
+            ("1-", "'1-' has an invalid digest"),
+            ("1-a-b", "'1-a-b' has an invalid digest"),
+        ];
+        for (rev, message) in &cases {
+            let error = rev.parse::<Revision>().unwrap_err();
+            assert_eq!(error.to_string(), format!("invalid revision: {}", message));
+        }
+    }
+
+    #[test]
+    fn ordering() {
+        let mut revs: Vec<Revision> = ["10-a", "2-b", "2-a", "9-z"]
+            .iter()
+            .map(|rev| rev.parse().unwrap())
+            .collect();
+        revs.sort();
+        let revs: Vec<String> = revs.into_iter().map(String::from).collect();
+        assert_eq!(revs, vec!["2-a", "2-b", "9-z", "10-a"]);
+    }
+
+    #[test]
+    fn ancestry() {
+        let revisions: RevisionHistory =
+            serde_json::from_str(r#"{"start": 3, "ids": ["c", "b", "a"]}"#).unwrap();
+        let ancestry: Vec<String> = revisions
+            .ancestry()
+            .unwrap()
+            .into_iter()
+            .map(String::from)
+            .collect();
+        assert_eq!(ancestry, vec!["3-c", "2-b", "1-a"]);
+
+        assert!(Revision::ancestry(1, ["a"]).is_ok());
+        assert!(Revision::ancestry(1, ["b", "a"]).is_err());
+        assert!(Revision::ancestry(1, ["c", "b", "a"]).is_err());
+        assert!(Revision::ancestry(0, ["a"]).is_err());
+    }
+
+    #[test]
+    fn serde() {
+        let rev: Revision = serde_json::from_str(r#""1-abc""#).unwrap();
+        assert_eq!(serde_json::to_string(&rev).unwrap(), r#""1-abc""#);
+        assert!(serde_json::from_str::<Revision>(r#""abc""#).is_err());
+    }
+
+    fn history(start: u64, ids: &[&str]) -> RevisionHistory {
+        RevisionHistory {
+            start,
+            ids: ids.iter().map(|id| id.to_string()).collect(),
+        }
+    }
+
+    fn rev(rev: &str) -> Revision {
+        rev.parse().unwrap()
+    }
+
+    #[test]
+    fn walk_history() {
+        let history = history(3, &["c", "b", "a"]);
+
+        assert_eq!(history.head(), Some(rev("3-c")));
+        assert!(history.contains(&rev("2-b")));
+        assert!(!history.contains(&rev("2-c")));
+        assert!(!history.contains(&rev("4-d")));
+
+        assert_eq!(history.parent(&rev("3-c")), Some(rev("2-b")));
+        assert_eq!(history.parent(&rev("1-a")), None);
+        assert_eq!(history.parent(&rev("2-x")), None);
+
+        // a truncated history doesn't know the parent of its oldest revision
+        let truncated = self::history(5, &["e", "d"]);
+        assert_eq!(truncated.parent(&rev("4-d")), None);
+    }
+
+    #[test]
+    fn common_ancestor() {
+        let ours = history(4, &["d", "c", "b", "a"]);
+        let theirs = history(3, &["z", "b", "a"]);
+        assert_eq!(ours.common_ancestor(&theirs), Some(rev("2-b")));
+        assert_eq!(theirs.common_ancestor(&ours), Some(rev("2-b")));
+
+        let unrelated = history(1, &["q"]);
+        assert_eq!(ours.common_ancestor(&unrelated), None);
+    }
+
+    #[test]
+    fn revs_info() {
+        let info: Vec<RevInfo> = serde_json::from_str(
+            r#"[
+                {"rev": "3-c", "status": "available"},
+                {"rev": "2-b", "status": "missing"},
+                {"rev": "1-a", "status": "deleted"}
+            ]"#,
+        )
+        .unwrap();
+        assert!(info[0].is_available());
+        assert_eq!(info[1].status, RevStatus::Missing);
+        assert_eq!(info[2].status, RevStatus::Deleted);
+        assert_eq!(info[2].rev, rev("1-a"));
+    }
+}
@@ -0,0 +1,613 @@
+//! Server-level endpoints, which aren't specific to any one database.
+
+use std::fmt;
+
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::client::Client;
+use crate::{DatabaseInfo, Error};
+
+/// The server's welcome message, from `GET /`
+#[derive(Debug, Clone, Deserialize)]
+pub struct ServerInfo {
+    /// Always "Welcome"
+    pub couchdb: String,
+
+    /// The version of CouchDB, such as `3.3.3`
+    pub version: String,
+
+    /// The commit CouchDB was built from
+    pub git_sha: Option<String>,
+
+    /// The unique id of the server (CouchDB 2 and later)
+    pub uuid: Option<String>,
+
+    /// Who packaged the server
+    pub vendor: Vendor,
+
+    /// The optional features which are enabled, such as `partitioned` (CouchDB 2.2 and
+    /// later)
+    #[serde(default)]
+    pub features: Vec<String>,
+}
+
+impl ServerInfo {
+    /// The version of CouchDB, if it can be parsed
+    pub fn version_number(&self) -> Option<Version> {
+        Version::parse(&self.version)
+    }
+
+    /// Whether the server is at least the given version of CouchDB.
+    ///
+    /// A version which can't be parsed is assumed to be recent enough.
+    pub fn is_at_least(&self, version: Version) -> bool {
+        self.version_number().is_none_or(|actual| actual >= version)
+    }
+
+    /// Whether the given optional feature is enabled, such as `partitioned`
+    pub fn has_feature(&self, feature: &str) -> bool {
+        self.features.iter().any(|enabled| enabled == feature)
+    }
+}
+
+/// A CouchDB version number, such as `3.3.3`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version {
+    /// The major version
+    pub major: u32,
+
+    /// The minor version
+    pub minor: u32,
+
+    /// The patch version
+    pub patch: u32,
+}
+
+impl Version {
+    /// Create a version number
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Version {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// Parse a version string, such as `3.3.3` or `2.3.1-a1b2c3d`.
+    ///
+    /// Missing minor and patch numbers are taken to be zero, and anything following the
+    /// digits of a number is ignored.
+    pub fn parse(version: &str) -> Option<Self> {
+        fn number(part: Option<&str>) -> Option<u32> {
+            let part = part?;
+            let end = part
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(part.len());
+            part[..end].parse().ok()
+        }
+
+        let mut parts = version.trim().splitn(3, '.');
+        let major = number(parts.next())?;
+        let minor = number(parts.next()).unwrap_or(0);
+        let patch = number(parts.next()).unwrap_or(0);
+        Some(Version::new(major, minor, patch))
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Who packaged the server
+#[derive(Debug, Clone, Deserialize)]
+pub struct Vendor {
+    /// The name of the vendor, such as "The Apache Software Foundation"
+    pub name: String,
+
+    /// The vendor's version, if different to CouchDB's
+    pub version: Option<String>,
+}
+
+/// A request to list the databases on the server.
+///
+/// The request is lazy- it doesn't do a thing until you call its '[send](AllDbsRequest::send)'
+/// method.
+///
+/// see [CouchDB API docs](https://docs.couchdb.org/en/stable/api/server/common.html#all-dbs)
+/// for details.
+pub struct AllDbsRequest {
+    client: Client,
+    query: AllDbsQuery,
+}
+
+#[derive(Serialize, Default)]
+struct AllDbsQuery {
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    descending: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    start_key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    end_key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    limit: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    skip: Option<usize>,
+}
+
+impl AllDbsRequest {
+    pub(crate) fn new(client: &Client) -> Self {
+        AllDbsRequest {
+            client: client.into(),
+            query: AllDbsQuery::default(),
+        }
+    }
+
+    /// List the databases in reverse order.
+    ///
+    /// Default is false.
+    pub fn descending(mut self, value: bool) -> Self {
+        self.query.descending = value;
+        self
+    }
+
+    /// Start listing at the given name (inclusive)
+    pub fn start_key(mut self, name: impl AsRef<str>) -> Self {
+        self.query.start_key = Some(json_string(name.as_ref()));
+        self
+    }
+
+    /// Stop listing at the given name (inclusive)
+    pub fn end_key(mut self, name: impl AsRef<str>) -> Self {
+        self.query.end_key = Some(json_string(name.as_ref()));
+        self
+    }
+
+    /// List at most this many databases
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.query.limit = Some(limit);
+        self
+    }
+
+    /// Skip this many databases before listing
+    pub fn skip(mut self, skip: usize) -> Self {
+        self.query.skip = Some(skip);
+        self
+    }
+
+    /// Send the request, returning the names of the databases.
+    pub async fn send(self) -> Result<Vec<String>, Error> {
+        self.client
+            .join(["_all_dbs"])?
+            .get()
+            .query(&self.query)
+            .send()
+            .await?
+            .json()
+    }
+}
+
+/// Keys are JSON encoded in the query string
+fn json_string(key: &str) -> String {
+    Value::from(key).to_string()
+}
+
+/// An entry in the response to [Client::dbs_info]
+#[derive(Debug, Clone, Deserialize)]
+pub struct DbsInfo {
+    /// The name of the database
+    pub key: String,
+
+    /// Information about the database, if it exists
+    pub info: Option<DatabaseInfo>,
+
+    /// The reason the information is missing, such as `not_found`
+    pub error: Option<String>,
+}
+
+/// The health of the server, from `GET /_up`
+#[derive(Debug, Clone, Deserialize)]
+pub struct UpStatus {
+    /// `ok`, or why the server isn't accepting requests (such as `maintenance_mode`)
+    pub status: String,
+
+    /// The state of the server's connections to the other nodes in the cluster
+    #[serde(default)]
+    pub seeds: Value,
+}
+
+impl UpStatus {
+    /// Whether the server is up, and not in maintenance mode
+    pub fn is_ok(&self) -> bool {
+        self.status == "ok"
+    }
+}
+
+/// A task which is running on the server, such as a replication or compaction.
+///
+/// The fields vary with the type of task; those which aren't common to every task are
+/// collected in [details](ActiveTask::details).
+#[derive(Debug, Clone, Deserialize)]
+pub struct ActiveTask {
+    /// The type of task, such as `replication`, `database_compaction` or `indexer`
+    #[serde(rename = "type")]
+    pub kind: String,
+
+    /// The node running the task
+    pub node: Option<String>,
+
+    /// The Erlang process id of the task
+    pub pid: String,
+
+    /// When the task started, in seconds since the epoch
+    pub started_on: u64,
+
+    /// When the task last reported its progress, in seconds since the epoch
+    pub updated_on: u64,
+
+    /// The database the task is working on, if any
+    pub database: Option<String>,
+
+    /// How far through the task is, as a percentage, if known
+    pub progress: Option<u32>,
+
+    /// Every other field of the task
+    #[serde(flatten)]
+    pub details: Map<String, Value>,
+}
+
+impl Client {
+    /// Retrieve the server's version, vendor and enabled features.
+    ///
+    /// This always asks the server; see [detect_server](Client::detect_server) for a
+    /// cached copy.
+    pub async fn server_info(&self) -> Result<ServerInfo, Error> {
+        self.root().get().send().await?.json()
+    }
+
+    /// The server's version, vendor and enabled features, retrieved the first time they're
+    /// needed and cached from then on.
+    ///
+    /// The cache is shared by this client and every [Database](crate::Database) created from
+    /// it. Requests which behave differently between versions of CouchDB use it to adapt
+    /// their parameters, or to fail with [Unsupported](Error::Unsupported) before anything
+    /// is sent.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use chesterfield::{Client, Version};
+    ///
+    /// # async fn run() {
+    /// let client = Client::from_url_str("http://localhost:5984").unwrap();
+    ///
+    /// let server = client.detect_server().await.unwrap();
+    /// if server.is_at_least(Version::new(3, 0, 0)) && server.has_feature("partitioned") {
+    ///     // ...
+    /// }
+    /// # }
+    /// ```
+    pub async fn detect_server(&self) -> Result<&ServerInfo, Error> {
+        if let Some(info) = self.detected().get() {
+            return Ok(info);
+        }
+
+        // failures aren't cached, so the next request tries again
+        let info = self.server_info().await?;
+        Ok(self.detected().get_or_init(|| info))
+    }
+
+    /// Fail unless the server is at least the given version of CouchDB
+    pub(crate) async fn require_version(&self, version: Version, what: &str) -> Result<(), Error> {
+        let server = self.detect_server().await?;
+        if server.is_at_least(version) {
+            Ok(())
+        } else {
+            Err(Error::Unsupported(format!(
+                "{} requires CouchDB {} or later, but the server is {}",
+                what, version, server.version
+            )))
+        }
+    }
+
+    /// Fail unless the server has the given optional feature enabled
+    pub(crate) async fn require_feature(&self, feature: &str, what: &str) -> Result<(), Error> {
+        let server = self.detect_server().await?;
+        if server.has_feature(feature) {
+            Ok(())
+        } else {
+            Err(Error::Unsupported(format!(
+                "{} requires the '{}' feature, which CouchDB {} doesn't have enabled",
+                what, feature, server.version
+            )))
+        }
+    }
+
+    /// List the databases on the server.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use chesterfield::Client;
+    ///
+    /// # async fn run() {
+    /// let client = Client::from_url_str("http://localhost:5984").unwrap();
+    ///
+    /// // the first page of ten databases, starting at 'b'
+    /// let names = client.all_dbs().start_key("b").limit(10).send().await.unwrap();
+    /// # }
+    /// ```
+    pub fn all_dbs(&self) -> AllDbsRequest {
+        AllDbsRequest::new(self)
+    }
+
+    /// Retrieve information about several databases at once.
+    ///
+    /// Databases which don't exist are included, with an [error](DbsInfo::error). Servers
+    /// older than CouchDB 2.2 don't have `_dbs_info`, so each database is asked in turn.
+    pub async fn dbs_info<I, S>(&self, names: I) -> Result<Vec<DbsInfo>, Error>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let keys: Vec<String> = names
+            .into_iter()
+            .map(|name| name.as_ref().to_string())
+            .collect();
+
+        if !self
+            .detect_server()
+            .await?
+            .is_at_least(Version::new(2, 2, 0))
+        {
+            let mut infos = Vec::with_capacity(keys.len());
+            for key in keys {
+                let response = self.join([&key])?.get().send().await?;
+                let entry = if response.status() == StatusCode::NOT_FOUND {
+                    DbsInfo {
+                        key,
+                        info: None,
+                        error: Some("not_found".to_string()),
+                    }
+                } else {
+                    DbsInfo {
+                        key,
+                        info: Some(response.json()?),
+                        error: None,
+                    }
+                };
+                infos.push(entry);
+            }
+            return Ok(infos);
+        }
+
+        self.join(["_dbs_info"])?
+            .post()
+            .json(&serde_json::json!({ "keys": keys }))
+            .send()
+            .await?
+            .json()
+    }
+
+    /// Check whether the server is up, and able to serve requests.
+    ///
+    /// A server in maintenance mode responds with `404 Not Found`; this is reported as a
+    /// status which isn't [ok](UpStatus::is_ok) rather than as an error. Servers older than
+    /// CouchDB 2.0 don't have `_up`, and are reported as ok if they answer at all.
+    pub async fn up(&self) -> Result<UpStatus, Error> {
+        if !self
+            .detect_server()
+            .await?
+            .is_at_least(Version::new(2, 0, 0))
+        {
+            self.server_info().await?;
+            return Ok(UpStatus {
+                status: "ok".to_string(),
+                seeds: Value::Null,
+            });
+        }
+
+        let response = self.join(["_up"])?.get().send().await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            if let Ok(status) = response.json_unchecked() {
+                return Ok(status);
+            }
+        }
+        response.json()
+    }
+
+    /// Generate universally unique identifiers on the server.
+    pub async fn uuids(&self, count: usize) -> Result<Vec<String>, Error> {
+        #[derive(Deserialize)]
+        struct Uuids {
+            uuids: Vec<String>,
+        }
+
+        let response: Uuids = self
+            .join(["_uuids"])?
+            .get()
+            .query(&[("count", count)])
+            .send()
+            .await?
+            .json()?;
+        Ok(response.uuids)
+    }
+
+    /// List the tasks which are running on the server.
+    ///
+    /// This requires admin privileges.
+    pub async fn active_tasks(&self) -> Result<Vec<ActiveTask>, Error> {
+        self.join(["_active_tasks"])?.get().send().await?.json()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::FakeTransport;
+    use crate::transport::{BoxFuture, Request, Response, Transport};
+    use crate::Url;
+    use serde_json::json;
+    use std::sync::{Arc, Mutex};
+
+    /// A test double which pretends to be CouchDB 1.7, which has neither `_up` nor
+    /// `_dbs_info`
+    #[derive(Default)]
+    struct CouchDb1 {
+        requests: Mutex<Vec<String>>,
+    }
+
+    impl Transport for CouchDb1 {
+        fn execute(&self, request: Request) -> BoxFuture<'_, Result<Response, Error>> {
+            let path = request.uri().path().to_string();
+            self.requests
+                .lock()
+                .unwrap()
+                .push(format!("{} {}", request.method(), path));
+
+            Box::pin(async move {
+                let body = match path.as_str() {
+                    "/" => json!({
+                        "couchdb": "Welcome",
+                        "version": "1.7.1",
+                        "vendor": { "name": "The Apache Software Foundation", "version": "1.7.1" },
+                    }),
+                    "/items" => json!({
+                        "db_name": "items",
+                        "doc_count": 1,
+                        "doc_del_count": 0,
+                        "update_seq": 1,
+                        "purge_seq": 0,
+                        "compact_running": false,
+                        "instance_start_time": "1500000000000000",
+                    }),
+                    _ => {
+                        let mut response = http::Response::new(
+                            br#"{"error":"not_found","reason":"missing"}"#.to_vec(),
+                        );
+                        *response.status_mut() = StatusCode::NOT_FOUND;
+                        return Ok(response);
+                    }
+                };
+                Ok(http::Response::new(body.to_string().into_bytes()))
+            })
+        }
+    }
+
+    #[test]
+    fn versions() {
+        assert_eq!(Version::parse("3.3.3"), Some(Version::new(3, 3, 3)));
+        assert_eq!(Version::parse("2.3.1-a1b2c3d"), Some(Version::new(2, 3, 1)));
+        assert_eq!(Version::parse("2"), Some(Version::new(2, 0, 0)));
+        assert_eq!(Version::parse("dev"), None);
+        assert!(Version::new(2, 10, 0) > Version::new(2, 2, 0));
+        assert_eq!(Version::new(1, 7, 1).to_string(), "1.7.1");
+    }
+
+    #[tokio::test]
+    async fn adapts_to_older_servers() {
+        let server = Arc::new(CouchDb1::default());
+        let url = Url::parse("http://couch/").unwrap();
+        let client = Client::with_transport(url, Arc::clone(&server));
+
+        assert!(client.up().await.unwrap().is_ok());
+
+        let infos = client.dbs_info(["items", "missing"]).await.unwrap();
+        assert_eq!(infos[0].info.as_ref().unwrap().doc_count, 1);
+        assert_eq!(infos[1].error.as_deref(), Some("not_found"));
+
+        let database = client.database("items").unwrap();
+        let error = database
+            .create()
+            .partitioned(true)
+            .send()
+            .await
+            .unwrap_err();
+        assert!(matches!(error, Error::Unsupported(_)));
+
+        let requests = server.requests.lock().unwrap();
+        assert_eq!(
+            *requests,
+            ["GET /", "GET /", "GET /items", "GET /missing"],
+            "the version should be detected once, and nothing sent which it can't support"
+        );
+    }
+
+    #[test]
+    fn active_tasks() {
+        let task: ActiveTask = serde_json::from_value(json!({
+            "changes_done": 64,
+            "database": "shards/00000000-3fffffff/items.1518525394",
+            "node": "couchdb@127.0.0.1",
+            "pid": "<0.2381.0>",
+            "progress": 42,
+            "started_on": 1518525421,
+            "total_changes": 150,
+            "type": "database_compaction",
+            "updated_on": 1518525423,
+        }))
+        .unwrap();
+        assert_eq!(task.kind, "database_compaction");
+        assert_eq!(task.progress, Some(42));
+        assert_eq!(task.details["total_changes"], 150);
+        assert!(!task.details.contains_key("type"));
+    }
+
+    #[tokio::test]
+    async fn server_endpoints() {
+        let client = FakeTransport::new().client();
+
+        let info = client.server_info().await.unwrap();
+        assert_eq!(info.couchdb, "Welcome");
+        assert!(info.version.starts_with("3."));
+        assert!(info.features.iter().any(|feature| feature == "partitioned"));
+
+        let detected = client.detect_server().await.unwrap();
+        assert!(detected.is_at_least(crate::Version::new(3, 0, 0)));
+        assert!(detected.has_feature("partitioned"));
+
+        for name in &["alpha", "bravo", "charlie", "delta"] {
+            client
+                .database(*name)
+                .unwrap()
+                .create()
+                .send()
+                .await
+                .unwrap();
+        }
+        let all = client.all_dbs().send().await.unwrap();
+        assert_eq!(all, vec!["alpha", "bravo", "charlie", "delta"]);
+        let page = client
+            .all_dbs()
+            .start_key("b")
+            .limit(2)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(page, vec!["bravo", "charlie"]);
+        let page = client.all_dbs().skip(3).send().await.unwrap();
+        assert_eq!(page, vec!["delta"]);
+        let reversed = client
+            .all_dbs()
+            .descending(true)
+            .end_key("charlie")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(reversed, vec!["delta", "charlie"]);
+
+        let infos = client.dbs_info(["alpha", "missing"]).await.unwrap();
+        assert_eq!(infos.len(), 2);
+        assert_eq!(infos[0].info.as_ref().unwrap().db_name, "alpha");
+        assert_eq!(infos[1].error.as_deref(), Some("not_found"));
+
+        assert!(client.up().await.unwrap().is_ok());
+
+        let uuids = client.uuids(3).await.unwrap();
+        assert_eq!(uuids.len(), 3);
+        assert_ne!(uuids[0], uuids[1]);
+
+        assert!(client.active_tasks().await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,802 @@
+//! Statistics and metrics of a CouchDB node, at `/_node/{node}/_stats`, `_system` and
+//! `_prometheus`.
+
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+use crate::client::Client;
+use crate::config::{NodeConfig, LOCAL_NODE};
+use crate::{Error, Version};
+
+/// A node in the cluster, from [Client::node] or [Client::local_node].
+///
+/// Everything here requires admin privileges.
+pub struct Node {
+    client: Client,
+    name: String,
+}
+
+impl Node {
+    pub(crate) fn new(client: &Client, name: &str) -> Result<Self, Error> {
+        Ok(Node {
+            client: client.root().join(["_node", name])?,
+            name: name.to_string(),
+        })
+    }
+
+    /// The name of the node, or `_local` for whichever node receives the requests
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The configuration of the node
+    pub fn config(&self) -> NodeConfig {
+        NodeConfig::new(&self.client, &self.name).expect("the node name was already checked")
+    }
+
+    /// Retrieve the node's statistics (CouchDB 2 and later).
+    ///
+    /// see [CouchDB API docs](https://docs.couchdb.org/en/stable/api/server/common.html#node-node-name-stats)
+    /// for details.
+    pub async fn stats(&self) -> Result<NodeStats, Error> {
+        self.client
+            .require_version(Version::new(2, 0, 0), "_node/{node}/_stats")
+            .await?;
+        self.client.join(["_stats"])?.get().send().await?.json()
+    }
+
+    /// Retrieve the state of the node's Erlang VM (CouchDB 2 and later).
+    ///
+    /// see [CouchDB API docs](https://docs.couchdb.org/en/stable/api/server/common.html#node-node-name-system)
+    /// for details.
+    pub async fn system(&self) -> Result<SystemStats, Error> {
+        self.client
+            .require_version(Version::new(2, 0, 0), "_node/{node}/_system")
+            .await?;
+        self.client.join(["_system"])?.get().send().await?.json()
+    }
+
+    /// Retrieve the node's statistics in the Prometheus text format (CouchDB 3.2 and later).
+    pub async fn prometheus_text(&self) -> Result<String, Error> {
+        self.client
+            .require_version(Version::new(3, 2, 0), "_node/{node}/_prometheus")
+            .await?;
+        self.client
+            .join(["_prometheus"])?
+            .get()
+            .send()
+            .await?
+            .text()
+    }
+
+    /// Retrieve the node's statistics in the Prometheus text format, parsed into metric
+    /// families (CouchDB 3.2 and later).
+    pub async fn prometheus(&self) -> Result<Vec<MetricFamily>, Error> {
+        MetricFamily::parse_all(&self.prometheus_text().await?)
+    }
+}
+
+impl Client {
+    /// A named node in the cluster, such as `couchdb@127.0.0.1`.
+    ///
+    /// # Errors
+    /// This method fails if the name is empty
+    pub fn node(&self, name: &str) -> Result<Node, Error> {
+        Node::new(self, name)
+    }
+
+    /// The node which receives the requests.
+    pub fn local_node(&self) -> Node {
+        Node::new(self, LOCAL_NODE).expect("_local is a valid path segment")
+    }
+
+    /// Retrieve the statistics of the node which receives the requests.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use chesterfield::Client;
+    ///
+    /// # async fn run() {
+    /// let client = Client::from_url_str("http://localhost:5984").unwrap();
+    ///
+    /// let stats = client.node_stats().await.unwrap();
+    /// let requests = stats.counter("couchdb/httpd/requests").unwrap_or(0);
+    /// let open = stats.gauge("couchdb/open_databases").unwrap_or(0.0);
+    /// if let Some(times) = stats.histogram("couchdb/request_time") {
+    ///     println!("{} requests, median {}ms, {} open databases", requests, times.median, open);
+    /// }
+    /// # }
+    /// ```
+    pub async fn node_stats(&self) -> Result<NodeStats, Error> {
+        self.local_node().stats().await
+    }
+}
+
+/// A node's statistics, by path (such as `couchdb/httpd/requests`).
+#[derive(Debug, Clone, Default)]
+pub struct NodeStats {
+    metrics: BTreeMap<String, Metric>,
+}
+
+impl NodeStats {
+    /// A metric, by path
+    pub fn get(&self, path: &str) -> Option<&Metric> {
+        self.metrics.get(path)
+    }
+
+    /// The value of a counter, by path
+    pub fn counter(&self, path: &str) -> Option<u64> {
+        match self.get(path)?.value {
+            MetricValue::Counter(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// The value of a gauge, by path
+    pub fn gauge(&self, path: &str) -> Option<f64> {
+        match self.get(path)?.value {
+            MetricValue::Gauge(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// A histogram, by path
+    pub fn histogram(&self, path: &str) -> Option<&Histogram> {
+        match &self.get(path)?.value {
+            MetricValue::Histogram(histogram) => Some(histogram),
+            _ => None,
+        }
+    }
+
+    /// Every metric, in order of path
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Metric)> {
+        self.metrics
+            .iter()
+            .map(|(path, metric)| (path.as_str(), metric))
+    }
+
+    /// The number of metrics
+    pub fn len(&self) -> usize {
+        self.metrics.len()
+    }
+
+    /// Whether there are no metrics
+    pub fn is_empty(&self) -> bool {
+        self.metrics.is_empty()
+    }
+
+    /// Collect the metrics below a point in the tree of statistics
+    fn collect(&mut self, path: &str, tree: Map<String, Value>) -> Result<(), serde_json::Error> {
+        for (key, value) in tree {
+            let path = if path.is_empty() {
+                key
+            } else {
+                format!("{}/{}", path, key)
+            };
+            let node = match value {
+                Value::Object(node) => node,
+                _ => continue,
+            };
+            if !(node.get("type").is_some_and(Value::is_string) && node.contains_key("value")) {
+                self.collect(&path, node)?;
+                continue;
+            }
+
+            let raw: RawMetric = serde_json::from_value(Value::Object(node))?;
+            let value = match raw.kind.as_str() {
+                "counter" => MetricValue::Counter(
+                    raw.value
+                        .as_u64()
+                        .or_else(|| raw.value.as_f64().map(|value| value as u64))
+                        .unwrap_or_default(),
+                ),
+                "gauge" => MetricValue::Gauge(raw.value.as_f64().unwrap_or_default()),
+                "histogram" => MetricValue::Histogram(Box::new(serde_json::from_value(raw.value)?)),
+                other => {
+                    log::debug!("ignoring '{}', a metric of unknown type '{}'", path, other);
+                    continue;
+                }
+            };
+            self.metrics.insert(
+                path,
+                Metric {
+                    desc: raw.desc,
+                    value,
+                },
+            );
+        }
+        Ok(())
+    }
+}
+
+// Not derived, because the statistics are a tree of arbitrary depth
+impl<'de> Deserialize<'de> for NodeStats {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let tree = Map::deserialize(deserializer)?;
+        let mut stats = NodeStats::default();
+        stats.collect("", tree).map_err(D::Error::custom)?;
+        Ok(stats)
+    }
+}
+
+#[derive(Deserialize)]
+struct RawMetric {
+    #[serde(rename = "type")]
+    kind: String,
+    value: Value,
+    #[serde(default)]
+    desc: String,
+}
+
+/// One of a node's statistics
+#[derive(Debug, Clone, PartialEq)]
+pub struct Metric {
+    /// What the metric measures
+    pub desc: String,
+
+    /// The value of the metric
+    pub value: MetricValue,
+}
+
+/// The value of one of a node's statistics
+#[derive(Debug, Clone, PartialEq)]
+pub enum MetricValue {
+    /// A count which only increases
+    Counter(u64),
+
+    /// A value which goes up and down
+    Gauge(f64),
+
+    /// A summary of the recent values of a measurement, such as request times
+    Histogram(Box<Histogram>),
+}
+
+/// A summary of the recent values of a measurement
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Histogram {
+    /// The number of values
+    pub n: u64,
+
+    /// The smallest value
+    pub min: f64,
+
+    /// The largest value
+    pub max: f64,
+
+    /// The arithmetic mean
+    pub arithmetic_mean: f64,
+
+    /// The geometric mean
+    pub geometric_mean: f64,
+
+    /// The harmonic mean
+    pub harmonic_mean: f64,
+
+    /// The median
+    pub median: f64,
+
+    /// The variance
+    pub variance: f64,
+
+    /// The standard deviation
+    pub standard_deviation: f64,
+
+    /// The skewness
+    pub skewness: f64,
+
+    /// The kurtosis
+    pub kurtosis: f64,
+
+    /// Percentiles, as pairs of the percentile (such as 99) and its value
+    #[serde(default)]
+    pub percentile: Vec<(f64, f64)>,
+
+    /// Bins, as pairs of the upper bound of the bin and the number of values in it
+    #[serde(default)]
+    pub histogram: Vec<(f64, u64)>,
+}
+
+/// The state of a node's Erlang VM
+#[derive(Debug, Clone, Deserialize)]
+pub struct SystemStats {
+    /// How long the node has been running, in seconds
+    pub uptime: u64,
+
+    /// Memory use, in bytes
+    pub memory: MemoryStats,
+
+    /// The number of processes waiting to run
+    pub run_queue: u64,
+
+    /// The number of ETS tables
+    pub ets_table_count: u64,
+
+    /// The number of context switches since the node started
+    pub context_switches: u64,
+
+    /// The number of reductions since the node started
+    pub reductions: u64,
+
+    /// The number of garbage collections since the node started
+    pub garbage_collection_count: u64,
+
+    /// The number of words reclaimed by garbage collection since the node started
+    pub words_reclaimed: u64,
+
+    /// The number of bytes read from ports since the node started
+    pub io_input: u64,
+
+    /// The number of bytes written to ports since the node started
+    pub io_output: u64,
+
+    /// The number of external (query server) processes
+    pub os_proc_count: u64,
+
+    /// The number of external processes which are no longer in use
+    pub stale_proc_count: u64,
+
+    /// The number of Erlang processes
+    pub process_count: u64,
+
+    /// The maximum number of Erlang processes
+    pub process_limit: u64,
+
+    /// The lengths of the message queues of named processes
+    #[serde(default)]
+    pub message_queues: BTreeMap<String, MessageQueue>,
+
+    /// The number of internal replication jobs waiting to run
+    #[serde(default)]
+    pub internal_replication_jobs: u64,
+
+    /// Every other field
+    #[serde(flatten)]
+    pub other: Map<String, Value>,
+}
+
+/// The memory used by a node's Erlang VM, in bytes
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct MemoryStats {
+    /// Memory not accounted for by the other fields
+    #[serde(default)]
+    pub other: u64,
+
+    /// Memory allocated for atoms
+    #[serde(default)]
+    pub atom: u64,
+
+    /// Memory used by atoms
+    #[serde(default)]
+    pub atom_used: u64,
+
+    /// Memory allocated for processes
+    #[serde(default)]
+    pub processes: u64,
+
+    /// Memory used by processes
+    #[serde(default)]
+    pub processes_used: u64,
+
+    /// Memory used by binaries
+    #[serde(default)]
+    pub binary: u64,
+
+    /// Memory used by code
+    #[serde(default)]
+    pub code: u64,
+
+    /// Memory used by ETS tables
+    #[serde(default)]
+    pub ets: u64,
+}
+
+/// The message queue of one named process, or a summary of the queues of a group of them
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum MessageQueue {
+    /// The length of a single process's queue
+    Length(u64),
+
+    /// A summary of the lengths of the queues of a group of processes
+    Summary {
+        /// The number of processes
+        count: u64,
+        /// The shortest queue
+        min: u64,
+        /// The longest queue
+        max: u64,
+        /// The median length
+        #[serde(rename = "50")]
+        p50: u64,
+        /// The 90th percentile length
+        #[serde(rename = "90")]
+        p90: u64,
+        /// The 99th percentile length
+        #[serde(rename = "99")]
+        p99: u64,
+    },
+}
+
+/// A family of metrics in the Prometheus text format, such as a counter or a histogram
+/// and all of its samples.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetricFamily {
+    /// The name of the family, such as `couchdb_request_time_seconds`
+    pub name: String,
+
+    /// The type of the family, from its `# TYPE` line
+    pub kind: MetricType,
+
+    /// The description of the family, from its `# HELP` line
+    pub help: Option<String>,
+
+    /// The samples of the family, such as each of its quantiles or buckets
+    pub samples: Vec<Sample>,
+}
+
+/// The type of a [MetricFamily]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricType {
+    /// A count which only increases
+    Counter,
+    /// A value which goes up and down
+    Gauge,
+    /// Values counted into buckets
+    Histogram,
+    /// Quantiles of a set of values
+    Summary,
+    /// Anything else
+    Untyped,
+}
+
+/// A single sample of a [MetricFamily]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    /// The name of the sample, which is the name of its family or (for histograms and
+    /// summaries) that name with a suffix such as `_sum` or `_bucket`
+    pub name: String,
+
+    /// The labels of the sample, such as its `quantile`
+    pub labels: BTreeMap<String, String>,
+
+    /// The value
+    pub value: f64,
+
+    /// When the sample was taken, in milliseconds since the epoch, if given
+    pub timestamp: Option<i64>,
+}
+
+/// The suffixes of the samples which belong to a family of a different name
+const SAMPLE_SUFFIXES: [&str; 5] = ["_bucket", "_sum", "_count", "_total", "_created"];
+
+impl MetricFamily {
+    /// Parse metrics in the Prometheus text exposition format.
+    ///
+    /// # Errors
+    /// This method fails if a line can't be parsed
+    pub fn parse_all(text: &str) -> Result<Vec<MetricFamily>, Error> {
+        let mut families: Vec<MetricFamily> = Vec::new();
+
+        for (number, line) in text.lines().enumerate() {
+            let invalid =
+                |reason: &str| Error::InvalidMetrics(format!("line {}: {}", number + 1, reason));
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(comment) = line.strip_prefix('#') {
+                let mut parts = comment.trim_start().splitn(3, char::is_whitespace);
+                match (parts.next(), parts.next(), parts.next()) {
+                    (Some("HELP"), Some(name), help) => {
+                        family(&mut families, name).help =
+                            Some(unescape(help.unwrap_or_default().trim(), false));
+                    }
+                    (Some("TYPE"), Some(name), Some(kind)) => {
+                        family(&mut families, name).kind = match kind.trim() {
+                            "counter" => MetricType::Counter,
+                            "gauge" => MetricType::Gauge,
+                            "histogram" => MetricType::Histogram,
+                            "summary" => MetricType::Summary,
+                            "untyped" => MetricType::Untyped,
+                            _ => return Err(invalid("unknown metric type")),
+                        };
+                    }
+                    // any other comment
+                    _ => (),
+                }
+                continue;
+            }
+
+            let sample = parse_sample(line).map_err(invalid)?;
+            let name = SAMPLE_SUFFIXES
+                .iter()
+                .filter_map(|suffix| sample.name.strip_suffix(suffix))
+                .find(|base| families.iter().any(|family| family.name == *base))
+                .unwrap_or(&sample.name)
+                .to_string();
+            family(&mut families, &name).samples.push(sample);
+        }
+
+        Ok(families)
+    }
+}
+
+/// The family of the given name, which is created if need be
+fn family<'a>(families: &'a mut Vec<MetricFamily>, name: &str) -> &'a mut MetricFamily {
+    let index = match families.iter().position(|family| family.name == name) {
+        Some(index) => index,
+        None => {
+            families.push(MetricFamily {
+                name: name.to_string(),
+                kind: MetricType::Untyped,
+                help: None,
+                samples: Vec::new(),
+            });
+            families.len() - 1
+        }
+    };
+    &mut families[index]
+}
+
+/// Parse a sample, such as `name{label="value"} 1.5 1700000000000`
+fn parse_sample(line: &str) -> Result<Sample, &'static str> {
+    let end = line
+        .find(|c: char| c == '{' || c.is_whitespace())
+        .unwrap_or(line.len());
+    let (name, mut rest) = line.split_at(end);
+    if name.is_empty() {
+        return Err("missing metric name");
+    }
+
+    let mut labels = BTreeMap::new();
+    if let Some(body) = rest.strip_prefix('{') {
+        rest = body;
+        loop {
+            rest = rest.trim_start();
+            if let Some(after) = rest.strip_prefix('}') {
+                rest = after;
+                break;
+            }
+            let (label, after) = rest.split_once('=').ok_or("malformed label")?;
+            let after = after
+                .trim_start()
+                .strip_prefix('"')
+                .ok_or("unquoted label value")?;
+            let close = closing_quote(after).ok_or("unterminated label value")?;
+            labels.insert(label.trim().to_string(), unescape(&after[..close], true));
+            rest = after[close + 1..].trim_start();
+            rest = rest.strip_prefix(',').unwrap_or(rest);
+        }
+    }
+
+    let mut fields = rest.split_whitespace();
+    let value = fields
+        .next()
+        .ok_or("missing value")?
+        .parse()
+        .map_err(|_| "invalid value")?;
+    let timestamp = fields
+        .next()
+        .map(|timestamp| timestamp.parse().map_err(|_| "invalid timestamp"))
+        .transpose()?;
+
+    Ok(Sample {
+        name: name.to_string(),
+        labels,
+        value,
+        timestamp,
+    })
+}
+
+/// The index of the first quote which isn't escaped with a backslash
+fn closing_quote(text: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (index, c) in text.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => return Some(index),
+            _ => (),
+        }
+    }
+    None
+}
+
+/// Undo the escaping of backslashes, newlines and (in label values) quotes
+fn unescape(text: &str, quotes: bool) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('\\') => unescaped.push('\\'),
+            Some('"') if quotes => unescaped.push('"'),
+            Some(other) => {
+                unescaped.push('\\');
+                unescaped.push(other);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fake_database;
+    use serde_json::json;
+
+    #[test]
+    fn node_stats() {
+        let stats: NodeStats = serde_json::from_value(json!({
+            "couchdb": {
+                "open_databases": {
+                    "value": 3,
+                    "type": "gauge",
+                    "desc": "number of open databases"
+                },
+                "httpd": {
+                    "requests": {
+                        "value": 42,
+                        "type": "counter",
+                        "desc": "number of HTTP requests"
+                    }
+                },
+                "request_time": {
+                    "value": {
+                        "min": 0.5, "max": 10, "arithmetic_mean": 2.5, "geometric_mean": 2,
+                        "harmonic_mean": 1.5, "median": 2, "variance": 1, "standard_deviation": 1,
+                        "skewness": 0, "kurtosis": 0,
+                        "percentile": [[50, 2], [99, 9.5]],
+                        "histogram": [[1, 3], [10, 7]],
+                        "n": 10
+                    },
+                    "type": "histogram",
+                    "desc": "length of a request inside CouchDB without MochiWeb"
+                }
+            },
+            "mem3": {
+                "shard_cache": {
+                    "eviction": { "value": 0, "type": "counter", "desc": "shard cache evictions" }
+                }
+            }
+        }))
+        .unwrap();
+
+        assert_eq!(stats.len(), 4);
+        assert_eq!(stats.counter("couchdb/httpd/requests"), Some(42));
+        assert_eq!(stats.gauge("couchdb/open_databases"), Some(3.0));
+        assert_eq!(stats.counter("couchdb/open_databases"), None);
+        let times = stats.histogram("couchdb/request_time").unwrap();
+        assert_eq!(times.n, 10);
+        assert_eq!(times.percentile[1], (99.0, 9.5));
+        assert_eq!(times.histogram[0], (1.0, 3));
+        assert_eq!(
+            stats.get("mem3/shard_cache/eviction").unwrap().desc,
+            "shard cache evictions"
+        );
+    }
+
+    #[test]
+    fn system_stats() {
+        let system: SystemStats = serde_json::from_value(json!({
+            "uptime": 259,
+            "memory": { "other": 1, "atom": 2, "atom_used": 3, "processes": 4,
+                        "processes_used": 5, "binary": 6, "code": 7, "ets": 8 },
+            "run_queue": 0,
+            "run_queue_dirty_cpu": 0,
+            "ets_table_count": 157,
+            "context_switches": 99504,
+            "reductions": 102012120,
+            "garbage_collection_count": 8023,
+            "words_reclaimed": 19820010,
+            "io_input": 1207203,
+            "io_output": 2017022,
+            "os_proc_count": 0,
+            "stale_proc_count": 0,
+            "process_count": 298,
+            "process_limit": 262144,
+            "message_queues": {
+                "couch_file": { "count": 4, "min": 0, "max": 2, "50": 0, "90": 1, "99": 2 },
+                "couch_server": 0
+            },
+            "internal_replication_jobs": 0,
+            "distribution": {}
+        }))
+        .unwrap();
+
+        assert_eq!(system.memory.ets, 8);
+        assert_eq!(
+            system.message_queues["couch_server"],
+            MessageQueue::Length(0)
+        );
+        assert!(matches!(
+            system.message_queues["couch_file"],
+            MessageQueue::Summary {
+                count: 4,
+                p99: 2,
+                ..
+            }
+        ));
+        assert_eq!(system.other["run_queue_dirty_cpu"], 0);
+    }
+
+    #[test]
+    fn prometheus() {
+        let text = r#"
+# HELP couchdb_uptime_seconds couchdb uptime
+# TYPE couchdb_uptime_seconds counter
+couchdb_uptime_seconds 259
+# TYPE couchdb_request_time_seconds summary
+couchdb_request_time_seconds{quantile="0.5"} 0.002
+couchdb_request_time_seconds{quantile="0.99"} 0.0095
+couchdb_request_time_seconds_sum 1.25
+couchdb_request_time_seconds_count 400
+# HELP couchdb_httpd_status_codes number of HTTP responses\nby status
+# TYPE couchdb_httpd_status_codes counter
+couchdb_httpd_status_codes{code="200", path="a \"quoted\\ path\""} 42 1700000000000
+couchdb_httpd_status_codes{code="404",} +Inf
+erlang_unlisted NaN
+"#;
+        let families = MetricFamily::parse_all(text).unwrap();
+        assert_eq!(families.len(), 4);
+
+        assert_eq!(families[0].name, "couchdb_uptime_seconds");
+        assert_eq!(families[0].kind, MetricType::Counter);
+        assert_eq!(families[0].help.as_deref(), Some("couchdb uptime"));
+        assert_eq!(families[0].samples[0].value, 259.0);
+
+        let summary = &families[1];
+        assert_eq!(summary.kind, MetricType::Summary);
+        assert_eq!(summary.samples.len(), 4);
+        assert_eq!(summary.samples[1].labels["quantile"], "0.99");
+        assert_eq!(
+            summary.samples[3].name,
+            "couchdb_request_time_seconds_count"
+        );
+
+        let codes = &families[2];
+        assert_eq!(
+            codes.help.as_deref(),
+            Some("number of HTTP responses\nby status")
+        );
+        assert_eq!(codes.samples[0].labels["path"], r#"a "quoted\ path""#);
+        assert_eq!(codes.samples[0].timestamp, Some(1700000000000));
+        assert_eq!(codes.samples[1].value, f64::INFINITY);
+
+        assert_eq!(families[3].kind, MetricType::Untyped);
+        assert!(families[3].samples[0].value.is_nan());
+
+        assert!(MetricFamily::parse_all("metric{label=\"open 1").is_err());
+        assert!(MetricFamily::parse_all("metric one").is_err());
+    }
+
+    #[tokio::test]
+    async fn node_statistics() {
+        let (client, _) = fake_database("items").await;
+
+        let stats = client.node_stats().await.unwrap();
+        assert_eq!(stats.gauge("couchdb/open_databases"), Some(1.0));
+        assert!(stats.histogram("couchdb/request_time").is_some());
+
+        let node = client.node("nonode@nohost").unwrap();
+        assert_eq!(node.system().await.unwrap().process_limit, 262144);
+        assert!(node.config().section("chttpd").await.is_ok());
+
+        let families = client.local_node().prometheus().await.unwrap();
+        assert_eq!(families[0].name, "couchdb_open_databases_total");
+        assert_eq!(families[0].kind, crate::MetricType::Gauge);
+        assert_eq!(families[0].samples[0].value, 1.0);
+
+        let missing = client.node("nobody@nowhere").unwrap().stats().await;
+        assert!(missing.err().unwrap().is_not_found());
+    }
+}
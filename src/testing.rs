@@ -0,0 +1,17 @@
+//! Utilities for testing code which uses chesterfield.
+
+#[cfg(any(test, feature = "fake-server"))]
+mod fake_server;
+#[cfg(feature = "fixtures")]
+mod fixtures;
+#[cfg(test)]
+mod support;
+
+#[cfg(feature = "fake-server")]
+pub use self::fake_server::FakeServer;
+#[cfg(any(test, feature = "fake-server"))]
+pub use self::fake_server::FakeTransport;
+#[cfg(feature = "fixtures")]
+pub use self::fixtures::{fixture, Recorder, Replayer, UnexpectedRequest, RECORD_ENV_VAR};
+#[cfg(test)]
+pub(crate) use self::support::{fake_database, Item};
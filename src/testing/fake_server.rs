@@ -0,0 +1,1355 @@
+//! An in-process fake CouchDB server.
+
+mod mango;
+#[cfg(feature = "fake-server")]
+mod server;
+mod store;
+
+#[cfg(feature = "fake-server")]
+pub use self::server::FakeServer;
+
+use self::store::{generation, new_id, Db, Doc, Props, Store, StoreError, StoreResult};
+use crate::transport::{BoxFuture, Request as TransportRequest, Response, Transport};
+use crate::{Client, Error, Url};
+use http::header::{CONTENT_LENGTH, CONTENT_TYPE, ETAG, IF_NONE_MATCH};
+use http::{HeaderMap, HeaderValue, Method, StatusCode};
+use serde_json::{json, Map, Value};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+
+/// The fake CouchDB server, answering requests in-process as a [Transport] rather than
+/// over a socket.
+///
+/// This supports everything `FakeServer` does, but needs neither a free port nor any
+/// particular async runtime. Clones share the same state.
+///
+/// Requires the `fake-server` feature.
+///
+/// # Example
+/// ```
+/// use chesterfield::testing::FakeTransport;
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let client = FakeTransport::new().client();
+///
+/// let database = client.database("items").unwrap();
+/// database.create().send().await.unwrap();
+///
+/// assert!(database.exists().await.unwrap());
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct FakeTransport {
+    store: Arc<Mutex<Store>>,
+}
+
+impl FakeTransport {
+    /// A new, empty server
+    pub fn new() -> Self {
+        FakeTransport::default()
+    }
+
+    /// Create a client which sends its requests to this server
+    pub fn client(&self) -> Client {
+        let url = Url::parse("http://fake-couchdb/").expect("a valid URL");
+        Client::with_transport(url, self.clone())
+    }
+}
+
+impl Transport for FakeTransport {
+    fn execute(&self, request: TransportRequest) -> BoxFuture<'_, Result<Response, Error>> {
+        let response = respond(&self.store, request);
+        Box::pin(async move { Ok(response) })
+    }
+}
+
+/// Answer a request from the state of the server
+fn respond(store: &Mutex<Store>, request: TransportRequest) -> Response {
+    let (parts, body) = request.into_parts();
+    let request = Request {
+        method: &parts.method,
+        headers: &parts.headers,
+        query: parts
+            .uri
+            .query()
+            .map(|query| {
+                url::form_urlencoded::parse(query.as_bytes())
+                    .into_owned()
+                    .collect()
+            })
+            .unwrap_or_default(),
+        body: &body,
+    };
+
+    let mut reply = {
+        let mut store = store.lock().expect("fake server state was poisoned");
+        route(&mut store, parts.uri.path(), &request).unwrap_or_else(Reply::from)
+    };
+
+    // a conditional GET of an unchanged document
+    let if_none_match = parts.headers.get(IF_NONE_MATCH);
+    let unchanged = match (&reply.etag, if_none_match) {
+        (Some(rev), Some(etag)) => {
+            reply.status == StatusCode::OK && *etag == format!("\"{}\"", rev)
+        }
+        _ => false,
+    };
+    let body = if unchanged {
+        reply.status = StatusCode::NOT_MODIFIED;
+        Vec::new()
+    } else {
+        match &reply.body {
+            Value::String(text) if reply.text => text.clone().into_bytes(),
+            body => body.to_string().into_bytes(),
+        }
+    };
+    let length = HeaderValue::from(body.len());
+    let mut response = if parts.method == Method::HEAD {
+        http::Response::new(Vec::new())
+    } else {
+        http::Response::new(body)
+    };
+    response.headers_mut().insert(CONTENT_LENGTH, length);
+    *response.status_mut() = reply.status;
+    let content_type = if reply.text {
+        "text/plain; version=0.0.4; charset=utf-8"
+    } else {
+        "application/json"
+    };
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+    if let Some(rev) = reply.etag {
+        if let Ok(etag) = HeaderValue::from_str(&format!("\"{}\"", rev)) {
+            response.headers_mut().insert(ETAG, etag);
+        }
+    }
+
+    response
+}
+
+/// The parts of an incoming request that the handlers care about
+struct Request<'a> {
+    method: &'a Method,
+    headers: &'a HeaderMap,
+    query: HashMap<String, String>,
+    body: &'a [u8],
+}
+
+impl<'a> Request<'a> {
+    fn flag(&self, name: &str) -> bool {
+        self.query
+            .get(name)
+            .map(|value| value == "true")
+            .unwrap_or(false)
+    }
+
+    /// A query parameter which is itself JSON encoded
+    fn json_param(&self, name: &str) -> StoreResult<Option<Value>> {
+        self.query
+            .get(name)
+            .map(|value| serde_json::from_str(value).map_err(bad_request))
+            .transpose()
+    }
+
+    fn usize_param(&self, name: &str) -> StoreResult<Option<usize>> {
+        self.query
+            .get(name)
+            .map(|value| value.parse().map_err(bad_request))
+            .transpose()
+    }
+
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).and_then(|value| value.to_str().ok())
+    }
+
+    fn json(&self) -> StoreResult<Value> {
+        serde_json::from_slice(self.body).map_err(bad_request)
+    }
+}
+
+fn bad_request(e: impl std::fmt::Display) -> StoreError {
+    StoreError::BadRequest(e.to_string())
+}
+
+struct Reply {
+    status: StatusCode,
+    body: Value,
+    etag: Option<String>,
+    /// Whether the body is a string to be sent as plain text, rather than as JSON
+    text: bool,
+}
+
+impl Reply {
+    fn new(status: StatusCode, body: Value) -> Self {
+        Reply {
+            status,
+            body,
+            etag: None,
+            text: false,
+        }
+    }
+
+    fn text(body: impl Into<String>) -> Self {
+        Reply {
+            text: true,
+            ..Reply::ok(Value::String(body.into()))
+        }
+    }
+
+    fn ok(body: Value) -> Self {
+        Reply::new(StatusCode::OK, body)
+    }
+
+    fn created(body: Value) -> Self {
+        Reply::new(StatusCode::CREATED, body)
+    }
+
+    fn with_etag(mut self, rev: impl Into<String>) -> Self {
+        self.etag = Some(rev.into());
+        self
+    }
+}
+
+impl From<StoreError> for Reply {
+    fn from(e: StoreError) -> Self {
+        let status = StatusCode::from_u16(e.status()).expect("valid status code");
+        Reply::new(status, e.to_json())
+    }
+}
+
+fn route(store: &mut Store, path: &str, request: &Request) -> StoreResult<Reply> {
+    let segments: Vec<String> = path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| percent_encoding::percent_decode_str(segment).decode_utf8_lossy())
+        .map(String::from)
+        .collect();
+    let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+    let method = request.method;
+
+    match segments.as_slice() {
+        [] if method == Method::GET || method == Method::HEAD => Ok(Reply::ok(json!({
+            "couchdb": "Welcome",
+            "version": "3.3.3",
+            "git_sha": "40afbcfc7",
+            "uuid": "fake0000000000000000000000000000",
+            "vendor": { "name": "chesterfield fake server" },
+            "features": ["partitioned", "scheduler"],
+        }))),
+        ["_all_dbs"] if method == Method::GET => all_dbs(store, request),
+        ["_dbs_info"] if method == Method::POST => dbs_info(store, request),
+        ["_up"] if method == Method::GET => Ok(Reply::ok(json!({ "status": "ok", "seeds": {} }))),
+        ["_uuids"] if method == Method::GET => uuids(request),
+        ["_active_tasks"] if method == Method::GET => Ok(Reply::ok(json!([]))),
+        ["_node", node, rest @ ..] => {
+            if *node != "_local" && *node != NODE_NAME {
+                return Err(StoreError::NotFound("missing"));
+            }
+            match rest {
+                ["_config", rest @ ..] => config(store, rest, request),
+                ["_stats"] if method == Method::GET => Ok(Reply::ok(node_stats(store))),
+                ["_system"] if method == Method::GET => Ok(Reply::ok(system_stats())),
+                ["_prometheus"] if method == Method::GET => Ok(Reply::text(prometheus(store))),
+                ["_stats"] | ["_system"] | ["_prometheus"] => Err(StoreError::MethodNotAllowed),
+                _ => Err(StoreError::NotFound("missing")),
+            }
+        }
+        ["_all_dbs"] | ["_dbs_info"] | ["_up"] | ["_uuids"] | ["_active_tasks"] | [] => {
+            Err(StoreError::MethodNotAllowed)
+        }
+        [db] => database(store, db, request),
+        [db, "_all_docs"] if method == Method::GET || method == Method::POST => {
+            all_docs(store.database(db)?, request)
+        }
+        [db, "_bulk_docs"] if method == Method::POST => bulk_docs(store.database_mut(db)?, request),
+        [db, "_changes"] if method == Method::GET || method == Method::POST => {
+            changes(store.database(db)?, request)
+        }
+        [db, "_find"] if method == Method::POST => find(store.database(db)?, request),
+        [db, "_security"] if method == Method::GET => Ok(Reply::ok(Value::Object(
+            store.database(db)?.security().clone(),
+        ))),
+        [db, "_security"] if method == Method::PUT => match request.json()? {
+            Value::Object(security) => {
+                store.database_mut(db)?.set_security(security);
+                Ok(Reply::ok(json!({ "ok": true })))
+            }
+            _ => Err(bad_request("the security object must be a JSON object")),
+        },
+        [db, "_compact"] | [db, "_view_cleanup"] if method == Method::POST => {
+            maintenance(store.database(db)?, request)
+        }
+        [db, "_compact", name] if method == Method::POST => {
+            let db = store.database(db)?;
+            design_document(db, name)?;
+            maintenance(db, request)
+        }
+        [db, "_ensure_full_commit"] if method == Method::POST => {
+            maintenance(store.database(db)?, request)?;
+            Ok(Reply::created(
+                json!({ "ok": true, "instance_start_time": "0" }),
+            ))
+        }
+        [db, "_design", name, "_info"] if method == Method::GET => {
+            let name = design_document(store.database(db)?, name)?;
+            Ok(Reply::ok(json!({
+                "name": name,
+                "view_index": {
+                    "compact_running": false,
+                    "updater_running": false,
+                    "waiting_clients": 0,
+                    "waiting_commit": false,
+                    "language": "javascript",
+                    "signature": "00000000000000000000000000000000",
+                    "sizes": { "active": 0, "external": 0, "file": 0 },
+                    "update_seq": 0,
+                    "purge_seq": 0,
+                },
+            })))
+        }
+        [db, prefix @ "_design", name] | [db, prefix @ "_local", name] => {
+            document(store, db, &format!("{}/{}", prefix, name), request)
+        }
+        [_, "_all_docs"]
+        | [_, "_bulk_docs"]
+        | [_, "_changes"]
+        | [_, "_find"]
+        | [_, "_compact"]
+        | [_, "_security"]
+        | [_, "_compact", _]
+        | [_, "_view_cleanup"]
+        | [_, "_ensure_full_commit"]
+        | [_, "_design", _, "_info"] => Err(StoreError::MethodNotAllowed),
+        [_, id] if id.starts_with('_') => Err(StoreError::BadRequest(
+            "Only reserved document ids may start with underscore.".to_string(),
+        )),
+        [db, id] => document(store, db, id, request),
+        _ => Err(StoreError::NotFound("missing")),
+    }
+}
+
+/// Compaction and the like, which have nothing to do in memory
+fn maintenance(_db: &Db, request: &Request) -> StoreResult<Reply> {
+    match request.header("content-type") {
+        Some(content_type) if content_type.starts_with("application/json") => {
+            Ok(Reply::new(StatusCode::ACCEPTED, json!({ "ok": true })))
+        }
+        _ => Err(StoreError::BadContentType),
+    }
+}
+
+/// The name of an existing design document
+fn design_document<'a>(db: &Db, name: &'a str) -> StoreResult<&'a str> {
+    match db.doc(&format!("_design/{}", name)) {
+        Ok(doc) if !doc.is_deleted() => Ok(name),
+        _ => Err(StoreError::NotFound("missing")),
+    }
+}
+
+fn database_info(name: &str, db: &Db) -> Value {
+    let (deleted, live): (Vec<&Doc>, Vec<&Doc>) = db
+        .docs()
+        .map(|(_, doc)| doc)
+        .partition(|doc| doc.is_deleted());
+    let external: usize = db
+        .docs()
+        .filter_map(|(id, doc)| doc.render(id, doc.winner()))
+        .map(|body| Value::Object(body).to_string().len())
+        .sum();
+    let props = db.props();
+    json!({
+        "db_name": name,
+        "doc_count": live.len(),
+        "doc_del_count": deleted.len(),
+        "update_seq": db.update_seq(),
+        "purge_seq": 0,
+        "compact_running": false,
+        "sizes": { "active": external, "external": external, "file": external },
+        "cluster": { "q": props.q, "n": props.n, "w": 1, "r": 1 },
+        "props": if props.partitioned { json!({ "partitioned": true }) } else { json!({}) },
+        "instance_start_time": "0",
+    })
+}
+
+fn all_dbs(store: &Store, request: &Request) -> StoreResult<Reply> {
+    let start_key = match request.json_param("start_key")? {
+        None => request.json_param("startkey")?,
+        key => key,
+    };
+    let end_key = match request.json_param("end_key")? {
+        None => request.json_param("endkey")?,
+        key => key,
+    };
+    let descending = request.flag("descending");
+    let skip = request.usize_param("skip")?.unwrap_or(0);
+    let limit = request.usize_param("limit")?.unwrap_or(usize::MAX);
+
+    let mut names = store.database_names();
+    if descending {
+        names.reverse();
+    }
+    let in_range = |name: &&String| {
+        let after_start = match start_key.as_ref().and_then(Value::as_str) {
+            Some(start) if descending => name.as_str() <= start,
+            Some(start) => name.as_str() >= start,
+            None => true,
+        };
+        let before_end = match end_key.as_ref().and_then(Value::as_str) {
+            Some(end) if descending => name.as_str() >= end,
+            Some(end) => name.as_str() <= end,
+            None => true,
+        };
+        after_start && before_end
+    };
+    let names: Vec<&String> = names
+        .into_iter()
+        .filter(in_range)
+        .skip(skip)
+        .take(limit)
+        .collect();
+    Ok(Reply::ok(json!(names)))
+}
+
+fn dbs_info(store: &Store, request: &Request) -> StoreResult<Reply> {
+    let body = request.json()?;
+    let keys = match body.get("keys") {
+        Some(Value::Array(keys)) => keys,
+        _ => return Err(bad_request("`keys` member must exist.")),
+    };
+    let results = keys
+        .iter()
+        .map(
+            |key| match key.as_str().map(|name| (name, store.database(name))) {
+                Some((name, Ok(db))) => json!({ "key": name, "info": database_info(name, db) }),
+                _ => json!({ "key": key, "error": "not_found" }),
+            },
+        )
+        .collect();
+    Ok(Reply::ok(Value::Array(results)))
+}
+
+/// The name of the fake server's only node
+const NODE_NAME: &str = "nonode@nohost";
+
+fn config(store: &mut Store, path: &[&str], request: &Request) -> StoreResult<Reply> {
+    let method = request.method;
+    match path {
+        [] if method == Method::GET => Ok(Reply::ok(json!(store.config().sections()))),
+        ["_reload"] if method == Method::POST => Ok(Reply::ok(json!({ "ok": true }))),
+        [section] if method == Method::GET => {
+            let settings = store.config().sections().get(*section);
+            Ok(Reply::ok(
+                settings.map_or_else(|| json!({}), |settings| json!(settings)),
+            ))
+        }
+        [section, key] if method == Method::GET => store
+            .config()
+            .get(section, key)
+            .map(|value| Reply::ok(json!(value)))
+            .ok_or(StoreError::NotFound("unknown_config_value")),
+        [section, key] if method == Method::PUT => match request.json()? {
+            Value::String(value) => {
+                let previous = store.config_mut().set(section, key, &value);
+                Ok(Reply::ok(json!(previous.unwrap_or_default())))
+            }
+            _ => Err(bad_request("config values must be strings")),
+        },
+        [section, key] if method == Method::DELETE => store
+            .config_mut()
+            .delete(section, key)
+            .map(|previous| Reply::ok(json!(previous)))
+            .ok_or(StoreError::NotFound("unknown_config_value")),
+        [] | ["_reload"] | [_] | [_, _] => Err(StoreError::MethodNotAllowed),
+        _ => Err(StoreError::NotFound("missing")),
+    }
+}
+
+fn node_stats(store: &Store) -> Value {
+    json!({
+        "couchdb": {
+            "open_databases": {
+                "value": store.database_names().len(),
+                "type": "gauge",
+                "desc": "number of open databases",
+            },
+            "request_time": {
+                "value": {
+                    "min": 0, "max": 0, "arithmetic_mean": 0, "geometric_mean": 0,
+                    "harmonic_mean": 0, "median": 0, "variance": 0, "standard_deviation": 0,
+                    "skewness": 0, "kurtosis": 0,
+                    "percentile": [[50, 0], [75, 0], [90, 0], [95, 0], [99, 0], [999, 0]],
+                    "histogram": [[0, 0]],
+                    "n": 0,
+                },
+                "type": "histogram",
+                "desc": "length of a request inside CouchDB without MochiWeb",
+            },
+        },
+    })
+}
+
+fn system_stats() -> Value {
+    json!({
+        "uptime": 0,
+        "memory": {
+            "other": 0, "atom": 0, "atom_used": 0, "processes": 0,
+            "processes_used": 0, "binary": 0, "code": 0, "ets": 0,
+        },
+        "run_queue": 0,
+        "ets_table_count": 0,
+        "context_switches": 0,
+        "reductions": 0,
+        "garbage_collection_count": 0,
+        "words_reclaimed": 0,
+        "io_input": 0,
+        "io_output": 0,
+        "os_proc_count": 0,
+        "stale_proc_count": 0,
+        "process_count": 1,
+        "process_limit": 262144,
+        "message_queues": {},
+        "internal_replication_jobs": 0,
+    })
+}
+
+fn prometheus(store: &Store) -> String {
+    format!(
+        "# TYPE couchdb_open_databases_total gauge\n\
+         couchdb_open_databases_total {}\n\
+         # TYPE couchdb_uptime_seconds counter\n\
+         couchdb_uptime_seconds 0\n",
+        store.database_names().len()
+    )
+}
+
+fn uuids(request: &Request) -> StoreResult<Reply> {
+    let count = request.usize_param("count")?.unwrap_or(1);
+    if count > 1000 {
+        return Err(bad_request("count parameter too large"));
+    }
+    let uuids: Vec<String> = (0..count).map(|_| new_id()).collect();
+    Ok(Reply::ok(json!({ "uuids": uuids })))
+}
+
+/// A query parameter which must be a positive integer, such as `q`
+fn positive_param(request: &Request, name: &str) -> StoreResult<Option<u32>> {
+    match request.usize_param(name)? {
+        Some(0) => Err(bad_request(format!("{} must be a positive integer", name))),
+        value => Ok(value.map(|value| value as u32)),
+    }
+}
+
+fn database(store: &mut Store, name: &str, request: &Request) -> StoreResult<Reply> {
+    match *request.method {
+        Method::PUT => {
+            let defaults = Props::default();
+            let props = Props {
+                q: positive_param(request, "q")?.unwrap_or(defaults.q),
+                n: positive_param(request, "n")?.unwrap_or(defaults.n),
+                partitioned: request.flag("partitioned"),
+            };
+            store.create_database(name)?;
+            store.database_mut(name)?.set_props(props);
+            Ok(Reply::created(json!({ "ok": true })))
+        }
+        Method::DELETE => {
+            store.delete_database(name)?;
+            Ok(Reply::ok(json!({ "ok": true })))
+        }
+        Method::GET | Method::HEAD => Ok(Reply::ok(database_info(name, store.database(name)?))),
+        Method::POST => {
+            let (id, mut fields) = split_document(request.json()?)?;
+            if name == "_users" {
+                hash_password(&mut fields.body);
+            }
+            let id = id.unwrap_or_else(new_id);
+            let rev = store.database_mut(name)?.update(
+                &id,
+                fields.rev.as_deref(),
+                fields.body,
+                fields.deleted,
+            )?;
+            Ok(Reply::created(json!({ "ok": true, "id": id, "rev": rev })).with_etag(rev))
+        }
+        _ => Err(StoreError::MethodNotAllowed),
+    }
+}
+
+fn document(store: &mut Store, db: &str, id: &str, request: &Request) -> StoreResult<Reply> {
+    match *request.method {
+        // for HEAD the body isn't sent, but its length is
+        Method::GET | Method::HEAD => get_document(store.database(db)?, id, request),
+        Method::PUT => {
+            let (_, mut fields) = split_document(request.json()?)?;
+            if fields.rev.is_none() {
+                fields.rev = request.query.get("rev").cloned();
+            }
+            if db == "_users" {
+                hash_password(&mut fields.body);
+            }
+            let db = store.database_mut(db)?;
+            let rev = if request.query.get("new_edits").map(String::as_str) == Some("false") {
+                db.force_update(id, &fields.ancestry()?, fields.body, fields.deleted)?
+            } else {
+                db.update(id, fields.rev.as_deref(), fields.body, fields.deleted)?
+            };
+            Ok(Reply::created(json!({ "ok": true, "id": id, "rev": rev })).with_etag(rev))
+        }
+        Method::DELETE => {
+            let rev = request.query.get("rev").ok_or(StoreError::Conflict)?;
+            let rev = store
+                .database_mut(db)?
+                .update(id, Some(rev), Map::new(), true)?;
+            Ok(Reply::ok(json!({ "ok": true, "id": id, "rev": rev })).with_etag(rev))
+        }
+        _ if request.method.as_str() == "COPY" => {
+            copy_document(store.database_mut(db)?, id, request)
+        }
+        _ => Err(StoreError::MethodNotAllowed),
+    }
+}
+
+fn copy_document(db: &mut Db, id: &str, request: &Request) -> StoreResult<Reply> {
+    // like CouchDB, take the header as-is (not percent-decoded), up to the first '?'
+    let destination = request
+        .headers
+        .get("destination")
+        .map(|value| String::from_utf8_lossy(value.as_bytes()))
+        .ok_or_else(|| bad_request("Destination header is mandatory for COPY."))?;
+    let (destination, destination_rev) = match destination.split_once('?') {
+        Some((destination, query)) => match query.split_once('=') {
+            Some((_, rev)) => (destination, Some(rev)),
+            None => return Err(bad_request("Invalid rev in Destination header.")),
+        },
+        None => (destination.as_ref(), None),
+    };
+
+    let doc = db.doc(id)?;
+    let rev = match request.query.get("rev") {
+        Some(rev) => rev.as_str(),
+        None if doc.is_deleted() => return Err(StoreError::NotFound("deleted")),
+        None => doc.winner(),
+    };
+    let (_, fields) = split_document(Value::Object(
+        doc.render(id, rev).ok_or(StoreError::NotFound("missing"))?,
+    ))?;
+
+    let rev = db.update(destination, destination_rev, fields.body, false)?;
+    Ok(Reply::created(json!({ "ok": true, "id": destination, "rev": rev })).with_etag(rev))
+}
+
+fn get_document(db: &Db, id: &str, request: &Request) -> StoreResult<Reply> {
+    let doc = db.doc(id)?;
+
+    if let Some(open_revs) = request.query.get("open_revs") {
+        let revs: Vec<String> = if open_revs == "all" {
+            doc.leaves().into_iter().map(String::from).collect()
+        } else {
+            serde_json::from_str(open_revs).map_err(bad_request)?
+        };
+        let results = revs
+            .into_iter()
+            .map(|rev| match render(doc, id, &rev, request) {
+                Some(body) => json!({ "ok": body }),
+                None => json!({ "missing": rev }),
+            })
+            .collect();
+        return Ok(Reply::ok(Value::Array(results)));
+    }
+
+    let rev = match request.query.get("rev") {
+        Some(rev) => rev.as_str(),
+        None if doc.is_deleted() => return Err(StoreError::NotFound("deleted")),
+        None => doc.winner(),
+    };
+
+    let body = render(doc, id, rev, request).ok_or(StoreError::NotFound("missing"))?;
+    Ok(Reply::ok(Value::Object(body)).with_etag(rev))
+}
+
+/// Render a revision of a document, including whatever metadata was asked for
+fn render(doc: &Doc, id: &str, rev: &str, request: &Request) -> Option<Map<String, Value>> {
+    let mut body = doc.render(id, rev)?;
+    let meta = request.flag("meta");
+
+    if request.flag("revs") {
+        let ids: Vec<&str> = doc
+            .ancestry(rev)
+            .into_iter()
+            .map(|rev| rev.split_once('-').map(|(_, hash)| hash).unwrap_or(rev))
+            .collect();
+        body.insert(
+            "_revisions".to_string(),
+            json!({ "start": generation(rev), "ids": ids }),
+        );
+    }
+
+    if meta || request.flag("revs_info") {
+        let revs_info: Vec<Value> = doc
+            .ancestry(rev)
+            .into_iter()
+            .map(|rev| json!({ "rev": rev, "status": doc.status(rev).as_str() }))
+            .collect();
+        body.insert("_revs_info".to_string(), Value::from(revs_info));
+    }
+
+    if meta || request.flag("conflicts") {
+        let conflicts = doc.conflicts();
+        if !conflicts.is_empty() {
+            body.insert("_conflicts".to_string(), json!(conflicts));
+        }
+    }
+
+    if meta || request.flag("deleted_conflicts") {
+        let deleted_conflicts = doc.deleted_conflicts();
+        if !deleted_conflicts.is_empty() {
+            body.insert("_deleted_conflicts".to_string(), json!(deleted_conflicts));
+        }
+    }
+
+    if request.flag("local_seq") {
+        body.insert("_local_seq".to_string(), Value::from(doc.seq()));
+    }
+
+    Some(body)
+}
+
+/// The special fields of a document, separated from its body
+struct Fields {
+    rev: Option<String>,
+    deleted: bool,
+    revisions: Option<Value>,
+    body: Map<String, Value>,
+}
+
+impl Fields {
+    /// The revision and its ancestors, newest first (for `new_edits=false`)
+    fn ancestry(&self) -> StoreResult<Vec<String>> {
+        let rev = self
+            .rev
+            .clone()
+            .ok_or_else(|| bad_request("_rev is required when new_edits is false"))?;
+
+        let revisions = match &self.revisions {
+            Some(revisions) => revisions,
+            None => return Ok(vec![rev]),
+        };
+
+        let start = revisions["start"]
+            .as_u64()
+            .ok_or_else(|| bad_request("_revisions.start must be a number"))?;
+        let ids = revisions["ids"]
+            .as_array()
+            .filter(|ids| !ids.is_empty())
+            .ok_or_else(|| bad_request("_revisions.ids must be a non-empty array"))?;
+
+        ids.iter()
+            .enumerate()
+            .map(|(i, id)| match id.as_str() {
+                Some(id) if (i as u64) < start => Ok(format!("{}-{}", start - i as u64, id)),
+                _ => Err(bad_request("invalid _revisions")),
+            })
+            .collect()
+    }
+}
+
+/// Replace a user's plain text password with a (fake) hash, as CouchDB does
+fn hash_password(body: &mut Map<String, Value>) {
+    if let Some(Value::String(password)) = body.remove("password") {
+        let salt = new_id();
+        let mut hasher = DefaultHasher::new();
+        (&salt, &password).hash(&mut hasher);
+        body.insert("password_scheme".to_string(), json!("pbkdf2"));
+        body.insert("iterations".to_string(), json!(10));
+        body.insert("salt".to_string(), json!(salt));
+        body.insert(
+            "derived_key".to_string(),
+            json!(format!("{:016x}", hasher.finish())),
+        );
+    }
+}
+
+fn split_document(document: Value) -> StoreResult<(Option<String>, Fields)> {
+    let mut body = match document {
+        Value::Object(body) => body,
+        _ => return Err(bad_request("Document must be a JSON object")),
+    };
+
+    let id = match body.remove("_id") {
+        Some(Value::String(id)) => Some(id),
+        Some(_) => return Err(bad_request("Document id must be a string")),
+        None => None,
+    };
+    let rev = match body.remove("_rev") {
+        Some(Value::String(rev)) => Some(rev),
+        Some(_) => return Err(bad_request("Document rev must be a string")),
+        None => None,
+    };
+    let deleted = body.remove("_deleted") == Some(Value::Bool(true));
+    let revisions = body.remove("_revisions");
+    body.retain(|key, _| !key.starts_with('_') || key == "_attachments");
+
+    Ok((
+        id,
+        Fields {
+            rev,
+            deleted,
+            revisions,
+            body,
+        },
+    ))
+}
+
+fn all_docs(db: &Db, request: &Request) -> StoreResult<Reply> {
+    let include_docs = request.flag("include_docs");
+    let descending = request.flag("descending");
+    let skip = request.usize_param("skip")?.unwrap_or(0);
+    let limit = request.usize_param("limit")?.unwrap_or(usize::MAX);
+
+    let row = |id: &str, doc: &Doc| {
+        let rev = doc.winner();
+        let mut row = json!({ "id": id, "key": id, "value": { "rev": rev } });
+        if doc.is_deleted() {
+            row["value"]["deleted"] = Value::from(true);
+            if include_docs {
+                row["doc"] = Value::Null;
+            }
+        } else if include_docs {
+            row["doc"] = doc
+                .render(id, rev)
+                .map(Value::Object)
+                .unwrap_or(Value::Null);
+        }
+        row
+    };
+
+    let keys = if *request.method == Method::POST {
+        request.json()?.get("keys").cloned()
+    } else {
+        request.json_param("keys")?
+    };
+    let live = db.docs().filter(|(_, doc)| !doc.is_deleted());
+    let total_rows = live.clone().count();
+
+    if let Some(keys) = keys {
+        let keys: Vec<String> = serde_json::from_value(keys).map_err(bad_request)?;
+        let rows: Vec<Value> = keys
+            .iter()
+            .map(|key| match db.doc(key) {
+                Ok(doc) => row(key, doc),
+                Err(_) => json!({ "key": key, "error": "not_found" }),
+            })
+            .skip(skip)
+            .take(limit)
+            .collect();
+        return Ok(Reply::ok(json!({
+            "total_rows": total_rows,
+            "offset": Value::Null,
+            "rows": rows,
+        })));
+    }
+
+    let key = request.json_param("key")?;
+    let start_key = match request.json_param("startkey")? {
+        Some(key) => Some(key),
+        None => request.json_param("start_key")?,
+    };
+    let end_key = match request.json_param("endkey")? {
+        Some(key) => Some(key),
+        None => request.json_param("end_key")?,
+    };
+    let inclusive_end = request.query.get("inclusive_end").map(String::as_str) != Some("false");
+
+    let as_str = |key: Option<Value>| -> StoreResult<Option<String>> {
+        key.map(|key| serde_json::from_value(key).map_err(bad_request))
+            .transpose()
+    };
+    let key = as_str(key)?;
+    let start_key = as_str(start_key)?;
+    let end_key = as_str(end_key)?;
+
+    let in_range = |id: &str| {
+        let past_start = match &start_key {
+            Some(start) if descending => id <= start.as_str(),
+            Some(start) => id >= start.as_str(),
+            None => true,
+        };
+        let before_end = match &end_key {
+            Some(end) if descending && inclusive_end => id >= end.as_str(),
+            Some(end) if descending => id > end.as_str(),
+            Some(end) if inclusive_end => id <= end.as_str(),
+            Some(end) => id < end.as_str(),
+            None => true,
+        };
+        let matches_key = key.as_deref().map(|key| key == id).unwrap_or(true);
+        past_start && before_end && matches_key
+    };
+
+    let ordered: Vec<(&String, &Doc)> = if descending {
+        live.rev().collect()
+    } else {
+        live.collect()
+    };
+    let offset = ordered.iter().take_while(|(id, _)| !in_range(id)).count() + skip;
+    let rows: Vec<Value> = ordered
+        .into_iter()
+        .filter(|(id, _)| in_range(id))
+        .skip(skip)
+        .take(limit)
+        .map(|(id, doc)| row(id, doc))
+        .collect();
+
+    Ok(Reply::ok(json!({
+        "total_rows": total_rows,
+        "offset": offset,
+        "rows": rows,
+    })))
+}
+
+fn find(db: &Db, request: &Request) -> StoreResult<Reply> {
+    let body = request.json()?;
+    let selector = match body.get("selector") {
+        Some(selector @ Value::Object(_)) => selector,
+        _ => return Err(bad_request("Missing required key: selector")),
+    };
+    // the bookmark is simply the number of matches already returned
+    let bookmark = body
+        .get("bookmark")
+        .and_then(Value::as_str)
+        .and_then(|bookmark| bookmark.parse::<usize>().ok())
+        .unwrap_or(0);
+    let skip = body.get("skip").and_then(Value::as_u64).unwrap_or(0) as usize + bookmark;
+    let limit = body.get("limit").and_then(Value::as_u64).unwrap_or(25) as usize;
+
+    let docs: Vec<Value> = db
+        .docs()
+        .filter(|(id, doc)| !doc.is_deleted() && !id.starts_with("_design/"))
+        .filter_map(|(id, doc)| doc.render(id, doc.winner()).map(Value::Object))
+        .filter(|doc| mango::matches(selector, doc))
+        .skip(skip)
+        .take(limit)
+        .collect();
+
+    let bookmark = (skip + docs.len()).to_string();
+    Ok(Reply::ok(json!({ "docs": docs, "bookmark": bookmark })))
+}
+
+fn bulk_docs(db: &mut Db, request: &Request) -> StoreResult<Reply> {
+    let body = request.json()?;
+    let new_edits = body
+        .get("new_edits")
+        .and_then(Value::as_bool)
+        .unwrap_or(true);
+    let docs = match body.get("docs") {
+        Some(Value::Array(docs)) => docs.clone(),
+        _ => return Err(bad_request("POST body must include `docs` parameter.")),
+    };
+
+    // check the whole batch before writing any of it, so a bad request writes nothing
+    let docs = docs
+        .into_iter()
+        .map(|document| {
+            let (id, fields) = split_document(document)?;
+            let ancestry = if new_edits {
+                Vec::new()
+            } else {
+                fields.ancestry()?
+            };
+            Ok((id.unwrap_or_else(new_id), fields, ancestry))
+        })
+        .collect::<StoreResult<Vec<_>>>()?;
+
+    let mut results = Vec::new();
+    for (id, fields, ancestry) in docs {
+        if new_edits {
+            let result = db.update(&id, fields.rev.as_deref(), fields.body, fields.deleted);
+            results.push(match result {
+                Ok(rev) => json!({ "ok": true, "id": id, "rev": rev }),
+                Err(e) => {
+                    let mut error = e.to_json();
+                    error["id"] = Value::from(id);
+                    error
+                }
+            });
+        } else {
+            db.force_update(&id, &ancestry, fields.body, fields.deleted)?;
+        }
+    }
+
+    Ok(Reply::created(Value::Array(results)))
+}
+
+fn changes(db: &Db, request: &Request) -> StoreResult<Reply> {
+    let include_docs = request.flag("include_docs");
+    let all_leaves = request.query.get("style").map(String::as_str) == Some("all_docs");
+    let limit = request.usize_param("limit")?.unwrap_or(usize::MAX);
+    let since = match request.query.get("since").map(String::as_str) {
+        Some("now") => db.update_seq(),
+        Some(since) => since.parse().map_err(bad_request)?,
+        None => 0,
+    };
+
+    let doc_ids: Option<Vec<String>> =
+        if request.query.get("filter").map(String::as_str) == Some("_doc_ids") {
+            let doc_ids = if *request.method == Method::POST {
+                request.json()?.get("doc_ids").cloned()
+            } else {
+                request.json_param("doc_ids")?
+            };
+            doc_ids
+                .map(|doc_ids| serde_json::from_value(doc_ids).map_err(bad_request))
+                .transpose()?
+        } else {
+            None
+        };
+
+    let mut changed: Vec<(&String, &Doc)> = db
+        .docs()
+        .filter(|(_, doc)| doc.seq() > since)
+        .filter(|(id, _)| {
+            doc_ids
+                .as_ref()
+                .map(|doc_ids| doc_ids.contains(id))
+                .unwrap_or(true)
+        })
+        .collect();
+    changed.sort_by_key(|(_, doc)| doc.seq());
+    if request.flag("descending") {
+        changed.reverse();
+    }
+
+    let pending = changed.len().saturating_sub(limit);
+    let results: Vec<Value> = changed
+        .into_iter()
+        .take(limit)
+        .map(|(id, doc)| {
+            let revs: Vec<Value> = if all_leaves {
+                doc.leaves()
+                    .into_iter()
+                    .map(|rev| json!({ "rev": rev }))
+                    .collect()
+            } else {
+                vec![json!({ "rev": doc.winner() })]
+            };
+            let mut result = json!({ "seq": doc.seq(), "id": id, "changes": revs });
+            if doc.is_deleted() {
+                result["deleted"] = Value::from(true);
+            }
+            if include_docs {
+                result["doc"] = doc
+                    .render(id, doc.winner())
+                    .map(Value::Object)
+                    .unwrap_or(Value::Null);
+            }
+            result
+        })
+        .collect();
+
+    let last_seq = results
+        .last()
+        .map(|result| result["seq"].clone())
+        .unwrap_or_else(|| Value::from(db.update_seq()));
+
+    Ok(Reply::ok(json!({
+        "results": results,
+        "last_seq": last_seq,
+        "pending": pending,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FakeTransport;
+    use crate::transport::Transport;
+    use crate::{GetResponse, HighestGeneration};
+    use http::Method;
+    use serde_json::{json, Value};
+
+    async fn request(
+        server: &FakeTransport,
+        method: Method,
+        path: &str,
+        body: Option<Value>,
+    ) -> (u16, Value) {
+        let body = body.map(|body| body.to_string().into_bytes());
+        let request = http::Request::builder()
+            .method(method)
+            .uri(format!("http://fake-couchdb{}", path))
+            .body(body.unwrap_or_default())
+            .unwrap();
+        let response = server.execute(request).await.unwrap();
+        let status = response.status().as_u16();
+        let body = serde_json::from_slice(response.body()).unwrap_or(Value::Null);
+        (status, body)
+    }
+
+    #[tokio::test]
+    async fn databases() {
+        let server = FakeTransport::new();
+        let database = server.client().database("items").unwrap();
+
+        assert!(!database.exists().await.unwrap());
+        database.create().send().await.unwrap();
+        assert!(database.exists().await.unwrap());
+
+        let (status, body) = request(&server, Method::PUT, "/items", None).await;
+        assert_eq!(status, 412);
+        assert_eq!(body["error"], "file_exists");
+
+        let (status, _) = request(&server, Method::DELETE, "/items", None).await;
+        assert_eq!(status, 200);
+        assert!(!database.exists().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn document_crud() {
+        let server = FakeTransport::new();
+        let database = server.client().database("items").unwrap();
+        database.create().send().await.unwrap();
+
+        let doc = json!({ "field": 1 });
+        let inserted = database
+            .insert(&doc, String::from("some-id"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(inserted.id, "some-id");
+
+        let fetched = database.get("some-id").send::<Value>().await.unwrap();
+        assert_eq!(fetched.meta_data()._rev, inserted.rev);
+        assert_eq!(fetched.into_inner().unwrap()["field"], 1);
+
+        let doc = json!({ "field": 2 });
+        let updated = database
+            .update(&doc, "some-id", inserted.rev.clone())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(updated.rev.generation(), 2);
+
+        // writing against a stale revision is a conflict
+        let (status, body) = request(
+            &server,
+            Method::PUT,
+            "/items/some-id",
+            Some(json!({ "_rev": inserted.rev, "field": 3 })),
+        )
+        .await;
+        assert_eq!(status, 409);
+        assert_eq!(body["error"], "conflict");
+
+        let deleted = database
+            .delete("some-id", updated.rev)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(deleted.rev.generation(), 3);
+
+        let (status, body) = request(&server, Method::GET, "/items/some-id", None).await;
+        assert_eq!(status, 404);
+        assert_eq!(body["reason"], "deleted");
+    }
+
+    #[tokio::test]
+    async fn bulk_docs_and_conflicts() {
+        let server = FakeTransport::new();
+        let database = server.client().database("items").unwrap();
+        database.create().send().await.unwrap();
+
+        let (status, body) = request(
+            &server,
+            Method::POST,
+            "/items/_bulk_docs",
+            Some(json!({ "docs": [{ "_id": "a" }, { "_id": "b" }, { "_id": "a" }] })),
+        )
+        .await;
+        assert_eq!(status, 201);
+        assert_eq!(body[0]["ok"], true);
+        assert_eq!(body[1]["ok"], true);
+        assert_eq!(body[2]["error"], "conflict");
+
+        // introduce a conflicting branch, as the replicator would
+        let (status, _) = request(
+            &server,
+            Method::POST,
+            "/items/_bulk_docs",
+            Some(json!({
+                "new_edits": false,
+                "docs": [{ "_id": "a", "_rev": "1-zzzz", "from": "replica" }],
+            })),
+        )
+        .await;
+        assert_eq!(status, 201);
+
+        // a bad document anywhere in the batch means nothing is written
+        let (status, _) = request(
+            &server,
+            Method::POST,
+            "/items/_bulk_docs",
+            Some(json!({
+                "new_edits": false,
+                "docs": [
+                    { "_id": "c", "_rev": "1-cccc" },
+                    { "_id": "d", "_rev": "2-dddd", "_revisions": { "start": 1, "ids": ["d", "x"] } },
+                ],
+            })),
+        )
+        .await;
+        assert_eq!(status, 400);
+        let (status, _) = request(&server, Method::GET, "/items/c", None).await;
+        assert_eq!(status, 404);
+
+        let (_, body) = request(&server, Method::GET, "/items/a?conflicts=true", None).await;
+        assert_eq!(body["_rev"], "1-zzzz");
+        assert_eq!(body["_conflicts"].as_array().unwrap().len(), 1);
+
+        let doc: GetResponse = database.get("a").meta(true).send().await.unwrap();
+        let meta = doc.meta_data();
+        let leaves = meta.leaves();
+        assert_eq!(leaves.len(), 2);
+        assert_eq!(leaves[0].to_string(), "1-zzzz");
+        assert_eq!(leaves[1].generation(), 1);
+        assert_eq!(meta.available_revisions().count(), 1);
+
+        let (_, body) = request(&server, Method::GET, "/items/a?open_revs=all", None).await;
+        assert_eq!(body.as_array().unwrap().len(), 2);
+
+        let resolution = database
+            .resolve_conflicts("a", HighestGeneration)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(resolution.rev.generation(), 2);
+        assert_eq!(resolution.deleted.len(), 1);
+
+        let doc: GetResponse = database.get("a").conflicts(true).send().await.unwrap();
+        assert_eq!(doc.meta_data()._rev, resolution.rev);
+        assert_eq!(doc.meta_data()._conflicts, None);
+        assert_eq!(doc.into_inner().unwrap()["from"], "replica");
+
+        assert_eq!(
+            database
+                .resolve_conflicts("a", HighestGeneration)
+                .await
+                .unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn all_docs_and_changes() {
+        let server = FakeTransport::new();
+        let database = server.client().database("items").unwrap();
+        database.create().send().await.unwrap();
+
+        for id in &["c", "a", "b"] {
+            database
+                .insert(&json!({}), id.to_string())
+                .send()
+                .await
+                .unwrap();
+        }
+
+        let (_, body) = request(
+            &server,
+            Method::GET,
+            "/items/_all_docs?startkey=%22b%22&include_docs=true",
+            None,
+        )
+        .await;
+        assert_eq!(body["total_rows"], 3);
+        assert_eq!(body["offset"], 1);
+        let ids: Vec<&str> = body["rows"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|row| row["doc"]["_id"].as_str().unwrap())
+            .collect();
+        assert_eq!(ids, vec!["b", "c"]);
+
+        let (_, body) = request(&server, Method::GET, "/items/_changes?since=1", None).await;
+        let ids: Vec<&str> = body["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|result| result["id"].as_str().unwrap())
+            .collect();
+        assert_eq!(ids, vec!["a", "b"]);
+        assert_eq!(body["last_seq"], 3);
+    }
+
+    #[tokio::test]
+    async fn awkward_ids() {
+        let (_, database) = crate::testing::fake_database("items").await;
+
+        for id in &[
+            "a/b",
+            "what?",
+            "#hash",
+            "100%",
+            "..",
+            "a b+c",
+            "_design/x/y",
+        ] {
+            let response = database
+                .insert(&json!({ "id": id }), id.to_string())
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(&response.id, id);
+        }
+
+        for id in &["a/b", "what?", "#hash", "100%", "a b+c", "_design/x/y"] {
+            let doc = database.get(*id).send::<Value>().await.unwrap();
+            assert_eq!(&doc.meta_data()._id, id);
+        }
+
+        // '..' can be created with a POST, but not addressed with a URL
+        assert!(database.get("..").send::<Value>().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn design_and_local_documents() {
+        let server = FakeTransport::new();
+        request(&server, Method::PUT, "/items", None).await;
+
+        let (status, body) = request(
+            &server,
+            Method::PUT,
+            "/items/_design/views",
+            Some(json!({ "views": {} })),
+        )
+        .await;
+        assert_eq!(status, 201);
+        assert_eq!(body["id"], "_design/views");
+
+        let (status, _) = request(&server, Method::GET, "/items/_design/views", None).await;
+        assert_eq!(status, 200);
+
+        let (status, body) = request(&server, Method::GET, "/items/_reserved", None).await;
+        assert_eq!(status, 400);
+        assert_eq!(body["error"], "bad_request");
+    }
+
+    #[cfg(all(feature = "fake-server", any(feature = "reqwest", feature = "hyper")))]
+    #[tokio::test]
+    async fn serves_over_http() {
+        let server = super::FakeServer::start().await.unwrap();
+        let database = server.client().unwrap().database("items").unwrap();
+
+        assert!(!database.exists().await.unwrap());
+        database.create().send().await.unwrap();
+        database
+            .insert(&json!({ "field": 1 }), String::from("some-id"))
+            .send()
+            .await
+            .unwrap();
+        assert!(database.head("some-id").await.unwrap().is_some());
+        let doc = database.get("some-id").send::<Value>().await.unwrap();
+        assert_eq!(doc.into_inner().unwrap()["field"], 1);
+    }
+}
@@ -0,0 +1,144 @@
+//! Just enough of the Mango query language for `_find`.
+//!
+//! Selectors are evaluated against each document in turn- there are no indexes.
+
+use serde_json::{Map, Value};
+use std::cmp::Ordering;
+
+/// Whether a document satisfies a selector
+pub(super) fn matches(selector: &Value, doc: &Value) -> bool {
+    match selector {
+        Value::Object(conditions) => conditions
+            .iter()
+            .all(|(key, condition)| matches_condition(key, condition, doc)),
+        _ => false,
+    }
+}
+
+fn matches_condition(key: &str, condition: &Value, doc: &Value) -> bool {
+    match key {
+        "$and" => each(condition).all(|selector| matches(selector, doc)),
+        "$or" => each(condition).any(|selector| matches(selector, doc)),
+        "$nor" => !each(condition).any(|selector| matches(selector, doc)),
+        "$not" => !matches(condition, doc),
+        field => matches_field(lookup(doc, field), condition),
+    }
+}
+
+fn each(condition: &Value) -> impl Iterator<Item = &Value> {
+    condition.as_array().into_iter().flatten()
+}
+
+/// Find a (possibly nested, dot-separated) field
+fn lookup<'a>(doc: &'a Value, field: &str) -> Option<&'a Value> {
+    field
+        .split('.')
+        .try_fold(doc, |value, segment| value.as_object()?.get(segment))
+}
+
+fn matches_field(value: Option<&Value>, condition: &Value) -> bool {
+    match condition {
+        Value::Object(operators) if is_operators(operators) => operators
+            .iter()
+            .all(|(operator, argument)| apply(operator, argument, value)),
+        Value::Object(_) => match value {
+            Some(value) => matches(condition, value),
+            None => false,
+        },
+        // a bare value is shorthand for $eq
+        _ => value == Some(condition),
+    }
+}
+
+fn is_operators(map: &Map<String, Value>) -> bool {
+    map.keys().all(|key| key.starts_with('$'))
+}
+
+fn apply(operator: &str, argument: &Value, value: Option<&Value>) -> bool {
+    match operator {
+        "$exists" => value.is_some() == argument.as_bool().unwrap_or(true),
+        "$ne" => value != Some(argument),
+        "$not" => !matches_field(value, argument),
+        _ => {
+            let value = match value {
+                Some(value) => value,
+                None => return false,
+            };
+            match operator {
+                "$eq" => value == argument,
+                "$gt" => compare(value, argument) == Some(Ordering::Greater),
+                "$gte" => matches!(
+                    compare(value, argument),
+                    Some(Ordering::Greater) | Some(Ordering::Equal)
+                ),
+                "$lt" => compare(value, argument) == Some(Ordering::Less),
+                "$lte" => matches!(
+                    compare(value, argument),
+                    Some(Ordering::Less) | Some(Ordering::Equal)
+                ),
+                "$in" => each(argument).any(|candidate| candidate == value),
+                "$nin" => !each(argument).any(|candidate| candidate == value),
+                "$size" => value.as_array().map(Vec::len) == argument.as_u64().map(|n| n as usize),
+                "$all" => value
+                    .as_array()
+                    .map(|values| each(argument).all(|wanted| values.contains(wanted)))
+                    .unwrap_or(false),
+                "$elemMatch" => value
+                    .as_array()
+                    .map(|values| values.iter().any(|v| matches_field(Some(v), argument)))
+                    .unwrap_or(false),
+                _ => false,
+            }
+        }
+    }
+}
+
+fn compare(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64()?.partial_cmp(&b.as_f64()?),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::matches;
+    use serde_json::json;
+
+    #[test]
+    fn selectors() {
+        let doc = json!({
+            "type": "user",
+            "age": 42,
+            "name": { "first": "Ada" },
+            "tags": ["a", "b"],
+        });
+
+        let cases = [
+            (json!({}), true),
+            (json!({ "type": "user" }), true),
+            (json!({ "type": "item" }), false),
+            (json!({ "type": { "$eq": "user" } }), true),
+            (json!({ "age": { "$gt": 40, "$lte": 42 } }), true),
+            (json!({ "age": { "$lt": 42 } }), false),
+            (json!({ "name.first": "Ada" }), true),
+            (json!({ "name": { "first": "Ada" } }), true),
+            (json!({ "missing": { "$exists": false } }), true),
+            (json!({ "age": { "$in": [1, 42] } }), true),
+            (json!({ "tags": { "$all": ["a", "b"] } }), true),
+            (json!({ "tags": { "$elemMatch": { "$eq": "b" } } }), true),
+            (json!({ "$or": [{ "age": 1 }, { "type": "user" }] }), true),
+            (
+                json!({ "$and": [{ "age": 42 }, { "type": "item" }] }),
+                false,
+            ),
+            (json!({ "type": { "$ne": "user" } }), false),
+        ];
+
+        for (selector, expected) in &cases {
+            assert_eq!(matches(selector, &doc), *expected, "{}", selector);
+        }
+    }
+}
@@ -0,0 +1,131 @@
+//! The fake server, bound to a local port.
+
+use super::respond;
+use super::store::Store;
+use crate::{Error, Url};
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use hyper::body::Incoming;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper_util::rt::TokioIo;
+use std::convert::Infallible;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+/// A fake CouchDB server, running in-process and bound to a local port.
+///
+/// This implements (a useful subset of) the CouchDB HTTP API entirely in memory, so
+/// that integration tests can run without Docker or network access. It supports
+///
+/// - server information, `_all_dbs`, `_dbs_info`, `_up`, `_uuids` and `_active_tasks`
+/// - creating, checking and deleting databases
+/// - creating, reading, updating and deleting documents, with MVCC revisions and
+///   conflict detection
+/// - copying documents with `COPY`
+/// - database security objects, and hashing the passwords of users in `_users`
+/// - the node configuration, statistics, system statistics and Prometheus metrics, at
+///   `/_node/_local/...`
+/// - compaction, view cleanup and `_ensure_full_commit` (which do nothing)
+/// - conditional requests with `If-None-Match`
+/// - `_all_docs`, `_bulk_docs` (including `new_edits=false`) and `_changes`
+/// - `_find`, with a subset of Mango selectors (evaluated without indexes)
+///
+/// The server is shut down when it's dropped.
+///
+/// Requires the `fake-server` feature.
+///
+/// # Example
+/// ```
+/// use chesterfield::testing::FakeServer;
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let server = FakeServer::start().await.unwrap();
+/// let client = server.client().unwrap();
+///
+/// let database = client.database("items").unwrap();
+/// database.create().send().await.unwrap();
+///
+/// assert!(database.exists().await.unwrap());
+/// # }
+/// ```
+pub struct FakeServer {
+    address: SocketAddr,
+    handle: JoinHandle<()>,
+}
+
+impl FakeServer {
+    /// Start a new, empty server on an unused local port.
+    ///
+    /// This must be called from within a tokio runtime.
+    pub async fn start() -> Result<Self, Error> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await?;
+        let address = listener.local_addr()?;
+        let store = Arc::new(Mutex::new(Store::default()));
+        let handle = tokio::spawn(serve(listener, store));
+
+        Ok(FakeServer { address, handle })
+    }
+
+    /// The base URL of the server
+    pub fn url(&self) -> Url {
+        Url::parse(&format!("http://{}", self.address)).expect("socket address is a valid URL")
+    }
+
+    /// The local port the server is bound to
+    pub fn port(&self) -> u16 {
+        self.address.port()
+    }
+
+    /// Create a client pointing at this server, using the default transport.
+    #[cfg(any(feature = "reqwest", feature = "hyper"))]
+    pub fn client(&self) -> Result<crate::Client, Error> {
+        crate::Client::new(self.url())
+    }
+}
+
+impl Drop for FakeServer {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+async fn serve(listener: TcpListener, store: Arc<Mutex<Store>>) {
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                log::warn!("fake server failed to accept connection: {}", e);
+                continue;
+            }
+        };
+
+        let store = Arc::clone(&store);
+        tokio::spawn(async move {
+            let service = service_fn(move |request| handle(Arc::clone(&store), request));
+            if let Err(e) = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                log::debug!("fake server connection error: {}", e);
+            }
+        });
+    }
+}
+
+async fn handle(
+    store: Arc<Mutex<Store>>,
+    request: hyper::Request<Incoming>,
+) -> Result<hyper::Response<Full<Bytes>>, Infallible> {
+    let (parts, body) = request.into_parts();
+    let body = match body.collect().await {
+        Ok(body) => body.to_bytes().to_vec(),
+        Err(_) => Vec::new(),
+    };
+
+    let response = respond(&store, http::Request::from_parts(parts, body));
+    Ok(response.map(|body| Full::new(Bytes::from(body))))
+}
@@ -0,0 +1,577 @@
+//! The in-memory state behind the fake server.
+//!
+//! Each document keeps its full revision tree, so that conflicting edits (introduced
+//! with `new_edits=false`, as the replicator does) behave the way they do in CouchDB.
+
+use serde_json::{json, Map, Value};
+use std::collections::hash_map::{DefaultHasher, RandomState};
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{BuildHasher, Hash, Hasher};
+
+/// Errors which map directly onto CouchDB error responses
+#[derive(Debug, PartialEq)]
+pub(super) enum StoreError {
+    NotFound(&'static str),
+    Conflict,
+    FileExists,
+    IllegalDatabaseName,
+    BadRequest(String),
+    MethodNotAllowed,
+    BadContentType,
+}
+
+impl StoreError {
+    pub(super) fn status(&self) -> u16 {
+        match self {
+            StoreError::NotFound(_) => 404,
+            StoreError::Conflict => 409,
+            StoreError::FileExists => 412,
+            StoreError::IllegalDatabaseName | StoreError::BadRequest(_) => 400,
+            StoreError::MethodNotAllowed => 405,
+            StoreError::BadContentType => 415,
+        }
+    }
+
+    pub(super) fn to_json(&self) -> Value {
+        let (error, reason) = match self {
+            StoreError::NotFound(reason) => ("not_found", (*reason).to_string()),
+            StoreError::Conflict => ("conflict", "Document update conflict.".to_string()),
+            StoreError::FileExists => (
+                "file_exists",
+                "The database could not be created, the file already exists.".to_string(),
+            ),
+            StoreError::IllegalDatabaseName => (
+                "illegal_database_name",
+                "Name must begin with a letter, and contain only lowercase letters, digits and _$()+-/"
+                    .to_string(),
+            ),
+            StoreError::BadRequest(reason) => ("bad_request", reason.clone()),
+            StoreError::MethodNotAllowed => (
+                "method_not_allowed",
+                "Method not allowed for this endpoint.".to_string(),
+            ),
+            StoreError::BadContentType => (
+                "bad_content_type",
+                "Content-Type must be application/json".to_string(),
+            ),
+        };
+        json!({ "error": error, "reason": reason })
+    }
+}
+
+pub(super) type StoreResult<T> = Result<T, StoreError>;
+
+#[derive(Default)]
+pub(super) struct Store {
+    databases: BTreeMap<String, Db>,
+    config: Config,
+}
+
+/// The node's configuration, by section and then key
+pub(super) struct Config(BTreeMap<String, BTreeMap<String, String>>);
+
+impl Default for Config {
+    fn default() -> Self {
+        let mut config = Config(BTreeMap::new());
+        config.set("couchdb", "max_document_size", "8000000");
+        config.set("couchdb", "uuid", "fake0000000000000000000000000000");
+        config.set("chttpd", "port", "5984");
+        config.set("chttpd", "bind_address", "127.0.0.1");
+        config.set("cluster", "q", "2");
+        config.set("cluster", "n", "1");
+        config
+    }
+}
+
+impl Config {
+    pub(super) fn sections(&self) -> &BTreeMap<String, BTreeMap<String, String>> {
+        &self.0
+    }
+
+    pub(super) fn get(&self, section: &str, key: &str) -> Option<&String> {
+        self.0.get(section)?.get(key)
+    }
+
+    /// Change a setting, returning its previous value
+    pub(super) fn set(&mut self, section: &str, key: &str, value: &str) -> Option<String> {
+        self.0
+            .entry(section.to_string())
+            .or_default()
+            .insert(key.to_string(), value.to_string())
+    }
+
+    /// Remove a setting, returning its previous value
+    pub(super) fn delete(&mut self, section: &str, key: &str) -> Option<String> {
+        let settings = self.0.get_mut(section)?;
+        let previous = settings.remove(key);
+        if settings.is_empty() {
+            self.0.remove(section);
+        }
+        previous
+    }
+}
+
+impl Store {
+    pub(super) fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub(super) fn config_mut(&mut self) -> &mut Config {
+        &mut self.config
+    }
+
+    pub(super) fn database_names(&self) -> Vec<&String> {
+        self.databases.keys().collect()
+    }
+
+    pub(super) fn create_database(&mut self, name: &str) -> StoreResult<()> {
+        if !is_valid_database_name(name) {
+            return Err(StoreError::IllegalDatabaseName);
+        }
+        if self.databases.contains_key(name) {
+            return Err(StoreError::FileExists);
+        }
+        self.databases.insert(name.to_string(), Db::default());
+        Ok(())
+    }
+
+    pub(super) fn delete_database(&mut self, name: &str) -> StoreResult<()> {
+        self.databases
+            .remove(name)
+            .map(|_| ())
+            .ok_or(StoreError::NotFound("Database does not exist."))
+    }
+
+    pub(super) fn database(&self, name: &str) -> StoreResult<&Db> {
+        self.databases
+            .get(name)
+            .ok_or(StoreError::NotFound("Database does not exist."))
+    }
+
+    pub(super) fn database_mut(&mut self, name: &str) -> StoreResult<&mut Db> {
+        self.databases
+            .get_mut(name)
+            .ok_or(StoreError::NotFound("Database does not exist."))
+    }
+}
+
+fn is_valid_database_name(name: &str) -> bool {
+    if matches!(name, "_users" | "_replicator" | "_global_changes") {
+        return true;
+    }
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_lowercase() => (),
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "_$()+-/".contains(c))
+}
+
+#[derive(Default)]
+pub(super) struct Db {
+    docs: BTreeMap<String, Doc>,
+    update_seq: u64,
+    props: Props,
+    security: Map<String, Value>,
+}
+
+/// The options a database was created with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct Props {
+    pub(super) q: u32,
+    pub(super) n: u32,
+    pub(super) partitioned: bool,
+}
+
+impl Default for Props {
+    fn default() -> Self {
+        // the defaults of a single node CouchDB 3
+        Props {
+            q: 2,
+            n: 1,
+            partitioned: false,
+        }
+    }
+}
+
+impl Db {
+    pub(super) fn props(&self) -> Props {
+        self.props
+    }
+
+    pub(super) fn set_props(&mut self, props: Props) {
+        self.props = props;
+    }
+
+    pub(super) fn security(&self) -> &Map<String, Value> {
+        &self.security
+    }
+
+    pub(super) fn set_security(&mut self, security: Map<String, Value>) {
+        self.security = security;
+    }
+
+    pub(super) fn update_seq(&self) -> u64 {
+        self.update_seq
+    }
+
+    pub(super) fn doc(&self, id: &str) -> StoreResult<&Doc> {
+        self.docs.get(id).ok_or(StoreError::NotFound("missing"))
+    }
+
+    /// All documents, in id order
+    pub(super) fn docs(&self) -> impl DoubleEndedIterator<Item = (&String, &Doc)> + Clone {
+        self.docs.iter()
+    }
+
+    /// Write a new revision of a document, as a normal client would.
+    ///
+    /// `rev` is the revision being replaced. It must be a current leaf of the
+    /// revision tree, unless the document doesn't exist (or has been deleted).
+    pub(super) fn update(
+        &mut self,
+        id: &str,
+        rev: Option<&str>,
+        body: Map<String, Value>,
+        deleted: bool,
+    ) -> StoreResult<String> {
+        let parent = match (self.docs.get(id), rev) {
+            (None, None) => None,
+            (None, Some(_)) => return Err(StoreError::Conflict),
+            (Some(doc), None) if doc.is_deleted() => Some(doc.winner().to_string()),
+            (Some(_), None) => return Err(StoreError::Conflict),
+            (Some(doc), Some(rev)) if doc.leaves().contains(&rev) => Some(rev.to_string()),
+            (Some(_), Some(_)) => return Err(StoreError::Conflict),
+        };
+
+        if parent.is_none() && deleted {
+            return Err(StoreError::NotFound("missing"));
+        }
+
+        let generation = parent.as_deref().map(generation).unwrap_or(0) + 1;
+        let rev = new_rev(generation, parent.as_deref(), &body, deleted);
+
+        self.update_seq += 1;
+        let seq = self.update_seq;
+        let doc = self.docs.entry(id.to_string()).or_default();
+        doc.seq = seq;
+        doc.revs.insert(
+            rev.clone(),
+            RevNode {
+                parent,
+                body: Some(body),
+                deleted,
+            },
+        );
+
+        Ok(rev)
+    }
+
+    /// Write a revision verbatim, as the replicator does (`new_edits=false`).
+    ///
+    /// `ancestry` is the revision followed by its ancestors, newest first. Any
+    /// ancestors which are not already known are recorded as 'missing'.
+    pub(super) fn force_update(
+        &mut self,
+        id: &str,
+        ancestry: &[String],
+        body: Map<String, Value>,
+        deleted: bool,
+    ) -> StoreResult<String> {
+        let rev = ancestry
+            .first()
+            .ok_or_else(|| StoreError::BadRequest("_rev is required".to_string()))?;
+
+        let doc = self.docs.entry(id.to_string()).or_default();
+        if doc.revs.contains_key(rev) {
+            return Ok(rev.clone());
+        }
+
+        self.update_seq += 1;
+        doc.seq = self.update_seq;
+
+        for (i, ancestor) in ancestry.iter().enumerate() {
+            let parent = ancestry.get(i + 1).cloned();
+            let node = doc.revs.entry(ancestor.clone()).or_insert(RevNode {
+                parent: None,
+                body: None,
+                deleted: false,
+            });
+            if node.parent.is_none() {
+                node.parent = parent;
+            }
+        }
+        let node = doc.revs.get_mut(rev).expect("revision was just inserted");
+        node.body = Some(body);
+        node.deleted = deleted;
+
+        Ok(rev.clone())
+    }
+}
+
+struct RevNode {
+    parent: Option<String>,
+    /// `None` if the revision is known about but its body is not
+    body: Option<Map<String, Value>>,
+    deleted: bool,
+}
+
+/// A document, and its revision tree
+#[derive(Default)]
+pub(super) struct Doc {
+    revs: HashMap<String, RevNode>,
+    seq: u64,
+}
+
+/// The status of a single revision, as reported in `_revs_info`
+pub(super) enum RevStatus {
+    Available,
+    Missing,
+    Deleted,
+}
+
+impl RevStatus {
+    pub(super) fn as_str(&self) -> &'static str {
+        match self {
+            RevStatus::Available => "available",
+            RevStatus::Missing => "missing",
+            RevStatus::Deleted => "deleted",
+        }
+    }
+}
+
+impl Doc {
+    /// The sequence number of the last change to this document
+    pub(super) fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    /// Leaf revisions, sorted so that the 'winning' revision comes first
+    pub(super) fn leaves(&self) -> Vec<&str> {
+        let mut leaves: Vec<&String> = self
+            .revs
+            .keys()
+            .filter(|rev| {
+                !self
+                    .revs
+                    .values()
+                    .any(|node| node.parent.as_ref() == Some(*rev))
+            })
+            .collect();
+
+        // the winner is the non-deleted leaf with the highest generation, tie-broken by hash
+        leaves.sort_by_key(|rev| {
+            (
+                std::cmp::Reverse(!self.revs[*rev].deleted),
+                std::cmp::Reverse(generation(rev)),
+                std::cmp::Reverse(rev.to_string()),
+            )
+        });
+        leaves.into_iter().map(String::as_str).collect()
+    }
+
+    /// The current 'winning' revision
+    pub(super) fn winner(&self) -> &str {
+        self.leaves()[0]
+    }
+
+    pub(super) fn is_deleted(&self) -> bool {
+        self.revs[self.winner()].deleted
+    }
+
+    /// Non-deleted leaf revisions which lost
+    pub(super) fn conflicts(&self) -> Vec<&str> {
+        self.leaves()
+            .into_iter()
+            .skip(1)
+            .filter(|rev| !self.revs[*rev].deleted)
+            .collect()
+    }
+
+    /// Deleted leaf revisions which lost
+    pub(super) fn deleted_conflicts(&self) -> Vec<&str> {
+        self.leaves()
+            .into_iter()
+            .skip(1)
+            .filter(|rev| self.revs[*rev].deleted)
+            .collect()
+    }
+
+    /// The revision, followed by its ancestors, newest first
+    pub(super) fn ancestry(&self, rev: &str) -> Vec<&str> {
+        let mut ancestry = Vec::new();
+        let mut current = self.revs.get_key_value(rev);
+        while let Some((rev, node)) = current {
+            ancestry.push(rev.as_str());
+            current = node
+                .parent
+                .as_ref()
+                .and_then(|parent| self.revs.get_key_value(parent));
+        }
+        ancestry
+    }
+
+    pub(super) fn status(&self, rev: &str) -> RevStatus {
+        match self.revs.get(rev) {
+            Some(RevNode { body: None, .. }) | None => RevStatus::Missing,
+            Some(RevNode { deleted: true, .. }) => RevStatus::Deleted,
+            Some(_) => RevStatus::Available,
+        }
+    }
+
+    /// The body of a revision, with the `_id`, `_rev` and `_deleted` fields added
+    pub(super) fn render(&self, id: &str, rev: &str) -> Option<Map<String, Value>> {
+        let node = self.revs.get(rev)?;
+        let mut body = node.body.clone()?;
+        body.insert("_id".to_string(), Value::from(id));
+        body.insert("_rev".to_string(), Value::from(rev));
+        if node.deleted {
+            body.insert("_deleted".to_string(), Value::from(true));
+        }
+        Some(body)
+    }
+}
+
+/// The generation number of a revision (the 'N' in 'N-hash')
+pub(super) fn generation(rev: &str) -> u64 {
+    rev.split('-')
+        .next()
+        .and_then(|generation| generation.parse().ok())
+        .unwrap_or(0)
+}
+
+/// Revisions are deterministic- the same edit to the same parent gives the same revision
+fn new_rev(
+    generation: u64,
+    parent: Option<&str>,
+    body: &Map<String, Value>,
+    deleted: bool,
+) -> String {
+    let mut hasher = DefaultHasher::new();
+    parent.hash(&mut hasher);
+    Value::Object(body.clone()).to_string().hash(&mut hasher);
+    deleted.hash(&mut hasher);
+    let high = hasher.finish();
+    generation.hash(&mut hasher);
+    let low = hasher.finish();
+    format!("{}-{:016x}{:016x}", generation, high, low)
+}
+
+/// A random document id
+pub(super) fn new_id() -> String {
+    let high = RandomState::new().build_hasher().finish();
+    let low = RandomState::new().build_hasher().finish();
+    format!("{:016x}{:016x}", high, low)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn body(value: Value) -> Map<String, Value> {
+        match value {
+            Value::Object(map) => map,
+            _ => panic!("not an object"),
+        }
+    }
+
+    #[test]
+    fn database_names() {
+        let mut store = Store::default();
+        assert!(store.create_database("a-valid_name$(+)/1").is_ok());
+        assert_eq!(
+            store.create_database("a-valid_name$(+)/1"),
+            Err(StoreError::FileExists)
+        );
+        assert_eq!(
+            store.create_database("Invalid"),
+            Err(StoreError::IllegalDatabaseName)
+        );
+        assert_eq!(
+            store.create_database("_private"),
+            Err(StoreError::IllegalDatabaseName)
+        );
+        assert!(store.create_database("_users").is_ok());
+    }
+
+    #[test]
+    fn updates_require_current_revision() {
+        let mut db = Db::default();
+        let rev1 = db
+            .update("doc", None, body(json!({"a": 1})), false)
+            .unwrap();
+        assert_eq!(generation(&rev1), 1);
+
+        assert_eq!(
+            db.update("doc", None, body(json!({"a": 2})), false),
+            Err(StoreError::Conflict)
+        );
+
+        let rev2 = db
+            .update("doc", Some(&rev1), body(json!({"a": 2})), false)
+            .unwrap();
+        assert_eq!(generation(&rev2), 2);
+
+        assert_eq!(
+            db.update("doc", Some(&rev1), body(json!({"a": 3})), false),
+            Err(StoreError::Conflict)
+        );
+
+        let doc = db.doc("doc").unwrap();
+        assert_eq!(doc.winner(), rev2);
+        assert_eq!(doc.ancestry(&rev2), vec![rev2.as_str(), rev1.as_str()]);
+        assert_eq!(db.update_seq(), 2);
+    }
+
+    #[test]
+    fn deleted_documents_can_be_recreated() {
+        let mut db = Db::default();
+        let rev1 = db.update("doc", None, Map::new(), false).unwrap();
+        let rev2 = db.update("doc", Some(&rev1), Map::new(), true).unwrap();
+        assert!(db.doc("doc").unwrap().is_deleted());
+
+        let rev3 = db.update("doc", None, Map::new(), false).unwrap();
+        let doc = db.doc("doc").unwrap();
+        assert!(!doc.is_deleted());
+        assert_eq!(doc.ancestry(&rev3), vec![&rev3, &rev2, &rev1]);
+    }
+
+    #[test]
+    fn conflicting_branches() {
+        let mut db = Db::default();
+        let rev1 = db.update("doc", None, Map::new(), false).unwrap();
+
+        let branch_a = vec!["2-a".to_string(), rev1.clone()];
+        let branch_b = vec!["2-b".to_string(), rev1.clone()];
+        db.force_update("doc", &branch_a, body(json!({"branch": "a"})), false)
+            .unwrap();
+        db.force_update("doc", &branch_b, body(json!({"branch": "b"})), false)
+            .unwrap();
+
+        let doc = db.doc("doc").unwrap();
+        assert_eq!(doc.leaves(), vec!["2-b", "2-a"]);
+        assert_eq!(doc.winner(), "2-b");
+        assert_eq!(doc.conflicts(), vec!["2-a"]);
+
+        // deleting the losing branch resolves the conflict
+        db.update("doc", Some("2-a"), Map::new(), true).unwrap();
+        let doc = db.doc("doc").unwrap();
+        assert_eq!(doc.winner(), "2-b");
+        assert!(doc.conflicts().is_empty());
+        assert_eq!(doc.deleted_conflicts().len(), 1);
+    }
+
+    #[test]
+    fn unknown_ancestors_are_missing() {
+        let mut db = Db::default();
+        let ancestry = vec!["3-c".to_string(), "2-b".to_string(), "1-a".to_string()];
+        db.force_update("doc", &ancestry, Map::new(), false)
+            .unwrap();
+
+        let doc = db.doc("doc").unwrap();
+        assert_eq!(doc.ancestry("3-c"), vec!["3-c", "2-b", "1-a"]);
+        assert!(matches!(doc.status("3-c"), RevStatus::Available));
+        assert!(matches!(doc.status("2-b"), RevStatus::Missing));
+        assert!(matches!(doc.status("1-a"), RevStatus::Missing));
+    }
+}
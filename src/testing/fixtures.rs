@@ -0,0 +1,493 @@
+//! Record-and-replay HTTP fixtures.
+
+use crate::transport::{BoxFuture, Request, Response, Transport};
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// The environment variable which switches [fixture] into recording mode
+pub const RECORD_ENV_VAR: &str = "CHESTERFIELD_RECORD";
+
+/// Create a transport for a test fixture.
+///
+/// If the `CHESTERFIELD_RECORD` environment variable is set, this returns a [Recorder]
+/// which sends requests using the transport returned by `live`, and saves the exchanges
+/// to `path`. Otherwise it returns a [Replayer] which serves the saved exchanges back
+/// from `path`, without touching the network.
+///
+/// # Example
+/// ```no_run
+/// use chesterfield::testing::fixture;
+/// use chesterfield::transport::ReqwestTransport;
+/// use chesterfield::{Client, Url};
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let transport = fixture("tests/fixtures/create.json", || ReqwestTransport::new()).unwrap();
+/// let url = Url::parse("http://localhost:5984").unwrap();
+/// let client = Client::with_transport(url, transport);
+///
+/// client.database("items").unwrap().create().send().await.unwrap();
+/// # }
+/// ```
+pub fn fixture<T, F>(path: impl Into<PathBuf>, live: F) -> Result<Box<dyn Transport>, Error>
+where
+    T: Transport,
+    F: FnOnce() -> Result<T, Error>,
+{
+    let path = path.into();
+    if std::env::var_os(RECORD_ENV_VAR).is_some() {
+        Ok(Box::new(Recorder::new(live()?, path)))
+    } else {
+        Ok(Box::new(Replayer::from_file(path)?))
+    }
+}
+
+/// A [Transport] which records HTTP exchanges to a fixture file.
+///
+/// Requests are sent using the wrapped transport. The exchanges are written
+/// to the fixture file when [save](Recorder::save) is called, and again when the
+/// recorder is dropped.
+pub struct Recorder<T> {
+    inner: T,
+    path: PathBuf,
+    exchanges: Mutex<Vec<Exchange>>,
+}
+
+impl<T: Transport> Recorder<T> {
+    /// Create a new recorder, which will write to the fixture file at `path`
+    pub fn new(inner: T, path: impl Into<PathBuf>) -> Self {
+        Recorder {
+            inner,
+            path: path.into(),
+            exchanges: Mutex::default(),
+        }
+    }
+
+    /// Write the exchanges recorded so far to the fixture file.
+    pub fn save(&self) -> Result<(), Error> {
+        let exchanges = self
+            .exchanges
+            .lock()
+            .expect("recorder was poisoned")
+            .clone();
+        write_fixture(&self.path, exchanges)
+    }
+}
+
+fn write_fixture(path: &Path, exchanges: Vec<Exchange>) -> Result<(), Error> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, serde_json::to_vec_pretty(&Fixture { exchanges })?)?;
+    Ok(())
+}
+
+impl<T: Transport> Transport for Recorder<T> {
+    fn execute(&self, request: Request) -> BoxFuture<'_, Result<Response, Error>> {
+        let recorded_request = RecordedRequest::from(&request);
+        Box::pin(async move {
+            let response = self.inner.execute(request).await?;
+            self.exchanges
+                .lock()
+                .expect("recorder was poisoned")
+                .push(Exchange {
+                    request: recorded_request,
+                    response: RecordedResponse::from(&response),
+                });
+            Ok(response)
+        })
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'_, ()> {
+        self.inner.sleep(duration)
+    }
+}
+
+impl<T> Drop for Recorder<T> {
+    fn drop(&mut self) {
+        let exchanges = match self.exchanges.get_mut() {
+            Ok(exchanges) => std::mem::take(exchanges),
+            Err(_) => return,
+        };
+        if let Err(e) = write_fixture(&self.path, exchanges) {
+            log::error!("failed to save fixture to '{}': {}", self.path.display(), e);
+        }
+    }
+}
+
+/// A [Transport] which serves recorded HTTP exchanges back from a fixture file.
+///
+/// Requests are matched on method, path (ignoring any trailing slash), query
+/// parameters (in any order), and body (JSON bodies are compared semantically).
+/// Each recorded exchange is served at most once, so a sequence of identical requests
+/// receive their recorded responses in order.
+///
+/// A request which doesn't match any remaining exchange fails with an [UnexpectedRequest] error.
+/// Responses are served exactly as they were recorded, byte for byte.
+///
+/// # Panics
+/// Dropping the replayer panics if any recorded exchange was never served, so that a test
+/// fails if it stops making a request which the fixture expects.
+pub struct Replayer {
+    exchanges: Mutex<Vec<Option<Exchange>>>,
+}
+
+impl Replayer {
+    /// Load the fixture file at `path`
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let fixture: Fixture = serde_json::from_slice(&fs::read(path)?)?;
+        Ok(Replayer {
+            exchanges: Mutex::new(fixture.exchanges.into_iter().map(Some).collect()),
+        })
+    }
+
+    /// The number of recorded exchanges which haven't been served yet
+    pub fn remaining(&self) -> usize {
+        self.exchanges
+            .lock()
+            .expect("replayer was poisoned")
+            .iter()
+            .filter(|exchange| exchange.is_some())
+            .count()
+    }
+}
+
+impl Transport for Replayer {
+    fn execute(&self, request: Request) -> BoxFuture<'_, Result<Response, Error>> {
+        let request = RecordedRequest::from(&request);
+        let response = self
+            .exchanges
+            .lock()
+            .expect("replayer was poisoned")
+            .iter_mut()
+            .find(|exchange| {
+                exchange
+                    .as_ref()
+                    .map(|exchange| exchange.request == request)
+                    .unwrap_or(false)
+            })
+            .and_then(Option::take)
+            .map(|exchange| exchange.response);
+
+        Box::pin(async move {
+            match response {
+                Some(response) => response.into_response(),
+                None => {
+                    log::error!("unexpected request: {}", UnexpectedRequest(request.clone()));
+                    Err(Error::Transport(Box::new(UnexpectedRequest(request))))
+                }
+            }
+        })
+    }
+}
+
+impl Drop for Replayer {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            return;
+        }
+        let unused: Vec<String> = match self.exchanges.get_mut() {
+            Ok(exchanges) => exchanges
+                .iter()
+                .flatten()
+                .map(|exchange| format!("{} {}", exchange.request.method, exchange.request.path))
+                .collect(),
+            Err(_) => return,
+        };
+        assert!(
+            unused.is_empty(),
+            "{} recorded exchange(s) were never requested: {}",
+            unused.len(),
+            unused.join(", ")
+        );
+    }
+}
+
+/// The error returned by a [Replayer] when a request doesn't match the fixture.
+#[derive(Debug)]
+pub struct UnexpectedRequest(RecordedRequest);
+
+impl std::fmt::Display for UnexpectedRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let request = &self.0;
+        write!(
+            f,
+            "no recorded response for {} {}",
+            request.method, request.path
+        )?;
+        if !request.query.is_empty() {
+            write!(f, " with query {:?}", request.query)?;
+        }
+        if request.body != Body::Empty {
+            write!(f, " with body {:?}", request.body)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for UnexpectedRequest {}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Fixture {
+    exchanges: Vec<Exchange>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Exchange {
+    request: RecordedRequest,
+    response: RecordedResponse,
+}
+
+/// A request, normalised so that equivalent requests compare equal
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct RecordedRequest {
+    method: String,
+    path: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    query: Vec<(String, String)>,
+    #[serde(default)]
+    body: Body,
+}
+
+impl From<&Request> for RecordedRequest {
+    fn from(request: &Request) -> Self {
+        let uri = request.uri();
+        let path = match uri.path().trim_end_matches('/') {
+            "" => "/",
+            path => path,
+        };
+        let mut query: Vec<(String, String)> = uri
+            .query()
+            .map(|query| {
+                url::form_urlencoded::parse(query.as_bytes())
+                    .into_owned()
+                    .collect()
+            })
+            .unwrap_or_default();
+        query.sort();
+
+        RecordedRequest {
+            method: request.method().to_string(),
+            path: path.to_string(),
+            query,
+            body: Body::from(request.body().as_slice()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RecordedResponse {
+    status: u16,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    headers: BTreeMap<String, String>,
+    #[serde(default)]
+    body: Body,
+}
+
+impl From<&Response> for RecordedResponse {
+    fn from(response: &Response) -> Self {
+        let headers = response
+            .headers()
+            .iter()
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect();
+
+        RecordedResponse {
+            status: response.status().as_u16(),
+            headers,
+            body: Body::exact(response.body()),
+        }
+    }
+}
+
+impl RecordedResponse {
+    fn into_response(self) -> Result<Response, Error> {
+        let mut response = http::Response::builder().status(self.status);
+        for (name, value) in &self.headers {
+            response = response.header(name, value);
+        }
+        Ok(response.body(self.body.into_bytes())?)
+    }
+}
+
+/// A request or response body.
+///
+/// JSON request bodies are stored as JSON, to keep fixtures readable and so that
+/// they compare equal regardless of formatting. Response bodies are stored exactly, so
+/// that they're replayed byte for byte (and agree with the recorded `Content-Length`).
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Body {
+    #[default]
+    Empty,
+    Json(Value),
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+impl From<&[u8]> for Body {
+    fn from(bytes: &[u8]) -> Self {
+        if bytes.is_empty() {
+            Body::Empty
+        } else if let Ok(json) = serde_json::from_slice(bytes) {
+            Body::Json(json)
+        } else {
+            Body::Text(String::from_utf8_lossy(bytes).into_owned())
+        }
+    }
+}
+
+impl Body {
+    /// The body exactly as it is, rather than as JSON
+    fn exact(bytes: &[u8]) -> Self {
+        if bytes.is_empty() {
+            Body::Empty
+        } else {
+            match String::from_utf8(bytes.to_vec()) {
+                Ok(text) => Body::Text(text),
+                Err(e) => Body::Binary(e.into_bytes()),
+            }
+        }
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        match self {
+            Body::Empty => Vec::new(),
+            Body::Json(json) => json.to_string().into_bytes(),
+            Body::Text(text) => text.into_bytes(),
+            Body::Binary(bytes) => bytes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A test double which counts requests, and returns the count in the response
+    #[derive(Default)]
+    struct Counter(AtomicUsize);
+
+    impl Transport for Counter {
+        fn execute(&self, _request: Request) -> BoxFuture<'_, Result<Response, Error>> {
+            let count = self.0.fetch_add(1, Ordering::SeqCst) + 1;
+            // formatted the way CouchDB formats it, which serde_json wouldn't reproduce
+            let body = format!("{{\"ok\":true, \"count\":{}}}\n", count).into_bytes();
+            Box::pin(async move {
+                Ok(http::Response::builder()
+                    .status(201)
+                    .header("etag", "\"1-abc\"")
+                    .header("content-length", body.len())
+                    .body(body)?)
+            })
+        }
+    }
+
+    fn request(uri: &str, body: &str) -> Request {
+        http::Request::builder()
+            .method("PUT")
+            .uri(uri)
+            .body(body.as_bytes().to_vec())
+            .unwrap()
+    }
+
+    fn fixture_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("chesterfield-{}-{}.json", name, std::process::id()))
+    }
+
+    #[tokio::test]
+    async fn record_and_replay() {
+        let path = fixture_path("record-and-replay");
+
+        let recorder = Recorder::new(Counter::default(), &path);
+        recorder
+            .execute(request(
+                "http://localhost:5984/db/doc/?b=2&a=1",
+                r#"{"x": 1}"#,
+            ))
+            .await
+            .unwrap();
+        recorder
+            .execute(request(
+                "http://localhost:5984/db/doc/?b=2&a=1",
+                r#"{"x": 1}"#,
+            ))
+            .await
+            .unwrap();
+        drop(recorder);
+
+        let replayer = Replayer::from_file(&path).unwrap();
+        assert_eq!(replayer.remaining(), 2);
+
+        // a different host, query order and body formatting all still match
+        let response = replayer
+            .execute(request(
+                "http://example.com/db/doc?a=1&b=2",
+                r#"{ "x" : 1 }"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 201);
+        assert_eq!(response.headers()["etag"], "\"1-abc\"");
+        assert_eq!(response.body(), b"{\"ok\":true, \"count\":1}\n");
+        assert_eq!(
+            response.headers()["content-length"],
+            response.body().len().to_string()
+        );
+
+        // identical requests are served in the order they were recorded
+        let response = replayer
+            .execute(request("http://example.com/db/doc", r#"{"x":1}"#))
+            .await;
+        assert!(response.is_err());
+        let response = replayer
+            .execute(request("http://example.com/db/doc?a=1&b=2", r#"{"x":1}"#))
+            .await
+            .unwrap();
+        assert_eq!(response.body(), b"{\"ok\":true, \"count\":2}\n");
+        assert_eq!(replayer.remaining(), 0);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn unexpected_requests_fail() {
+        let path = fixture_path("unexpected");
+        Recorder::new(Counter::default(), &path).save().unwrap();
+
+        let replayer = Replayer::from_file(&path).unwrap();
+        let error = replayer
+            .execute(request("http://localhost:5984/db", ""))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "transport error: no recorded response for PUT /db"
+        );
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "1 recorded exchange(s) were never requested: PUT /db/doc")]
+    async fn unused_exchanges_fail() {
+        let path = fixture_path("unused");
+        let recorder = Recorder::new(Counter::default(), &path);
+        recorder
+            .execute(request("http://localhost:5984/db/doc", "{}"))
+            .await
+            .unwrap();
+        drop(recorder);
+
+        let replayer = Replayer::from_file(&path).unwrap();
+        fs::remove_file(path).unwrap();
+        drop(replayer);
+    }
+}
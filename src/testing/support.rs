@@ -0,0 +1,62 @@
+//! Fixtures shared by the crate's own tests.
+
+use super::FakeTransport;
+use crate::{Client, Database, DocId, Document, Revision};
+use serde::{Deserialize, Serialize};
+
+/// A client of a new fake server, and a database which has been created on it
+pub(crate) async fn fake_database(name: &str) -> (Client, Database) {
+    let client = FakeTransport::new().client();
+    let database = client.database(name).unwrap();
+    database.create().send().await.unwrap();
+    (client, database)
+}
+
+/// A document which carries its own id and revision, and can be marked deleted
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct Item {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub(crate) id: Option<DocId>,
+    #[serde(rename = "_rev", skip_serializing_if = "Option::is_none")]
+    pub(crate) rev: Option<Revision>,
+    #[serde(
+        rename = "_deleted",
+        default,
+        skip_serializing_if = "std::ops::Not::not"
+    )]
+    pub(crate) deleted: bool,
+    #[serde(default)]
+    pub(crate) count: u32,
+}
+
+impl Item {
+    /// A new, unsaved item
+    pub(crate) fn new(count: u32) -> Self {
+        Item {
+            count,
+            ..Item::default()
+        }
+    }
+}
+
+impl Document for Item {
+    fn id(&self) -> Option<&DocId> {
+        self.id.as_ref()
+    }
+
+    fn set_id(&mut self, id: DocId) {
+        self.id = Some(id);
+    }
+
+    fn rev(&self) -> Option<&Revision> {
+        self.rev.as_ref()
+    }
+
+    fn set_rev(&mut self, rev: Revision) {
+        self.rev = Some(rev);
+    }
+
+    fn deleted(&self) -> bool {
+        self.deleted
+    }
+}
@@ -0,0 +1,414 @@
+//! Pluggable HTTP transports.
+//!
+//! A [Client](crate::Client) doesn't talk to the network itself. Instead it builds
+//! plain [http] requests and hands them to a [Transport] to execute. The default
+//! transport is backed by [reqwest](https://docs.rs/reqwest), but you can swap in
+//! [hyper](https://docs.rs/hyper) (with the `hyper` feature), or your own implementation.
+//!
+//! Because a transport is just a trait object, it's also a convenient place to
+//! hang middleware- logging, request signing, metrics, test doubles and so on. One such
+//! middleware is built in: [CachingTransport], which caches documents by ETag.
+//!
+//! # Example
+//! ```
+//! use chesterfield::transport::{BoxFuture, Request, Response, Transport};
+//! use chesterfield::{Client, Error, Url};
+//!
+//! /// A transport which logs each request before handing it on
+//! struct Logging<T>(T);
+//!
+//! impl<T: Transport> Transport for Logging<T> {
+//!     fn execute(&self, request: Request) -> BoxFuture<'_, Result<Response, Error>> {
+//!         println!("{} {}", request.method(), request.uri());
+//!         self.0.execute(request)
+//!     }
+//! }
+//!
+//! # #[cfg(feature = "reqwest")]
+//! # {
+//! use chesterfield::transport::ReqwestTransport;
+//!
+//! let url = Url::parse("http://localhost:5984").unwrap();
+//! let transport = Logging(ReqwestTransport::new().unwrap());
+//! let client = Client::with_transport(url, transport);
+//! # }
+//! ```
+
+use crate::error::ErrorResponse;
+use crate::{Error, Url};
+use http::header::{HeaderName, HeaderValue, CONTENT_LENGTH, CONTENT_TYPE, ETAG};
+use http::{Extensions, HeaderMap, Method, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+mod cache;
+#[cfg(feature = "hyper")]
+mod hyper;
+#[cfg(feature = "reqwest")]
+mod reqwest;
+
+pub(crate) use self::cache::Cacheable;
+pub use self::cache::{CachingTransport, DocumentCache};
+#[cfg(feature = "hyper")]
+pub use self::hyper::HyperTransport;
+#[cfg(feature = "reqwest")]
+pub use self::reqwest::ReqwestTransport;
+
+/// The transport used by [Client::new](crate::Client::new).
+///
+/// This is [ReqwestTransport] when the `reqwest` feature is enabled (the default),
+/// otherwise [HyperTransport].
+#[cfg(feature = "reqwest")]
+pub type DefaultTransport = ReqwestTransport;
+
+/// The transport used by [Client::new](crate::Client::new).
+///
+/// This is [ReqwestTransport] when the `reqwest` feature is enabled (the default),
+/// otherwise [HyperTransport].
+#[cfg(all(feature = "hyper", not(feature = "reqwest")))]
+pub type DefaultTransport = HyperTransport;
+
+/// An owned, boxed future, as returned by [Transport::execute].
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// An HTTP request, as built by the client.
+pub type Request = http::Request<Vec<u8>>;
+
+/// An HTTP response, as returned by a [Transport].
+///
+/// The body should be fully buffered.
+pub type Response = http::Response<Vec<u8>>;
+
+/// Something which can execute HTTP requests.
+pub trait Transport: Send + Sync + 'static {
+    /// Send the request, and return the response.
+    ///
+    /// Non-success status codes are *not* errors at this level- they should be
+    /// returned as a normal response.
+    fn execute(&self, request: Request) -> BoxFuture<'_, Result<Response, Error>>;
+
+    /// Wait for the given duration, such as between retries of a conflicting write.
+    ///
+    /// The default doesn't depend on any async runtime: it parks a thread for the
+    /// duration. Transports which run on a particular runtime should use its timer instead.
+    fn sleep(&self, duration: Duration) -> BoxFuture<'_, ()> {
+        Box::pin(ThreadSleep::new(duration))
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for Arc<T> {
+    fn execute(&self, request: Request) -> BoxFuture<'_, Result<Response, Error>> {
+        (**self).execute(request)
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'_, ()> {
+        (**self).sleep(duration)
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for Box<T> {
+    fn execute(&self, request: Request) -> BoxFuture<'_, Result<Response, Error>> {
+        (**self).execute(request)
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'_, ()> {
+        (**self).sleep(duration)
+    }
+}
+
+/// A timer which wakes its task from a thread of its own, for runtime-agnostic sleeps
+struct ThreadSleep {
+    duration: Duration,
+    state: Option<Arc<Mutex<SleepState>>>,
+}
+
+struct SleepState {
+    done: bool,
+    waker: Option<Waker>,
+}
+
+impl ThreadSleep {
+    fn new(duration: Duration) -> Self {
+        ThreadSleep {
+            duration,
+            state: None,
+        }
+    }
+}
+
+impl Future for ThreadSleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.duration.is_zero() {
+            return Poll::Ready(());
+        }
+
+        if let Some(state) = &self.state {
+            let mut state = state.lock().unwrap_or_else(PoisonError::into_inner);
+            if state.done {
+                return Poll::Ready(());
+            }
+            state.waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+
+        let state = Arc::new(Mutex::new(SleepState {
+            done: false,
+            waker: Some(cx.waker().clone()),
+        }));
+        let timer = Arc::clone(&state);
+        let duration = self.duration;
+        std::thread::spawn(move || {
+            std::thread::sleep(duration);
+            let mut state = timer.lock().unwrap_or_else(PoisonError::into_inner);
+            state.done = true;
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        });
+        self.state = Some(state);
+        Poll::Pending
+    }
+}
+
+/// Builder for requests that are sent through a [Transport].
+///
+/// This deliberately mirrors the bits of the `reqwest` builder API that the
+/// rest of the crate uses.
+pub(crate) struct RequestBuilder {
+    transport: Arc<dyn Transport>,
+    method: Method,
+    url: Url,
+    headers: HeaderMap,
+    body: Vec<u8>,
+    extensions: Extensions,
+    error: Option<Error>,
+}
+
+impl RequestBuilder {
+    pub(crate) fn new(transport: Arc<dyn Transport>, method: Method, url: Url) -> Self {
+        RequestBuilder {
+            transport,
+            method,
+            url,
+            headers: HeaderMap::new(),
+            body: Vec::new(),
+            extensions: Extensions::new(),
+            error: None,
+        }
+    }
+
+    /// Append the serialised query parameters to the URL
+    pub(crate) fn query<T: Serialize + ?Sized>(mut self, query: &T) -> Self {
+        match serde_urlencoded::to_string(query) {
+            Ok(encoded) => {
+                if !encoded.is_empty() {
+                    let query = match self.url.query() {
+                        Some(existing) if !existing.is_empty() => {
+                            format!("{}&{}", existing, encoded)
+                        }
+                        _ => encoded,
+                    };
+                    self.url.set_query(Some(&query));
+                }
+            }
+            Err(e) => self.error = Some(e.into()),
+        }
+        self
+    }
+
+    /// Set a header on the request
+    pub(crate) fn header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    /// Attach a value to the request, for middleware such as [CachingTransport]
+    pub(crate) fn extension<T: Clone + Send + Sync + 'static>(mut self, value: T) -> Self {
+        self.extensions.insert(value);
+        self
+    }
+
+    /// Serialise the body as JSON
+    pub(crate) fn json<T: Serialize + ?Sized>(mut self, json: &T) -> Self {
+        match serde_json::to_vec(json) {
+            Ok(body) => {
+                self.body = body;
+                self.headers
+                    .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+            }
+            Err(e) => self.error = Some(e.into()),
+        }
+        self
+    }
+
+    /// Build the request and send it using the transport
+    pub(crate) async fn send(self) -> Result<ResponseExt, Error> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+
+        let mut request = http::Request::builder()
+            .method(self.method)
+            .uri(self.url.as_str())
+            .body(self.body)?;
+        *request.headers_mut() = self.headers;
+        *request.extensions_mut() = self.extensions;
+
+        let response = self.transport.execute(request).await?;
+        Ok(ResponseExt(response))
+    }
+}
+
+/// A thin wrapper around a [Response] with a couple of convenience methods.
+pub(crate) struct ResponseExt(Response);
+
+impl ResponseExt {
+    pub(crate) fn status(&self) -> StatusCode {
+        self.0.status()
+    }
+
+    /// The entity tag of the response, without its quotes.
+    ///
+    /// For a document, this is its revision.
+    pub(crate) fn etag(&self) -> Option<&str> {
+        let etag = self.0.headers().get(ETAG)?.to_str().ok()?;
+        let etag = etag.strip_prefix("W/").unwrap_or(etag);
+        Some(etag.trim_matches('"'))
+    }
+
+    /// The `Content-Length` of the response, which for a `HEAD` request is the length of
+    /// the body that wasn't sent
+    pub(crate) fn content_length(&self) -> Option<u64> {
+        self.0
+            .headers()
+            .get(CONTENT_LENGTH)?
+            .to_str()
+            .ok()?
+            .parse()
+            .ok()
+    }
+
+    /// Deserialise the body as JSON.
+    ///
+    /// If CouchDB responded with an error status, the error is returned instead.
+    pub(crate) fn json<T: DeserializeOwned>(self) -> Result<T, Error> {
+        let response = self.error_for_status()?;
+        Ok(serde_json::from_slice(response.0.body())?)
+    }
+
+    /// The body as text.
+    ///
+    /// If CouchDB responded with an error status, the error is returned instead.
+    pub(crate) fn text(self) -> Result<String, Error> {
+        let response = self.error_for_status()?;
+        Ok(String::from_utf8_lossy(response.0.body()).into_owned())
+    }
+
+    /// Deserialise the body as JSON, whatever the status.
+    ///
+    /// This is for the few endpoints which use an error status for a response that isn't
+    /// an error, such as `_up` in maintenance mode.
+    pub(crate) fn json_unchecked<T: DeserializeOwned>(&self) -> Result<T, Error> {
+        Ok(serde_json::from_slice(self.0.body())?)
+    }
+
+    /// Turn an error status into an [Error::CouchDb](crate::Error::CouchDb)
+    pub(crate) fn error_for_status(self) -> Result<Self, Error> {
+        let status = self.status();
+        // a 304 has no body to deserialise, so it's reported like an error
+        if status.is_success() || (status.is_redirection() && status != StatusCode::NOT_MODIFIED) {
+            return Ok(self);
+        }
+        let body = serde_json::from_slice(self.0.body()).unwrap_or_else(|_| ErrorResponse {
+            error: status
+                .canonical_reason()
+                .unwrap_or("unknown")
+                .to_lowercase(),
+            reason: String::from_utf8_lossy(self.0.body()).into_owned(),
+        });
+        Err(Error::CouchDb(status, body))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// A test double which records requests and returns a canned response
+    #[derive(Default)]
+    struct Recorder {
+        requests: Mutex<Vec<Request>>,
+    }
+
+    impl Transport for Recorder {
+        fn execute(&self, request: Request) -> BoxFuture<'_, Result<Response, Error>> {
+            self.requests.lock().unwrap().push(request);
+            Box::pin(async { Ok(http::Response::new(br#"{"ok":true}"#.to_vec())) })
+        }
+    }
+
+    #[derive(Serialize)]
+    struct Query {
+        rev: &'static str,
+    }
+
+    #[tokio::test]
+    async fn builds_requests() {
+        let recorder = Arc::new(Recorder::default());
+        let url = Url::parse("http://localhost:5984/db/doc?batch=ok").unwrap();
+
+        let response: serde_json::Value = RequestBuilder::new(recorder.clone(), Method::PUT, url)
+            .query(&Query { rev: "1-abc" })
+            .json(&serde_json::json!({"field": 1}))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .unwrap();
+
+        assert_eq!(response, serde_json::json!({"ok": true}));
+
+        let requests = recorder.requests.lock().unwrap();
+        let request = &requests[0];
+        assert_eq!(request.method(), Method::PUT);
+        assert_eq!(
+            request.uri(),
+            "http://localhost:5984/db/doc?batch=ok&rev=1-abc"
+        );
+        assert_eq!(request.headers()[CONTENT_TYPE], "application/json");
+        assert_eq!(request.body(), br#"{"field":1}"#);
+    }
+
+    #[test]
+    fn error_responses() {
+        let mut response = http::Response::new(
+            br#"{"error":"conflict","reason":"Document update conflict."}"#.to_vec(),
+        );
+        *response.status_mut() = StatusCode::CONFLICT;
+
+        let error = ResponseExt(response)
+            .json::<serde_json::Value>()
+            .unwrap_err();
+        assert!(error.is_conflict());
+        assert_eq!(
+            error.to_string(),
+            "couchdb error (409 Conflict): conflict: Document update conflict."
+        );
+
+        let mut response = http::Response::new(b"Bad Gateway".to_vec());
+        *response.status_mut() = StatusCode::BAD_GATEWAY;
+
+        let error = ResponseExt(response)
+            .json::<serde_json::Value>()
+            .unwrap_err();
+        assert_eq!(error.status(), Some(StatusCode::BAD_GATEWAY));
+    }
+}
@@ -0,0 +1,530 @@
+use super::{BoxFuture, Request, Response, Transport};
+use crate::{Error, Url};
+use http::header::{ETAG, IF_NONE_MATCH};
+use http::{HeaderMap, HeaderValue, Method, StatusCode};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// The number of responses a [DocumentCache] holds, by default
+const DEFAULT_MAX_ENTRIES: usize = 1024;
+
+/// The total size of the response bodies a [DocumentCache] holds, by default
+const DEFAULT_MAX_BYTES: usize = 16 * 1024 * 1024;
+
+/// A client-side cache of document `GET` responses, keyed by URL and validated by ETag.
+///
+/// The cache is used by wrapping a transport in a [CachingTransport]. Each cached
+/// response is revalidated with an `If-None-Match` request, so a cached document is
+/// only ever returned when CouchDB has confirmed (with `304 Not Modified`) that it's
+/// still current- what the cache saves is the download and the work of producing it.
+///
+/// When the cache is full, the least recently used responses are evicted. The cache is
+/// a cheap handle, so it can be cloned and kept to [invalidate](DocumentCache::invalidate)
+/// entries explicitly.
+///
+/// # Example
+/// ```
+/// # #[cfg(feature = "reqwest")]
+/// # {
+/// use chesterfield::transport::{CachingTransport, DocumentCache, ReqwestTransport};
+/// use chesterfield::{Client, Url};
+///
+/// let cache = DocumentCache::new().max_entries(100).max_bytes(1024 * 1024);
+/// let transport = CachingTransport::new(ReqwestTransport::new().unwrap(), cache.clone());
+///
+/// let url = Url::parse("http://localhost:5984").unwrap();
+/// let client = Client::with_transport(url, transport);
+/// let database = client.database("items").unwrap();
+///
+/// // forget everything cached from the database
+/// cache.invalidate(database.url());
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct DocumentCache {
+    state: Arc<Mutex<CacheState>>,
+}
+
+#[derive(Default)]
+struct CacheState {
+    max_entries: usize,
+    max_bytes: usize,
+    bytes: usize,
+    /// Incremented on each use, to order the entries from least to most recently used
+    clock: u64,
+    entries: HashMap<String, Entry>,
+    recency: BTreeMap<u64, String>,
+}
+
+struct Entry {
+    etag: HeaderValue,
+    headers: HeaderMap,
+    body: Vec<u8>,
+    last_used: u64,
+}
+
+impl DocumentCache {
+    /// Create a new, empty cache.
+    pub fn new() -> Self {
+        let state = CacheState {
+            max_entries: DEFAULT_MAX_ENTRIES,
+            max_bytes: DEFAULT_MAX_BYTES,
+            ..CacheState::default()
+        };
+        DocumentCache {
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    /// The maximum number of responses to hold.
+    ///
+    /// Default is 1024.
+    pub fn max_entries(self, max_entries: usize) -> Self {
+        self.with_state(|state| {
+            state.max_entries = max_entries;
+            state.evict();
+        });
+        self
+    }
+
+    /// The maximum total size of the response bodies to hold, in bytes. Responses which
+    /// are bigger than this on their own are never cached.
+    ///
+    /// Default is 16MiB.
+    pub fn max_bytes(self, max_bytes: usize) -> Self {
+        self.with_state(|state| {
+            state.max_bytes = max_bytes;
+            state.evict();
+        });
+        self
+    }
+
+    /// The number of responses in the cache
+    pub fn len(&self) -> usize {
+        self.with_state(|state| state.entries.len())
+    }
+
+    /// Whether the cache is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Remove every response for the given URL, and for any URL beneath it.
+    ///
+    /// Query strings are ignored- so invalidating a document's URL removes every cached
+    /// variant of it, and invalidating a database's URL removes everything cached from
+    /// that database.
+    pub fn invalidate(&self, url: &Url) {
+        let prefix = without_query(url.as_str())
+            .trim_end_matches('/')
+            .to_string();
+        self.with_state(|state| {
+            state.remove_where(|key| {
+                let key = without_query(key);
+                key == prefix
+                    || key
+                        .strip_prefix(prefix.as_str())
+                        .is_some_and(|rest| rest.starts_with('/'))
+            })
+        });
+    }
+
+    /// Remove every response from the cache.
+    pub fn clear(&self) {
+        self.with_state(|state| state.remove_where(|_| true));
+    }
+
+    fn with_state<R>(&self, f: impl FnOnce(&mut CacheState) -> R) -> R {
+        // the state is always left consistent, so a panic elsewhere doesn't spoil it
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        f(&mut state)
+    }
+}
+
+impl Default for DocumentCache {
+    fn default() -> Self {
+        DocumentCache::new()
+    }
+}
+
+impl CacheState {
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    /// The ETag of a cached response, marking it as recently used
+    fn etag(&mut self, key: &str) -> Option<HeaderValue> {
+        let now = self.tick();
+        let entry = self.entries.get_mut(key)?;
+        self.recency.remove(&entry.last_used);
+        self.recency.insert(now, key.to_string());
+        entry.last_used = now;
+        Some(entry.etag.clone())
+    }
+
+    fn response(&self, key: &str) -> Option<Response> {
+        let entry = self.entries.get(key)?;
+        let mut response = http::Response::new(entry.body.clone());
+        *response.headers_mut() = entry.headers.clone();
+        Some(response)
+    }
+
+    fn insert(&mut self, key: String, etag: HeaderValue, headers: HeaderMap, body: Vec<u8>) {
+        self.remove(&key);
+        if body.len() > self.max_bytes || self.max_entries == 0 {
+            return;
+        }
+
+        let now = self.tick();
+        self.bytes += body.len();
+        self.recency.insert(now, key.clone());
+        self.entries.insert(
+            key,
+            Entry {
+                etag,
+                headers,
+                body,
+                last_used: now,
+            },
+        );
+        self.evict();
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.bytes -= entry.body.len();
+            self.recency.remove(&entry.last_used);
+        }
+    }
+
+    fn remove_where(&mut self, mut f: impl FnMut(&str) -> bool) {
+        let keys: Vec<String> = self.entries.keys().filter(|key| f(key)).cloned().collect();
+        for key in keys {
+            self.remove(&key);
+        }
+    }
+
+    /// Evict the least recently used responses until the cache is within its limits
+    fn evict(&mut self) {
+        while self.entries.len() > self.max_entries || self.bytes > self.max_bytes {
+            let key = match self.recency.values().next() {
+                Some(key) => key.clone(),
+                None => return,
+            };
+            self.remove(&key);
+        }
+    }
+}
+
+/// A copy of a `GET` request, without any `If-None-Match` header
+fn unconditional(request: &Request) -> Request {
+    let mut copy = http::Request::new(request.body().clone());
+    *copy.method_mut() = request.method().clone();
+    *copy.uri_mut() = request.uri().clone();
+    *copy.version_mut() = request.version();
+    *copy.headers_mut() = request.headers().clone();
+    copy.headers_mut().remove(IF_NONE_MATCH);
+    copy.extensions_mut().insert(Cacheable);
+    copy
+}
+
+fn without_query(url: &str) -> &str {
+    url.split(['?', '#']).next().unwrap_or(url)
+}
+
+/// Marks a request for a document, whose response a [CachingTransport] may cache
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Cacheable;
+
+/// A [Transport] which caches document `GET` responses in a [DocumentCache].
+///
+/// Only the responses to [Database::get](crate::Database::get) are cached- other `GET`
+/// requests (views, changes feeds, server endpoints and so on) are passed through
+/// untouched, as are requests which already carry an `If-None-Match` header, so that an
+/// explicitly conditional request still sees the `304 Not Modified`. Any other request
+/// (`PUT`, `DELETE` and so on) invalidates the cached responses for its URL.
+pub struct CachingTransport<T> {
+    inner: T,
+    cache: DocumentCache,
+}
+
+impl<T: Transport> CachingTransport<T> {
+    /// Wrap a transport, caching its responses in the given cache.
+    pub fn new(inner: T, cache: DocumentCache) -> Self {
+        CachingTransport { inner, cache }
+    }
+
+    /// The cache used by the transport
+    pub fn cache(&self) -> &DocumentCache {
+        &self.cache
+    }
+}
+
+impl<T: Transport> Transport for CachingTransport<T> {
+    fn execute(&self, mut request: Request) -> BoxFuture<'_, Result<Response, Error>> {
+        Box::pin(async move {
+            let key = request.uri().to_string();
+
+            if request.method() != Method::GET {
+                if request.method() != Method::HEAD {
+                    self.cache.with_state(|state| {
+                        state.remove_where(|k| without_query(k) == without_query(&key))
+                    });
+                }
+                return self.inner.execute(request).await;
+            }
+            if request.extensions().get::<Cacheable>().is_none()
+                || request.headers().contains_key(IF_NONE_MATCH)
+            {
+                return self.inner.execute(request).await;
+            }
+
+            let etag = self.cache.with_state(|state| state.etag(&key));
+            let unconditional = etag.as_ref().map(|_| unconditional(&request));
+            if let Some(etag) = etag {
+                request.headers_mut().insert(IF_NONE_MATCH, etag);
+            }
+
+            let mut response = self.inner.execute(request).await?;
+            if let (StatusCode::NOT_MODIFIED, Some(unconditional)) =
+                (response.status(), unconditional)
+            {
+                match self.cache.with_state(|state| state.response(&key)) {
+                    Some(cached) => return Ok(cached),
+                    // evicted while the request was in flight, so fetch it again in full
+                    None => response = self.inner.execute(unconditional).await?,
+                }
+            }
+            match response.status() {
+                StatusCode::OK => {
+                    if let Some(etag) = response.headers().get(ETAG) {
+                        let etag = etag.clone();
+                        let headers = response.headers().clone();
+                        let body = response.body().clone();
+                        self.cache
+                            .with_state(|state| state.insert(key, etag, headers, body));
+                    }
+                }
+                _ => self.cache.with_state(|state| state.remove(&key)),
+            }
+            Ok(response)
+        })
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'_, ()> {
+        self.inner.sleep(duration)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A test double which serves a fixed document, honouring `If-None-Match`
+    #[derive(Default)]
+    struct Server {
+        requests: Mutex<Vec<Request>>,
+    }
+
+    impl Transport for Server {
+        fn execute(&self, request: Request) -> BoxFuture<'_, Result<Response, Error>> {
+            let fresh = request
+                .headers()
+                .get(IF_NONE_MATCH)
+                .is_some_and(|etag| etag == "\"1-abc\"");
+            self.requests.lock().unwrap().push(request);
+
+            Box::pin(async move {
+                let mut response = if fresh {
+                    http::Response::new(Vec::new())
+                } else {
+                    http::Response::new(br#"{"_id":"a","_rev":"1-abc"}"#.to_vec())
+                };
+                if fresh {
+                    *response.status_mut() = StatusCode::NOT_MODIFIED;
+                }
+                response
+                    .headers_mut()
+                    .insert(ETAG, HeaderValue::from_static("\"1-abc\""));
+                Ok(response)
+            })
+        }
+    }
+
+    fn get(url: &str) -> Request {
+        let mut request = http::Request::get(url).body(Vec::new()).unwrap();
+        request.extensions_mut().insert(Cacheable);
+        request
+    }
+
+    /// A test double which empties the cache whenever a conditional request is in flight
+    struct Evicting {
+        server: Server,
+        cache: DocumentCache,
+    }
+
+    impl Transport for Evicting {
+        fn execute(&self, request: Request) -> BoxFuture<'_, Result<Response, Error>> {
+            if request.headers().contains_key(IF_NONE_MATCH) {
+                self.cache.clear();
+            }
+            self.server.execute(request)
+        }
+    }
+
+    #[tokio::test]
+    async fn revalidates_cached_responses() {
+        let server = Arc::new(Server::default());
+        let cache = DocumentCache::new();
+        let transport = CachingTransport::new(Arc::clone(&server), cache.clone());
+
+        let first = transport.execute(get("http://couch/db/a")).await.unwrap();
+        let second = transport.execute(get("http://couch/db/a")).await.unwrap();
+        assert_eq!(second.status(), StatusCode::OK);
+        assert_eq!(second.body(), first.body());
+        assert_eq!(cache.len(), 1);
+
+        let requests = server.requests.lock().unwrap();
+        assert!(!requests[0].headers().contains_key(IF_NONE_MATCH));
+        assert_eq!(requests[1].headers()[IF_NONE_MATCH], "\"1-abc\"");
+    }
+
+    #[tokio::test]
+    async fn refetches_evicted_responses() {
+        let cache = DocumentCache::new();
+        let evicting = Arc::new(Evicting {
+            server: Server::default(),
+            cache: cache.clone(),
+        });
+        let transport = CachingTransport::new(Arc::clone(&evicting), cache.clone());
+
+        let first = transport.execute(get("http://couch/db/a")).await.unwrap();
+        let second = transport.execute(get("http://couch/db/a")).await.unwrap();
+        assert_eq!(second.status(), StatusCode::OK);
+        assert_eq!(second.body(), first.body());
+        assert_eq!(cache.len(), 1);
+
+        let requests = evicting.server.requests.lock().unwrap();
+        assert_eq!(requests.len(), 3);
+        assert!(requests[1].headers().contains_key(IF_NONE_MATCH));
+        assert!(!requests[2].headers().contains_key(IF_NONE_MATCH));
+    }
+
+    #[tokio::test]
+    async fn only_documents_are_cached() {
+        let cache = DocumentCache::new();
+        let transport = CachingTransport::new(Server::default(), cache.clone());
+
+        let request = http::Request::get("http://couch/_uuids")
+            .body(Vec::new())
+            .unwrap();
+        transport.execute(request).await.unwrap();
+        assert!(cache.is_empty());
+    }
+
+    #[tokio::test]
+    async fn explicit_conditions_pass_through() {
+        let cache = DocumentCache::new();
+        let transport = CachingTransport::new(Server::default(), cache.clone());
+
+        transport.execute(get("http://couch/db/a")).await.unwrap();
+        let mut request = get("http://couch/db/a");
+        request
+            .headers_mut()
+            .insert(IF_NONE_MATCH, HeaderValue::from_static("\"1-abc\""));
+        let response = transport.execute(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    }
+
+    #[tokio::test]
+    async fn invalidation_and_limits() {
+        let cache = DocumentCache::new().max_entries(2);
+        let transport = CachingTransport::new(Server::default(), cache.clone());
+
+        for url in &[
+            "http://couch/db/a",
+            "http://couch/db/b",
+            "http://couch/db/c",
+        ] {
+            transport.execute(get(url)).await.unwrap();
+        }
+        // the least recently used entry was evicted
+        assert_eq!(cache.len(), 2);
+        assert!(cache.with_state(|state| !state.entries.contains_key("http://couch/db/a")));
+
+        let delete = http::Request::delete("http://couch/db/b?rev=1-abc")
+            .body(Vec::new())
+            .unwrap();
+        transport.execute(delete).await.unwrap();
+        assert_eq!(cache.len(), 1);
+
+        transport
+            .execute(get("http://couch/other/a"))
+            .await
+            .unwrap();
+        cache.invalidate(&Url::parse("http://couch/db").unwrap());
+        assert_eq!(cache.len(), 1);
+        cache.clear();
+        assert!(cache.is_empty());
+
+        let tiny = DocumentCache::new().max_bytes(4);
+        let transport = CachingTransport::new(Server::default(), tiny.clone());
+        transport.execute(get("http://couch/db/a")).await.unwrap();
+        assert!(tiny.is_empty());
+    }
+
+    #[tokio::test]
+    async fn conditional_gets() {
+        use crate::testing::FakeTransport;
+        use crate::{Client, GetResponse};
+        use serde_json::{json, Value};
+
+        let cache = DocumentCache::new();
+        let transport = CachingTransport::new(FakeTransport::new(), cache.clone());
+        let client = Client::with_transport(Url::parse("http://fake-couchdb/").unwrap(), transport);
+        let database = client.database("items").unwrap();
+        database.create().send().await.unwrap();
+
+        let inserted = database
+            .insert(&json!({ "field": 1 }), String::from("some-id"))
+            .send()
+            .await
+            .unwrap();
+
+        let unchanged = database
+            .get("some-id")
+            .if_none_match(inserted.rev.clone())
+            .send::<Value>()
+            .await;
+        assert!(unchanged.unwrap_err().is_not_modified());
+
+        // the second fetch is answered from the cache, after a 304
+        for _ in 0..2 {
+            let doc: GetResponse = database.get("some-id").send().await.unwrap();
+            assert_eq!(doc.meta_data()._rev, inserted.rev);
+            assert_eq!(doc.into_inner().unwrap()["field"], 1);
+        }
+        database.info().await.unwrap();
+        assert_eq!(cache.len(), 1);
+
+        let updated = database
+            .update(&json!({ "field": 2 }), "some-id", inserted.rev.clone())
+            .send()
+            .await
+            .unwrap();
+        assert!(cache.is_empty());
+        let doc: GetResponse = database
+            .get("some-id")
+            .if_none_match(inserted.rev)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(doc.meta_data()._rev, updated.rev);
+
+        database.get("some-id").send::<Value>().await.unwrap();
+        cache.invalidate(&database.document_url("some-id").unwrap());
+        assert!(cache.is_empty());
+    }
+}
@@ -0,0 +1,61 @@
+use super::{BoxFuture, Request, Response, Transport};
+use crate::Error;
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use hyper_util::client::legacy::connect::{Connect, HttpConnector};
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::TokioExecutor;
+use std::time::Duration;
+
+/// A [Transport] backed by a [hyper](https://docs.rs/hyper) client.
+///
+/// By default this only speaks plain HTTP. If you need TLS, build a hyper client with
+/// a TLS-capable connector (such as `hyper-rustls`) and pass it to [HyperTransport::from_client].
+#[derive(Debug, Clone)]
+pub struct HyperTransport<C = HttpConnector> {
+    client: Client<C, Full<Bytes>>,
+}
+
+impl HyperTransport {
+    /// Create a new transport with a default (HTTP only) hyper client.
+    pub fn new() -> Result<Self, Error> {
+        let client = Client::builder(TokioExecutor::new()).build_http();
+        Ok(HyperTransport { client })
+    }
+}
+
+impl<C> HyperTransport<C> {
+    /// Create a new transport from an existing hyper client.
+    pub fn from_client(client: Client<C, Full<Bytes>>) -> Self {
+        HyperTransport { client }
+    }
+}
+
+impl<C> Transport for HyperTransport<C>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+{
+    fn execute(&self, request: Request) -> BoxFuture<'_, Result<Response, Error>> {
+        Box::pin(async move {
+            let request = request.map(|body| Full::new(Bytes::from(body)));
+            let response = self
+                .client
+                .request(request)
+                .await
+                .map_err(|e| Error::Transport(Box::new(e)))?;
+
+            let (parts, body) = response.into_parts();
+            let body = body
+                .collect()
+                .await
+                .map_err(|e| Error::Transport(Box::new(e)))?
+                .to_bytes();
+
+            Ok(http::Response::from_parts(parts, body.to_vec()))
+        })
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'_, ()> {
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
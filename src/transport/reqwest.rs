@@ -0,0 +1,55 @@
+use super::{BoxFuture, Request, Response, Transport};
+use crate::Error;
+use std::convert::TryFrom;
+use std::time::Duration;
+
+/// A [Transport] backed by an asynchronous [reqwest](https://docs.rs/reqwest) client.
+///
+/// This is the default transport.
+#[derive(Debug, Clone)]
+pub struct ReqwestTransport {
+    client: reqwest::Client,
+}
+
+impl ReqwestTransport {
+    /// Create a new transport with a default reqwest client.
+    ///
+    /// # Errors
+    /// This method fails if the TLS backend fails to initialise
+    pub fn new() -> Result<Self, Error> {
+        let client = reqwest::ClientBuilder::new().build()?;
+        Ok(ReqwestTransport { client })
+    }
+
+    /// Create a new transport from an existing reqwest client.
+    ///
+    /// Use this if you need to configure timeouts, proxies, default headers etc.
+    pub fn from_client(client: reqwest::Client) -> Self {
+        ReqwestTransport { client }
+    }
+}
+
+impl Transport for ReqwestTransport {
+    fn execute(&self, request: Request) -> BoxFuture<'_, Result<Response, Error>> {
+        Box::pin(async move {
+            let request = reqwest::Request::try_from(request)?;
+            let response = self.client.execute(request).await?;
+
+            let status = response.status();
+            let version = response.version();
+            let headers = response.headers().clone();
+            let body = response.bytes().await?;
+
+            let mut response = http::Response::new(body.to_vec());
+            *response.status_mut() = status;
+            *response.version_mut() = version;
+            *response.headers_mut() = headers;
+
+            Ok(response)
+        })
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'_, ()> {
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
@@ -0,0 +1,350 @@
+//! Managing the users in the `_users` database.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::fmt;
+
+use crate::client::Client;
+use crate::{Database, DocId, Error, Revision};
+
+/// The prefix of the id of every user document
+const USER_PREFIX: &str = "org.couchdb.user:";
+
+/// A user, as stored in the `_users` database.
+///
+/// Setting a [password](User::password) and saving the user has CouchDB hash it; the
+/// stored user then has a [derived_key](User::derived_key) and [salt](User::salt) (or, on
+/// old servers, a [password_sha](User::password_sha)) instead. None of these are included in
+/// the `Debug` output.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct User {
+    /// The user's name
+    pub name: String,
+
+    /// The user's roles
+    #[serde(default)]
+    pub roles: Vec<String>,
+
+    /// A new password in plain text, which CouchDB hashes when the user is saved
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+
+    /// How the password is hashed, such as `pbkdf2`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password_scheme: Option<String>,
+
+    /// The number of iterations of the password hash
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iterations: Option<u32>,
+
+    /// The hashed password
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub derived_key: Option<String>,
+
+    /// The salt of the hashed password
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub salt: Option<String>,
+
+    /// The hashed password, for the `simple` scheme of CouchDB 1.x
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password_sha: Option<String>,
+
+    /// Every other field of the user document
+    #[serde(flatten)]
+    pub other: Map<String, Value>,
+
+    #[serde(rename = "type", default = "user_type")]
+    kind: String,
+
+    // the id follows from the name, and the revision is sent separately
+    #[serde(rename = "_id", default, skip_serializing)]
+    _id: Option<String>,
+    #[serde(rename = "_rev", default, skip_serializing)]
+    _rev: Option<Revision>,
+}
+
+fn user_type() -> String {
+    "user".to_string()
+}
+
+impl User {
+    /// Create a new user, with no password or roles
+    pub fn new(name: impl Into<String>) -> Self {
+        User {
+            name: name.into(),
+            roles: Vec::new(),
+            password: None,
+            password_scheme: None,
+            iterations: None,
+            derived_key: None,
+            salt: None,
+            password_sha: None,
+            other: Map::new(),
+            kind: user_type(),
+            _id: None,
+            _rev: None,
+        }
+    }
+
+    /// Set the user's password
+    pub fn with_password(mut self, password: impl Into<String>) -> Self {
+        self.set_password(password);
+        self
+    }
+
+    /// Set the user's roles
+    pub fn with_roles<I, S>(mut self, roles: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.roles = roles.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// The current revision of the user document, if it was retrieved from the server
+    pub fn rev(&self) -> Option<&Revision> {
+        self._rev.as_ref()
+    }
+
+    /// Replace the user's password, discarding the hash of the old one
+    pub fn set_password(&mut self, password: impl Into<String>) {
+        self.password = Some(password.into());
+        self.password_scheme = None;
+        self.iterations = None;
+        self.derived_key = None;
+        self.salt = None;
+        self.password_sha = None;
+    }
+}
+
+impl fmt::Debug for User {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        /// Shows whether a secret is set, but not what it is
+        struct Redacted<'a>(&'a Option<String>);
+
+        impl fmt::Debug for Redacted<'_> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                match self.0 {
+                    Some(_) => f.write_str("Some(<redacted>)"),
+                    None => f.write_str("None"),
+                }
+            }
+        }
+
+        f.debug_struct("User")
+            .field("name", &self.name)
+            .field("roles", &self.roles)
+            .field("password", &Redacted(&self.password))
+            .field("password_scheme", &self.password_scheme)
+            .field("iterations", &self.iterations)
+            .field("derived_key", &Redacted(&self.derived_key))
+            .field("salt", &Redacted(&self.salt))
+            .field("password_sha", &Redacted(&self.password_sha))
+            .field("other", &self.other)
+            .field("rev", &self._rev)
+            .finish()
+    }
+}
+
+/// An interface to the users in the `_users` database, from [Client::users].
+///
+/// Users are identified by name; the `org.couchdb.user:` prefix of their document ids is
+/// added for you. Changes to an existing user are a read-modify-write, which is retried if
+/// somebody else changes the user at the same time.
+///
+/// This requires admin privileges, except that users may read and update their own
+/// document.
+///
+/// # Example
+/// ```no_run
+/// use chesterfield::{Client, User};
+///
+/// # async fn run() {
+/// let client = Client::from_url_str("http://localhost:5984").unwrap();
+/// let users = client.users();
+///
+/// let user = User::new("alice")
+///     .with_password("correct horse battery staple")
+///     .with_roles(["staff"]);
+/// users.create(&user).await.unwrap();
+///
+/// users.add_role("alice", "ops").await.unwrap();
+/// users.set_password("alice", "tr0ub4dor&3").await.unwrap();
+/// # }
+/// ```
+pub struct Users {
+    database: Database,
+}
+
+impl Users {
+    pub(crate) fn new(client: &Client) -> Result<Self, Error> {
+        Ok(Users {
+            database: client.root().database("_users")?,
+        })
+    }
+
+    /// The id of a user's document, such as `org.couchdb.user:alice`
+    pub fn id(name: &str) -> Result<DocId, Error> {
+        DocId::new(format!("{}{}", USER_PREFIX, name))
+    }
+
+    /// Create a new user, returning the revision of their document.
+    ///
+    /// # Errors
+    /// If the user already exists, this fails with an error for which
+    /// [is_conflict](Error::is_conflict) is true.
+    pub async fn create(&self, user: &User) -> Result<Revision, Error> {
+        let id = Users::id(&user.name)?;
+        let response = self.database.insert(user, id.to_string()).send().await?;
+        Ok(response.rev)
+    }
+
+    /// Retrieve a user.
+    pub async fn get(&self, name: &str) -> Result<User, Error> {
+        let response = self.database.get(Users::id(name)?).send::<User>().await?;
+        response
+            .into_inner()
+            .ok_or_else(|| Error::InvalidDocId(format!("user '{}' has no document", name)))
+    }
+
+    /// Replace a user's password, returning the new revision of their document.
+    pub async fn set_password(&self, name: &str, password: &str) -> Result<Revision, Error> {
+        self.modify(name, |user| user.set_password(password)).await
+    }
+
+    /// Replace a user's roles, returning the new revision of their document.
+    pub async fn set_roles<I, S>(&self, name: &str, roles: I) -> Result<Revision, Error>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let roles: Vec<String> = roles.into_iter().map(Into::into).collect();
+        self.modify(name, |user| user.roles = roles.clone()).await
+    }
+
+    /// Give a user a role (if they don't already have it), returning the new revision of
+    /// their document.
+    pub async fn add_role(&self, name: &str, role: &str) -> Result<Revision, Error> {
+        self.modify(name, |user| {
+            if !user.roles.iter().any(|existing| existing == role) {
+                user.roles.push(role.to_string());
+            }
+        })
+        .await
+    }
+
+    /// Take a role away from a user, returning the new revision of their document.
+    pub async fn remove_role(&self, name: &str, role: &str) -> Result<Revision, Error> {
+        self.modify(name, |user| user.roles.retain(|existing| existing != role))
+            .await
+    }
+
+    /// Modify a user, retrying if somebody else modifies them at the same time.
+    ///
+    /// See [Database::modify](crate::Database::modify).
+    pub async fn modify<F>(&self, name: &str, modify: F) -> Result<Revision, Error>
+    where
+        F: FnMut(&mut User),
+    {
+        self.database.modify(Users::id(name)?, modify).send().await
+    }
+
+    /// Delete a user.
+    pub async fn delete(&self, name: &str) -> Result<(), Error> {
+        let user = self.get(name).await?;
+        let rev = user
+            .rev()
+            .cloned()
+            .ok_or_else(|| Error::InvalidRevision(format!("user '{}' has no revision", name)))?;
+        self.database.delete(Users::id(name)?, rev).send().await?;
+        Ok(())
+    }
+}
+
+impl Client {
+    /// An interface to the users in the `_users` database.
+    pub fn users(&self) -> Users {
+        Users::new(self).expect("_users is a valid database name")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fake_database;
+    use serde_json::json;
+
+    #[test]
+    fn secrets_are_redacted() {
+        let user: User = serde_json::from_value(json!({
+            "_id": "org.couchdb.user:alice",
+            "_rev": "1-abc",
+            "type": "user",
+            "name": "alice",
+            "roles": ["staff"],
+            "password_scheme": "pbkdf2",
+            "iterations": 10,
+            "derived_key": "3f8c5e1b2d",
+            "salt": "a1b2c3d4",
+            "email": "alice@example.com",
+        }))
+        .unwrap();
+        assert_eq!(user.rev().unwrap().to_string(), "1-abc");
+
+        let debug = format!("{:?}", user.clone().with_password("hunter2"));
+        assert!(!debug.contains("hunter2"));
+        assert!(!debug.contains("3f8c5e1b2d"));
+        assert!(!debug.contains("a1b2c3d4"));
+        assert!(debug.contains("alice@example.com"));
+
+        let debug = format!("{:?}", user);
+        assert!(!debug.contains("3f8c5e1b2d"));
+        assert!(debug.contains("derived_key: Some(<redacted>)"));
+
+        let value = serde_json::to_value(&user).unwrap();
+        assert_eq!(value["type"], "user");
+        assert!(value.get("_id").is_none());
+        assert!(value.get("_rev").is_none());
+    }
+
+    #[tokio::test]
+    async fn users() {
+        let (client, _) = fake_database("_users").await;
+        let users = client.users();
+
+        let user = crate::User::new("alice")
+            .with_password("hunter2")
+            .with_roles(["staff"]);
+        users.create(&user).await.unwrap();
+        assert!(users.create(&user).await.err().unwrap().is_conflict());
+
+        let stored = users.get("alice").await.unwrap();
+        assert_eq!(stored.roles, vec!["staff"]);
+        assert!(stored.password.is_none());
+        let derived_key = stored.derived_key.clone().unwrap();
+
+        users.add_role("alice", "ops").await.unwrap();
+        users.add_role("alice", "ops").await.unwrap();
+        users.remove_role("alice", "staff").await.unwrap();
+        users.set_password("alice", "correct horse").await.unwrap();
+        let stored = users.get("alice").await.unwrap();
+        assert_eq!(stored.roles, vec!["ops"]);
+        assert_ne!(stored.derived_key.unwrap(), derived_key);
+
+        let raw = client
+            .database("_users")
+            .unwrap()
+            .get("org.couchdb.user:alice")
+            .send::<Value>()
+            .await
+            .unwrap()
+            .into_inner()
+            .unwrap();
+        assert_eq!(raw["type"], "user");
+        assert!(raw.get("password").is_none());
+
+        users.delete("alice").await.unwrap();
+        assert!(users.get("alice").await.err().unwrap().is_not_found());
+    }
+}